@@ -1,27 +1,51 @@
 mod annotated_line;
 mod annotated_line_iterator;
 mod command_bar;
+mod config;
+mod digraph;
 mod document_status;
 mod editor_cmd;
 mod file_type;
+mod fuzzy;
+mod help;
 mod highlighter;
 mod line;
 mod message_bar;
+mod palette;
+mod path_completion;
+mod picker;
+mod recent_files;
+mod shell;
 mod status_bar;
 mod terminal;
+mod theme;
 mod ui_component;
 mod view;
 
-use std::{fmt::Display, io::ErrorKind, time::Duration};
+use std::{
+    collections::HashMap, fmt::Display, io::ErrorKind, io::Write, path::Path, time::Duration,
+};
 
-use crossterm::event::{Event, KeyEvent, KeyEventKind, read};
-use editor_cmd::{EditorCommand, TextCommand};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, poll, read};
+use editor_cmd::{
+    Direction, EditorCommand, ExCommand, PickerCommand, SearchDirection, Surround, TextCommand,
+    VisualCommand, VisualKind,
+};
 use terminal::Terminal;
-use view::View;
+use view::LoadStatus;
+
+pub use line::Line;
+pub use view::{Buffer, View};
 
 use crate::editor::{
     command_bar::{Cmd, CommandBar},
+    config::Config,
+    file_type::FileType,
     message_bar::MessageBar,
+    palette::{Palette, PaletteAction},
+    picker::Picker,
+    recent_files::RecentFiles,
+    shell::Job,
     status_bar::StatusBar,
     terminal::{Position, TerminalSize},
     ui_component::UiComponent,
@@ -29,14 +53,39 @@ use crate::editor::{
 
 const TIMES_TO_QUIT: u8 = 3;
 const MESSAGE_DURATION: Duration = Duration::new(5, 0);
-const DEFAULT_MESSAGE: &str = "HELP: '/' = find | Ctrl-S = save | Ctrl-Q = quit";
+/// How often a load or a command running on the background is checked.
+const BUSY_POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long a frame of smooth scrolling stays on the screen.
+const SCROLL_FRAME: Duration = Duration::from_millis(16);
+/// How long the keyboard has to be idle before the gutter is updated.
+const DIFF_DELAY: Duration = Duration::from_millis(300);
+const DEFAULT_MESSAGE: &str = "HELP: F1 = help | Ctrl-P = commands | Ctrl-S = save | Ctrl-Q = quit";
 
 #[derive(Clone, Copy, Default, PartialEq, Eq)]
 pub enum EditorMode {
     #[default]
     Normal,
     Insert,
+    Visual,
     Command,
+    Picker,
+    Help,
+}
+
+/// Rapresents a command whose output replaces the buffer once it finishes,
+/// it is dropped if the buffer changed in the meantime.
+struct BufferJob {
+    job: Job,
+    input: String,
+    done_message: String,
+}
+
+/// Rapresents what the items of the open picker are.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+enum PickerContent {
+    #[default]
+    RecentFiles,
+    Commands,
 }
 
 impl Display for EditorMode {
@@ -47,49 +96,102 @@ impl Display for EditorMode {
             match &self {
                 EditorMode::Normal => "NORMAL",
                 EditorMode::Insert => "INSERT",
+                EditorMode::Visual => "VISUAL",
                 EditorMode::Command => "COMMAND",
+                EditorMode::Picker => "PICKER",
+                EditorMode::Help => "HELP",
             }
         )
     }
 }
 
 #[derive(Default)]
+#[allow(clippy::struct_excessive_bools)]
 pub struct Editor {
     mode: EditorMode,
     switched_mode: bool,
     should_quit: bool,
     view: View,
+    /// The view of the file while the help is shown in its place.
+    previous_view: Option<View>,
     status_bar: StatusBar,
     message_bar: MessageBar,
     command_bar: CommandBar,
+    picker: Picker,
+    picker_content: PickerContent,
+    palette: Palette,
+    buffer_job: Option<BufferJob>,
+    recent_files: RecentFiles,
     size: TerminalSize,
     pressed_quit: u8,
+    pending_key: Option<char>,
+    pending_count: Option<u32>,
+    pending_literal: Option<String>,
+    pending_digraph: Option<String>,
+    pending_surround: Option<(Surround, String)>,
+    /// The mode the selection started with Shift goes back to.
+    shift_selection_origin: Option<EditorMode>,
+    pending_save_as: Option<String>,
+    quit_after_save: bool,
+    terminal: Terminal,
+    owns_terminal: bool,
+    recording: Option<(char, Vec<Event>)>,
+    macros: HashMap<char, Vec<Event>>,
+    last_macro: Option<char>,
+    replaying: bool,
 }
 
 impl Editor {
+    /// Creates an editor showing the text, without touching
+    /// the terminal nor reading the command line arguments.
+    pub fn from_text(text: &str) -> Self {
+        let mut editor = Self::default();
+        editor.view = View::from_text(text);
+        editor
+    }
+
+    /// Draws the screen on `out` instead of stdout.
+    pub fn set_output(&mut self, out: impl Write + 'static) {
+        self.terminal = Terminal::new(out);
+    }
+
+    pub fn view(&self) -> &View {
+        &self.view
+    }
+
     /// Creates a new instance of the text editor
     /// and sets a panic hook for terminating correcly
     /// even when unwinding during panic.
     pub fn new() -> Result<Self, std::io::Error> {
         let default_hook = std::panic::take_hook();
         std::panic::set_hook(Box::new(move |panic_info| {
-            let _ = Terminal::terminate();
+            let _ = Terminal::default().terminate();
             default_hook(panic_info);
         }));
 
-        Terminal::initialize()?;
         let mut editor = Editor::default();
+        editor.terminal.initialize()?;
+        editor.owns_terminal = true;
+        editor.view.set_config(Config::load());
+        editor.recent_files = RecentFiles::load();
 
-        let args: Vec<String> = std::env::args().collect();
-        let file_name = args.get(1);
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        let launch = LaunchArgs::parse(&args);
         let mut init_message = DEFAULT_MESSAGE.to_string();
-        if let Some(path) = file_name {
+        if let Some(path) = &launch.file_name {
             let res = editor.view.load(path);
             match res {
-                Ok(()) => Terminal::set_title(path)?,
+                Ok(()) => {
+                    editor.terminal.set_title(path)?;
+                    editor.remember_recent_file(path);
+                    editor.show_loading();
+                    if let Some(line_number) = launch.line_number {
+                        editor.view.goto(line_number, launch.column);
+                    }
+                }
                 Err(_) => init_message = format!("ERR: Could not open file: {path}"),
             }
-            Terminal::set_title(path)?;
+            editor.terminal.set_title(path)?;
         }
 
         let size = Terminal::size().unwrap_or_default();
@@ -125,6 +227,11 @@ impl Editor {
             height: 1,
             width: size.width,
         });
+
+        self.picker.resize(TerminalSize {
+            height: size.height.saturating_sub(2),
+            width: size.width,
+        });
     }
 
     /// Runs the editor with a infinite loop that reads
@@ -138,44 +245,196 @@ impl Editor {
                 break;
             }
 
-            let event = read();
-            match event {
-                Ok(event) => self.evaluate_event(event),
-                Err(err) => {
-                    #[cfg(debug_assertions)]
-                    panic!("Unrecognized event, error: {err:?}");
+            // While a file loads or a command runs their progress
+            // is checked every time no event arrives for a while.
+            if self.is_busy() && !poll(BUSY_POLL_INTERVAL).unwrap_or(true) {
+                self.poll_busy();
+            } else if self.view.is_scrolling() && !poll(SCROLL_FRAME).unwrap_or(true) {
+                self.view.scroll_step();
+            } else if self.view.has_stale_diff() && !poll(DIFF_DELAY).unwrap_or(true) {
+                self.view.refresh_diff();
+            } else {
+                let event = read();
+                match event {
+                    Ok(event) => self.evaluate_event(event),
+                    Err(err) => {
+                        #[cfg(debug_assertions)]
+                        panic!("Unrecognized event, error: {err:?}");
+                    }
                 }
             }
 
-            let status = self.view.get_status();
-            self.status_bar.update_status(status);
-            self.status_bar.update_editor_mode(self.mode);
+            self.update_status();
         }
     }
 
+    /// Handles the event like `run` does, without waiting for the terminal
+    /// nor drawing anything, so that the editor can be driven by scripts.
+    /// A command started by the event is waited for and smooth scrolling
+    /// ends at once, since nothing else would check on them.
+    pub fn feed_event(&mut self, event: Event) {
+        self.evaluate_event(event);
+        self.view.finish_scrolling();
+        if let Some(job) = self.buffer_job.take() {
+            let result = job.job.wait();
+            self.finish_buffer_job(&job.input, &job.done_message, result);
+        }
+        self.update_status();
+    }
+
+    /// Shows a spinner with the message in the status bar until
+    /// `clear_busy` is called, it moves while no key is pressed.
+    pub fn set_busy(&mut self, message: &str) {
+        self.status_bar.set_busy(Some(message));
+    }
+
+    pub fn clear_busy(&mut self) {
+        self.status_bar.set_busy(None);
+    }
+
+    fn is_busy(&self) -> bool {
+        self.status_bar.is_busy() || self.view.is_loading() || self.buffer_job.is_some()
+    }
+
+    fn poll_busy(&mut self) {
+        if self.view.is_loading() {
+            self.poll_loading();
+        }
+
+        let result = self.buffer_job.as_ref().and_then(|job| job.job.poll());
+        if let Some(result) = result
+            && let Some(job) = self.buffer_job.take()
+        {
+            self.finish_buffer_job(&job.input, &job.done_message, result);
+        }
+
+        self.status_bar.tick();
+    }
+
+    /// Draws the whole screen returning what would be written to
+    /// the terminal, escape sequences included.
+    pub fn render_frame(&mut self) -> String {
+        self.view.set_needs_redraw(true);
+        self.status_bar.set_needs_redraw(true);
+        self.message_bar.set_needs_redraw(true);
+        self.command_bar.set_needs_redraw(true);
+        self.picker.set_needs_redraw(true);
+
+        self.terminal.start_capture();
+        self.refresh_screen();
+        let output = self.terminal.stop_capture();
+        String::from_utf8_lossy(&output).into_owned()
+    }
+
+    /// The lines of the buffer as they are now.
+    pub fn snapshot(&self) -> Vec<String> {
+        self.view.lines()
+    }
+
+    pub fn should_quit(&self) -> bool {
+        self.should_quit
+    }
+
+    fn update_status(&mut self) {
+        let status = self.view.get_status();
+        self.status_bar.update_status(status);
+        self.status_bar.update_editor_mode(self.mode);
+    }
+
     /// Evaluates an event from the keyboard and resizing
     fn evaluate_event(&mut self, event: Event) {
+        if self.view.is_loading() {
+            self.evaluate_event_while_loading(event);
+            return;
+        }
+
         let should_process = match event {
             Event::Key(KeyEvent { kind, .. }) => kind == KeyEventKind::Press,
-            Event::Resize(_, _) => true,
+            Event::Resize(_, _) | Event::Paste(_) => true,
             _ => false,
         };
 
+        if should_process
+            && !self.replaying
+            && let Some((_, events)) = &mut self.recording
+            && !matches!(event, Event::Resize(_, _))
+        {
+            events.push(event.clone());
+        }
+
+        if let Event::Paste(text) = &event {
+            self.process_paste(text);
+            return;
+        }
+
+        if should_process
+            && self.pending_surround.is_some()
+            && let Event::Key(key) = event
+        {
+            self.process_surround(key);
+            return;
+        }
+
         if should_process {
             match self.mode {
                 EditorMode::Normal => {
-                    if let Ok(cmd) = EditorCommand::try_from(event) {
+                    let cmd = match (self.pending_key, &event) {
+                        (Some(prefix), Event::Key(_)) => {
+                            self.pending_key = None;
+                            EditorCommand::try_from((prefix, event))
+                        }
+                        _ => EditorCommand::try_from(event),
+                    };
+
+                    if let Ok(cmd) = cmd {
                         self.process_normal_command(cmd);
                     }
                 }
                 EditorMode::Insert => {
-                    if let Ok(cmd) = TextCommand::try_from(event) {
-                        self.process_insertion(cmd);
+                    if self.pending_literal.is_some() {
+                        if let Event::Key(key) = event {
+                            self.process_literal(key);
+                        }
+                    } else if self.pending_digraph.is_some() {
+                        if let Event::Key(key) = event {
+                            self.process_digraph(key);
+                        }
+                    } else if let Ok(cmd) = TextCommand::try_from(event) {
+                        self.process_insertion(self.resolve_interrupt(cmd));
+                    }
+                }
+                EditorMode::Visual => {
+                    let cmd = match (self.pending_key.take(), &event) {
+                        (Some(prefix), Event::Key(_)) => VisualCommand::try_from((prefix, event)),
+                        _ => VisualCommand::try_from(event),
+                    };
+
+                    if let Ok(cmd) = cmd {
+                        self.process_visual_command(cmd);
                     }
                 }
                 EditorMode::Command => {
                     if let Ok(cmd) = TextCommand::try_from(event) {
-                        self.process_command(cmd);
+                        self.process_command(self.resolve_interrupt(cmd));
+                    }
+                }
+                EditorMode::Picker => {
+                    if let Event::Resize(width, height) = event {
+                        self.resize(TerminalSize {
+                            width: width.into(),
+                            height: height.into(),
+                        });
+                    } else if self.picker.is_filtered() {
+                        if let Ok(cmd) = TextCommand::try_from(event) {
+                            self.process_picker_query(self.resolve_interrupt(cmd));
+                        }
+                    } else if let Ok(cmd) = PickerCommand::try_from(event) {
+                        self.process_picker_command(cmd);
+                    }
+                }
+                EditorMode::Help => {
+                    if let Ok(cmd) = EditorCommand::try_from(event) {
+                        self.process_help_command(cmd);
                     }
                 }
             }
@@ -185,6 +444,52 @@ impl Editor {
         }
     }
 
+    /// Ctrl-C works as Esc only if the config asks for it.
+    fn resolve_interrupt(&self, cmd: TextCommand) -> TextCommand {
+        match cmd {
+            TextCommand::Interrupt if self.view.config().ctrl_c_exits => TextCommand::Exit,
+            cmd => cmd,
+        }
+    }
+
+    /// While a file is loading only resizing and aborting
+    /// the load with Ctrl-Q are accepted.
+    fn evaluate_event_while_loading(&mut self, event: Event) {
+        match EditorCommand::try_from(event) {
+            Ok(EditorCommand::Quit) => {
+                self.view.cancel_loading();
+                self.clear_busy();
+                self.message_bar.set_message("Loading aborted");
+            }
+            Ok(cmd @ EditorCommand::Resize(_)) => self.process_normal_command(cmd),
+            _ => {}
+        }
+    }
+
+    fn show_loading(&mut self) {
+        if self.view.is_loading() {
+            self.set_busy("Loading");
+        }
+    }
+
+    fn poll_loading(&mut self) {
+        let status = self.view.poll_loading();
+        if matches!(status, Some(LoadStatus::Loaded | LoadStatus::Failed(_))) {
+            self.clear_busy();
+        }
+
+        match status {
+            Some(LoadStatus::Progress(percent)) => self
+                .message_bar
+                .set_message(&format!("Loading… {percent}%")),
+            Some(LoadStatus::Loaded) => self.message_bar.set_message(DEFAULT_MESSAGE),
+            Some(LoadStatus::Failed(path)) => self
+                .message_bar
+                .set_message(&format!("ERR: Could not open file: {path}")),
+            None => {}
+        }
+    }
+
     fn enter_command_mode(&mut self, cmd: Cmd) {
         self.mode = EditorMode::Command;
         self.command_bar.set_command(cmd);
@@ -197,45 +502,688 @@ impl Editor {
         self.switched_mode = true;
     }
 
-    fn execute_command(&mut self) {
-        let cmd = self.command_bar.get_command().expect("Command wasn't set");
+    fn execute_command(&mut self, cmd: Cmd, line: String) {
         match cmd {
-            Cmd::Search => {
-                let needle = self.command_bar.get_line();
-                self.view.set_search_term(needle);
-                self.view.search();
+            Cmd::Search | Cmd::SearchBackward | Cmd::SearchInSelection => {
+                let direction = if cmd == Cmd::SearchBackward {
+                    SearchDirection::Backward
+                } else {
+                    SearchDirection::Forward
+                };
+                if cmd != Cmd::SearchInSelection {
+                    self.view.clear_search_scope();
+                }
+                self.view.set_search_term(line);
+                let found = self.view.search(direction);
+                self.show_match_count(found);
+            }
+            Cmd::ReplaceInSelection => {
+                let Some((needle, replacement)) = line.split_once('/') else {
+                    self.view.clear_search_scope();
+                    self.message_bar
+                        .set_message("ERR: Type the text to replace as old/new");
+                    return;
+                };
+
+                let replaced = self.view.replace_in_scope(needle, replacement);
+                self.message_bar
+                    .set_message(&format!("Replaced {replaced} occurrences"));
+            }
+            Cmd::AlignSelection => {
+                if line.is_empty() {
+                    self.view.clear_search_scope();
+                    self.message_bar
+                        .set_message("ERR: Type the text to align the lines on");
+                    return;
+                }
+
+                let padded = self.view.align_in_scope(&line);
+                self.message_bar
+                    .set_message(&format!("Aligned {padded} lines on {line}"));
             }
-            Cmd::SaveAs => {
-                let file_name = self.command_bar.get_line();
-                let _ = self.view.save_as(&file_name);
-                self.message_bar.set_message("File was saved successfully");
+            Cmd::Goto => match line.trim().parse() {
+                Ok(line_number) => self.view.goto(line_number, None),
+                Err(_) => self.message_bar.set_message("ERR: Not a line number"),
+            },
+            Cmd::SaveAs => self.save_as(line),
+            Cmd::ConfirmOverwrite => {
+                let file_name = self.pending_save_as.take().unwrap_or_default();
+                if line == "y" {
+                    self.write_file_as(&file_name);
+                } else {
+                    self.quit_after_save = false;
+                    self.message_bar.set_message("File was not saved");
+                }
             }
+            Cmd::QuitConfirm => match line.as_str() {
+                "y" => {
+                    self.quit_after_save = true;
+                    self.save();
+                }
+                "n" => self.should_quit = true,
+                _ => self.message_bar.set_message("Quit cancelled"),
+            },
+            Cmd::ConfirmReload => {
+                if line == "y" {
+                    self.reload();
+                } else {
+                    self.message_bar.set_message("File was not reloaded");
+                }
+            }
+            Cmd::Ex => match ExCommand::try_from(line.as_str()) {
+                Ok(cmd) => self.execute_ex_command(cmd),
+                Err(err) => self.message_bar.set_message(&err),
+            },
         }
     }
 
+    fn save(&mut self) {
+        let res = self.view.save();
+        self.handle_save_result(res);
+    }
+
+    fn handle_save_result(&mut self, res: Result<(), std::io::Error>) {
+        match res {
+            Ok(()) => self.saved(),
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                self.enter_command_mode(Cmd::SaveAs);
+            }
+            Err(err) => self.report_save_error(&err),
+        }
+    }
+
+    /// Saves the file with a new name, asking for a confirmation
+    /// if it would overwrite a file other than the one being edited.
+    fn save_as(&mut self, file_name: String) {
+        let target = Path::new(&file_name);
+        let is_current = self
+            .view
+            .file_path()
+            .and_then(|current| current.canonicalize().ok())
+            .is_some_and(|current| target.canonicalize().is_ok_and(|t| t == current));
+
+        if target.exists() && !is_current {
+            self.pending_save_as = Some(file_name);
+            self.enter_command_mode(Cmd::ConfirmOverwrite);
+        } else {
+            self.write_file_as(&file_name);
+        }
+    }
+
+    fn write_file_as(&mut self, file_name: &str) {
+        match self.view.save_as(file_name) {
+            Ok(()) => self.saved(),
+            Err(err) => self.report_save_error(&err),
+        }
+    }
+
+    /// Quits if the save was asked by `ZZ`.
+    fn saved(&mut self) {
+        self.pressed_quit = TIMES_TO_QUIT;
+        self.message_bar.set_message("File was saved successfully");
+        self.should_quit = self.quit_after_save;
+    }
+
+    fn report_save_error(&mut self, err: &std::io::Error) {
+        self.quit_after_save = false;
+        let reason = match err.kind() {
+            ErrorKind::PermissionDenied => "Permission denied",
+            ErrorKind::ReadOnlyFilesystem => "Read-only file system",
+            ErrorKind::StorageFull | ErrorKind::QuotaExceeded => "No space left on device",
+            _ => "",
+        };
+
+        let detail = match (reason, err.raw_os_error()) {
+            ("", _) => err.to_string(),
+            (reason, Some(code)) => format!("{reason} (os error {code})"),
+            (reason, None) => format!("{reason}: {err}"),
+        };
+
+        self.message_bar
+            .set_message(&format!("Error writing file: {detail}"));
+    }
+
+    fn execute_ex_command(&mut self, cmd: ExCommand) {
+        match cmd {
+            ExCommand::Stats => {
+                let stats = self.view.stats();
+                self.message_bar.set_message(&format!(
+                    "{} lines, {} words, {} characters, {} bytes",
+                    stats.lines, stats.words, stats.chars, stats.bytes
+                ));
+            }
+            ExCommand::Recent => self.open_recent_files(),
+            ExCommand::Write => self.save(),
+            ExCommand::ForceWrite => {
+                let res = self.view.save_forced();
+                self.handle_save_result(res);
+            }
+            ExCommand::Reload => {
+                if self.view.is_file_modified() {
+                    self.enter_command_mode(Cmd::ConfirmReload);
+                } else {
+                    self.reload();
+                }
+            }
+            ExCommand::SetFileType(file_type) => self.view.set_file_type(file_type),
+            ExCommand::SetEncoding(encoding) => self.view.set_encoding(encoding),
+            ExCommand::SetFinalNewline(enabled) => self.view.set_final_newline(enabled),
+            ExCommand::SetHighlightSearch(enabled) => self.view.set_highlight_search(enabled),
+            ExCommand::HideMatches => self.view.hide_matches(),
+            ExCommand::SetWordHighlight(enabled) => self.view.set_word_highlight(enabled),
+            ExCommand::SetCursorColumn(enabled) => self.view.set_cursor_column(enabled),
+            ExCommand::Append(file_name) => match self.view.append_to(file_name) {
+                Ok(bytes) => self
+                    .message_bar
+                    .set_message(&format!("Appended {bytes} bytes to {file_name}")),
+                Err(err) => self.report_save_error(&err),
+            },
+            ExCommand::ToggleFocus => {
+                let enabled = !self.view.config().focus_mode;
+                self.view.set_focus_mode(enabled);
+                self.switched_mode = true;
+            }
+            ExCommand::Filter(command) => self.filter_buffer(command),
+            ExCommand::Format => self.format_buffer(),
+            ExCommand::Help => self.open_help(),
+        }
+    }
+
+    fn format_buffer(&mut self) {
+        if self.view.file_type() != FileType::Rust {
+            self.message_bar
+                .set_message("ERR: Only Rust files can be formatted");
+        } else if self.view.is_read_only() {
+            self.message_bar
+                .set_message("Large files are opened read-only");
+        } else {
+            self.start_buffer_job("rustfmt", "File was formatted", shell::rustfmt);
+        }
+    }
+
+    /// Replaces the buffer with what the command prints when fed
+    /// with it, on failure the buffer is left as it was.
+    fn filter_buffer(&mut self, command: &str) {
+        if self.view.is_read_only() {
+            self.message_bar
+                .set_message("Large files are opened read-only");
+            return;
+        }
+
+        let thread_command = command.to_string();
+        let program = command.split_whitespace().next().unwrap_or(command);
+        self.start_buffer_job(
+            program,
+            &format!("Filtered through {command}"),
+            move |input| shell::filter(&thread_command, input),
+        );
+    }
+
+    /// Runs the command on the text of the buffer in the background,
+    /// only one of them can run at a time.
+    fn start_buffer_job(
+        &mut self,
+        name: &str,
+        done_message: &str,
+        run: impl FnOnce(&str) -> Result<String, String> + Send + 'static,
+    ) {
+        if self.buffer_job.is_some() {
+            self.message_bar
+                .set_message("ERR: Another command is still running");
+            return;
+        }
+
+        let input = self.view.text();
+        let thread_input = input.clone();
+        self.buffer_job = Some(BufferJob {
+            job: Job::start(move || run(&thread_input)),
+            input,
+            done_message: done_message.to_string(),
+        });
+        self.set_busy(&format!("Running {name}"));
+    }
+
+    fn finish_buffer_job(
+        &mut self,
+        input: &str,
+        done_message: &str,
+        result: Result<String, String>,
+    ) {
+        self.clear_busy();
+        match result {
+            Ok(_) if self.view.text() != input => self
+                .message_bar
+                .set_message("ERR: The buffer changed while the command was running"),
+            Ok(output) => {
+                self.view.replace_text(&output);
+                self.message_bar.set_message(done_message);
+            }
+            Err(err) => self.message_bar.set_message(&format!("ERR: {err}")),
+        }
+    }
+
+    fn reload(&mut self) {
+        match self.view.reload() {
+            Ok(()) => {
+                self.pressed_quit = TIMES_TO_QUIT;
+                self.message_bar.set_message("File was reloaded");
+            }
+            Err(err) if err.kind() == ErrorKind::NotFound && self.view.file_path().is_none() => {
+                self.message_bar
+                    .set_message("ERR: The buffer has no file name");
+            }
+            Err(err) => self
+                .message_bar
+                .set_message(&format!("ERR: Could not reload file: {err}")),
+        }
+    }
+
+    fn submit_command(&mut self) {
+        let cmd = self.command_bar.get_command().expect("Command wasn't set");
+        self.command_bar.push_history();
+        let line = self.command_bar.get_line();
+        self.exit_command_mode();
+        self.execute_command(cmd, line);
+    }
+
     fn process_command(&mut self, cmd: TextCommand) {
+        if self
+            .command_bar
+            .get_command()
+            .is_some_and(Cmd::is_confirmation)
+        {
+            match cmd {
+                TextCommand::Write(answer) => {
+                    self.command_bar
+                        .set_line(&answer.to_lowercase().to_string());
+                    self.submit_command();
+                }
+                TextCommand::Exit => self.submit_command(),
+                _ => {}
+            }
+            return;
+        }
+
         match cmd {
             TextCommand::Write(symbol) => self.command_bar.handle_insertion(symbol),
+            TextCommand::Move(mov) | TextCommand::SelectMove(mov) => {
+                self.command_bar.handle_movement(mov);
+            }
+            TextCommand::Tab => {
+                if self.command_bar.get_command().is_some_and(Cmd::completes) {
+                    self.command_bar.handle_completion();
+                } else {
+                    self.command_bar.handle_insertion('\t');
+                }
+            }
+            TextCommand::BackTab => {
+                if self.command_bar.get_command().is_some_and(Cmd::completes) {
+                    self.command_bar.handle_completion_back();
+                }
+            }
             TextCommand::Deletion => self.command_bar.handle_deletion(),
             TextCommand::Backspace => self.command_bar.handle_backspace(),
-            TextCommand::Exit => self.exit_command_mode(),
-            TextCommand::Enter => {
-                self.execute_command();
+            TextCommand::DeleteToLineStart => self.command_bar.handle_clear(),
+            TextCommand::DeleteWordBack => self.command_bar.handle_delete_word_back(),
+            TextCommand::Exit => {
+                if self.command_bar.get_command().is_some_and(|cmd| {
+                    matches!(
+                        cmd,
+                        Cmd::SearchInSelection | Cmd::ReplaceInSelection | Cmd::AlignSelection
+                    )
+                }) {
+                    self.view.clear_search_scope();
+                }
+                self.quit_after_save = false;
                 self.exit_command_mode();
             }
+            TextCommand::Interrupt | TextCommand::Literal | TextCommand::Digraph => {}
+            TextCommand::Enter => self.submit_command(),
+        }
+        self.update_match_preview();
+    }
+
+    /// Shows how many times the search being typed matches.
+    fn update_match_preview(&mut self) {
+        let command = self.command_bar.get_command();
+        let count = if command.is_some_and(Cmd::is_search) {
+            let scoped = command == Some(Cmd::SearchInSelection);
+            self.view
+                .preview_match_count(&self.command_bar.get_line(), scoped)
+        } else {
+            None
+        };
+        self.command_bar.set_match_count(count);
+    }
+
+    fn enter_search(&mut self, cmd: Cmd) {
+        self.enter_command_mode(cmd);
+        self.command_bar.set_line(self.view.search_term());
+        self.update_match_preview();
+    }
+
+    fn process_paste(&mut self, text: &str) {
+        match self.mode {
+            EditorMode::Insert => self.view.handle_paste(text),
+            EditorMode::Command => {
+                for ch in text.chars().filter(|ch| !ch.is_control()) {
+                    self.command_bar.handle_insertion(ch);
+                }
+                self.update_match_preview();
+            }
+            EditorMode::Normal | EditorMode::Visual | EditorMode::Picker | EditorMode::Help => {}
+        }
+    }
+
+    fn remember_recent_file(&mut self, path: &str) {
+        self.recent_files.push(path);
+        let _ = self.recent_files.save();
+    }
+
+    fn open_recent_files(&mut self) {
+        let items: Vec<String> = self
+            .recent_files
+            .paths()
+            .map(|path| path.display().to_string())
+            .collect();
+
+        if items.is_empty() {
+            self.message_bar.set_message("No recent files");
+            return;
+        }
+
+        self.picker.set_filtered_items("Recent files", items);
+        self.picker_content = PickerContent::RecentFiles;
+        self.mode = EditorMode::Picker;
+        self.switched_mode = true;
+    }
+
+    fn open_palette(&mut self) {
+        self.picker
+            .set_filtered_items("Commands", self.palette.labels());
+        self.picker_content = PickerContent::Commands;
+        self.mode = EditorMode::Picker;
+        self.switched_mode = true;
+    }
+
+    /// Runs a command chosen in the palette.
+    fn run_palette_action(&mut self, action: PaletteAction) {
+        match action {
+            PaletteAction::Normal(cmd) => self.process_normal_command(cmd),
+            PaletteAction::Ex(line) => self.execute_command(Cmd::Ex, line.to_string()),
+            PaletteAction::Prompt(cmd) => self.enter_command_mode(cmd),
+        }
+    }
+
+    /// Shows the key bindings in place of the file, in a view
+    /// that can only be scrolled until it is closed.
+    fn open_help(&mut self) {
+        let mut help = View::from_text(&help::text());
+        help.set_config(self.view.config().clone());
+        self.previous_view = Some(std::mem::replace(&mut self.view, help));
+        self.resize(self.size);
+        self.mode = EditorMode::Help;
+        self.switched_mode = true;
+    }
+
+    fn close_help(&mut self) {
+        if let Some(view) = self.previous_view.take() {
+            self.view = view;
+        }
+        self.resize(self.size);
+        self.view.set_needs_redraw(true);
+        self.mode = EditorMode::Normal;
+        self.switched_mode = true;
+    }
+
+    fn process_help_command(&mut self, cmd: EditorCommand) {
+        match cmd {
+            EditorCommand::Move(direction) => self.view.handle_movement(direction),
+            EditorCommand::Resize(size) => self.resize(size),
+            EditorCommand::ExitSearch | EditorCommand::Prefix('q') | EditorCommand::Quit => {
+                self.close_help();
+            }
+            _ => {}
+        }
+    }
+
+    fn exit_picker(&mut self) {
+        self.mode = EditorMode::Normal;
+        self.switched_mode = true;
+        self.view.set_needs_redraw(true);
+    }
+
+    fn process_picker_command(&mut self, cmd: PickerCommand) {
+        match cmd {
+            PickerCommand::Up => self.picker.move_up(),
+            PickerCommand::Down => self.picker.move_down(),
+            PickerCommand::Exit => self.exit_picker(),
+            PickerCommand::Select if self.picker_content == PickerContent::Commands => {
+                let action = self
+                    .picker
+                    .selected_index()
+                    .and_then(|index| self.palette.action(index));
+                self.exit_picker();
+                if let Some(action) = action {
+                    self.run_palette_action(action);
+                }
+            }
+            PickerCommand::Select => {
+                let path = self.picker.selected_item().map(str::to_string);
+                self.exit_picker();
+                if let Some(path) = path {
+                    self.open_file(&path);
+                }
+            }
+        }
+    }
+
+    /// Typing in a picker with a query filters its items,
+    /// they are still browsed with the arrows.
+    fn process_picker_query(&mut self, cmd: TextCommand) {
+        match cmd {
+            TextCommand::Write(ch) => self.picker.push_query(ch),
+            TextCommand::Backspace => self.picker.pop_query(),
+            TextCommand::Move(Direction::Up) => self.process_picker_command(PickerCommand::Up),
+            TextCommand::Move(Direction::Down) => {
+                self.process_picker_command(PickerCommand::Down);
+            }
+            TextCommand::Enter => self.process_picker_command(PickerCommand::Select),
+            TextCommand::Exit => self.process_picker_command(PickerCommand::Exit),
+            _ => {}
+        }
+    }
+
+    fn open_file(&mut self, path: &str) {
+        if self.view.is_file_modified() {
+            self.message_bar
+                .set_message("File has unsaved changes, save it before opening another one");
+            return;
+        }
+
+        match self.view.load(path) {
+            Ok(()) => {
+                let _ = self.terminal.set_title(path);
+                self.remember_recent_file(path);
+                self.show_loading();
+                self.pressed_quit = TIMES_TO_QUIT;
+            }
+            Err(_) => self
+                .message_bar
+                .set_message(&format!("ERR: Could not open file: {path}")),
         }
     }
 
     fn process_insertion(&mut self, cmd: TextCommand) {
+        // The cursors added with Ctrl-D only take the characters typed
+        // and deleted, anything else leaves the main cursor alone.
+        if !matches!(
+            cmd,
+            TextCommand::Write(_) | TextCommand::Backspace | TextCommand::Deletion
+        ) {
+            self.view.clear_extra_cursors();
+        }
+
         match cmd {
+            TextCommand::Write(symbol) if self.view.has_extra_cursors() => {
+                self.view.insert_at_cursors(symbol);
+            }
+            TextCommand::Backspace if self.view.has_extra_cursors() => {
+                self.view.backspace_at_cursors();
+            }
+            TextCommand::Deletion if self.view.has_extra_cursors() => {
+                self.view.delete_at_cursors();
+            }
             TextCommand::Write(symbol) => self.view.handle_insertion(symbol),
+            TextCommand::Tab => {
+                if !self.view.expand_snippet() {
+                    self.view.handle_insertion('\t');
+                }
+            }
+            TextCommand::BackTab => self.view.handle_command(EditorCommand::Dedent),
+            TextCommand::Move(mov) => self.view.handle_movement(mov),
+            TextCommand::SelectMove(mov) => {
+                self.view.mark_insert_end();
+                self.view.finish_block_insert();
+                self.start_shift_selection(mov);
+            }
+            TextCommand::DeleteToLineStart => self.view.delete_to_line_start(),
             TextCommand::Enter => self.view.handle_enter(),
+            TextCommand::DeleteWordBack => self.view.delete_word_back(),
             TextCommand::Deletion => self.view.handle_deletion(),
             TextCommand::Backspace => self.view.handle_backspace(),
             TextCommand::Exit => {
+                self.view.mark_insert_end();
+                self.view.finish_block_insert();
                 self.mode = EditorMode::Normal;
                 self.switched_mode = true;
             }
+            TextCommand::Literal => self.pending_literal = Some(String::new()),
+            TextCommand::Digraph => self.pending_digraph = Some(String::new()),
+            TextCommand::Interrupt => {}
+        }
+    }
+
+    /// Inserts the key pressed after Ctrl-V as it is, or the code
+    /// point typed as `u` followed by four hex digits.
+    fn process_literal(&mut self, key: KeyEvent) {
+        let Some(mut typed) = self.pending_literal.take() else {
+            return;
+        };
+
+        if typed.starts_with('u') {
+            let KeyCode::Char(digit @ ('0'..='9' | 'a'..='f' | 'A'..='F')) = key.code else {
+                self.message_bar.set_message("Invalid code point");
+                return;
+            };
+
+            typed.push(digit);
+            if typed.len() < 5 {
+                self.pending_literal = Some(typed);
+            } else if let Some(ch) = u32::from_str_radix(&typed[1..], 16)
+                .ok()
+                .and_then(char::from_u32)
+            {
+                self.view.insert_raw(ch);
+            } else {
+                self.message_bar.set_message("Invalid code point");
+            }
+            return;
+        }
+
+        let literal = match (key.code, key.modifiers) {
+            (KeyCode::Char('u'), KeyModifiers::NONE) => {
+                self.pending_literal = Some(String::from("u"));
+                return;
+            }
+            (KeyCode::Char(ch @ 'a'..='z'), KeyModifiers::CONTROL) => {
+                char::from_u32(u32::from(ch).saturating_sub(96))
+            }
+            (KeyCode::Char(ch), _) => Some(ch),
+            (KeyCode::Tab, _) => Some('\t'),
+            (KeyCode::Enter, _) => Some('\r'),
+            (KeyCode::Esc, _) => Some('\u{1b}'),
+            (KeyCode::Backspace, _) => Some('\u{8}'),
+            _ => None,
+        };
+
+        if let Some(ch) = literal {
+            self.view.insert_raw(ch);
+        }
+    }
+
+    /// Collects the two characters typed after Ctrl-K and
+    /// inserts the character of their digraph.
+    fn process_digraph(&mut self, key: KeyEvent) {
+        let Some(mut typed) = self.pending_digraph.take() else {
+            return;
+        };
+
+        let KeyCode::Char(ch) = key.code else {
+            return;
+        };
+
+        let Some(first) = typed.chars().next() else {
+            typed.push(ch);
+            self.pending_digraph = Some(typed);
+            return;
+        };
+
+        match digraph::lookup(first, ch) {
+            Some(symbol) => self.view.insert_raw(symbol),
+            None => self
+                .message_bar
+                .set_message(&format!("Unknown digraph: {first}{ch}")),
+        }
+    }
+
+    /// Collects the characters typed after a surround command, the
+    /// edit is made once all of them are there. Any other key cancels it.
+    fn process_surround(&mut self, key: KeyEvent) {
+        let Some((_, typed)) = &mut self.pending_surround else {
+            return;
+        };
+
+        match key.code {
+            KeyCode::Char(ch) if ch.is_ascii_punctuation() => typed.push(ch),
+            KeyCode::Char(ch) => {
+                self.pending_surround = None;
+                self.message_bar
+                    .set_message(&format!("ERR: Can't surround with {ch}"));
+            }
+            _ => self.pending_surround = None,
+        }
+
+        let Some((surround, typed)) = self
+            .pending_surround
+            .take_if(|(surround, typed)| typed.chars().count() >= surround.chars_needed())
+        else {
+            if self.pending_surround.is_none() && self.mode == EditorMode::Visual {
+                self.exit_visual_mode();
+            }
+            return;
+        };
+
+        let mut chars = typed.chars();
+        let first = chars.next().unwrap_or_default();
+        let done = match surround {
+            Surround::Add if self.mode == EditorMode::Visual => self.view.surround_selection(first),
+            Surround::Add => self.view.surround_word(first),
+            Surround::Change => self
+                .view
+                .change_surround(first, chars.next().unwrap_or_default()),
+            Surround::Delete => self.view.delete_surround(first),
+        };
+
+        if self.mode == EditorMode::Visual {
+            self.exit_visual_mode();
+        }
+        if !done && surround == Surround::Add {
+            self.message_bar
+                .set_message("ERR: No word under the cursor");
+        } else if !done {
+            self.message_bar
+                .set_message(&format!("ERR: No {first} around the cursor"));
         }
     }
 
@@ -251,48 +1199,315 @@ impl Editor {
         }
     }
 
+    /// Shows which match the cursor is on, or that the
+    /// search term didn't match when nothing was `found`.
+    fn show_match_count(&mut self, found: bool) {
+        let term = self.view.search_term();
+        if term.is_empty() {
+            return;
+        }
+        if !found {
+            let message = format!("Pattern not found: {term}");
+            self.message_bar.set_message(&message);
+            return;
+        }
+
+        if let Some((ordinal, count)) = self.view.match_count() {
+            self.message_bar
+                .set_message(&format!("[{ordinal}/{count}]"));
+        }
+    }
+
     fn clear_search(&mut self) {
         self.view.clear_search_term();
+        self.view.clear_extra_cursors();
+    }
+
+    fn add_cursor(&mut self) {
+        if !self.view.add_cursor_on_next_occurrence() {
+            self.message_bar
+                .set_message("No other occurrence of the word under the cursor");
+        }
     }
 
     fn process_normal_command(&mut self, cmd: EditorCommand) {
+        let count = match cmd {
+            EditorCommand::Count(digit) => {
+                self.push_count_digit(digit);
+                return;
+            }
+            EditorCommand::Move(Direction::LineStart) if self.pending_count.is_some() => {
+                self.push_count_digit(0);
+                return;
+            }
+            EditorCommand::Prefix(_) | EditorCommand::Resize(_) => self.pending_count,
+            _ => self.pending_count.take(),
+        }
+        .unwrap_or(1);
+
         match cmd {
             EditorCommand::ExitSearch => self.clear_search(),
-            EditorCommand::Search => self.enter_command_mode(Cmd::Search),
-            EditorCommand::NextOccurrence => self.view.search_next(),
-            EditorCommand::PrevOccurrence => self.view.search_prev(),
-            EditorCommand::Save => {
-                let res = self.view.save();
-                match res {
-                    Ok(()) => {
-                        self.pressed_quit = TIMES_TO_QUIT;
-                        self.message_bar.set_message("File was saved successfully");
-                    }
-                    Err(err) if err.kind() == ErrorKind::NotFound => {
-                        self.enter_command_mode(Cmd::SaveAs);
-                    }
-                    Err(_) => self.message_bar.set_message("Error writing file"),
+            EditorCommand::Search => self.enter_search(Cmd::Search),
+            EditorCommand::SearchBackward => self.enter_search(Cmd::SearchBackward),
+            EditorCommand::ExCommand => self.enter_command_mode(Cmd::Ex),
+            EditorCommand::Palette => self.open_palette(),
+            EditorCommand::Help => self.open_help(),
+            EditorCommand::Prefix('q') if self.recording.is_some() => self.stop_recording(),
+            EditorCommand::Prefix(prefix) => self.pending_key = Some(prefix),
+            EditorCommand::Record(register) => {
+                self.recording = Some((register, Vec::new()));
+                self.message_bar
+                    .set_message(&format!("Recording @{register}"));
+            }
+            EditorCommand::Replay(register) => self.replay_macro(register, count),
+            EditorCommand::Surround(surround) => {
+                self.pending_surround = Some((surround, String::new()));
+            }
+            EditorCommand::ToggleFold => {
+                if !self.view.toggle_fold() {
+                    self.message_bar
+                        .set_message("ERR: No fold under the cursor");
                 }
             }
-
+            EditorCommand::NextOccurrence => {
+                let found = self.view.search_next();
+                self.show_match_count(found);
+            }
+            EditorCommand::PrevOccurrence => {
+                let found = self.view.search_prev();
+                self.show_match_count(found);
+            }
+            EditorCommand::SearchWord(direction) => {
+                if self.view.search_word_under_cursor(direction) {
+                    self.show_match_count(true);
+                } else {
+                    self.message_bar.set_message("No word under the cursor");
+                }
+            }
+            EditorCommand::AddCursor => self.add_cursor(),
+            EditorCommand::Increment(delta) => self.view.handle_command(EditorCommand::Increment(
+                delta.saturating_mul(i64::from(count)),
+            )),
+            EditorCommand::Save => self.save(),
             EditorCommand::Quit => {
-                if self.view.is_file_modified() {
+                if self.view.is_file_modified() && self.view.config().quit_prompt {
+                    self.enter_command_mode(Cmd::QuitConfirm);
+                } else if self.view.is_file_modified() {
                     self.warn_unsaved_file();
                 } else {
                     self.should_quit = true;
                 }
             }
-
-            EditorCommand::EnterInsert => {
-                self.mode = EditorMode::Insert;
-                self.switched_mode = true;
+            EditorCommand::SaveAndQuit => {
+                if self.view.is_file_modified() {
+                    self.quit_after_save = true;
+                    self.save();
+                } else {
+                    self.should_quit = true;
+                }
             }
+            EditorCommand::ForceQuit => self.should_quit = true,
+
+            EditorCommand::Visual(kind) => self.enter_visual_mode(kind),
+            EditorCommand::SelectMove(direction) => self.start_shift_selection(direction),
+            EditorCommand::Reselect => self.reselect(),
+            EditorCommand::Paste => self.view.paste(),
+            EditorCommand::EnterInsert
+            | EditorCommand::ChangeToEol
+            | EditorCommand::SubstituteChar
+            | EditorCommand::SubstituteLine => self.enter_insert(cmd),
+            EditorCommand::Resize(size) => self.resize(size),
             _ => self.view.handle_command(cmd),
         }
+    }
+
+    fn process_visual_command(&mut self, cmd: VisualCommand) {
+        self.view.remember_selection();
+        match cmd {
+            VisualCommand::Move(direction) => self.view.handle_movement(direction),
+            VisualCommand::Arrow(direction) => {
+                if self.shift_selection_origin.is_some() {
+                    self.exit_visual_mode();
+                }
+                self.view.handle_movement(direction);
+            }
+            VisualCommand::Prefix(prefix) => self.pending_key = Some(prefix),
+            VisualCommand::Select(kind) if self.view.selection_kind() == Some(kind) => {
+                self.exit_visual_mode();
+            }
+            VisualCommand::Select(kind) => self.view.select(kind),
+            VisualCommand::Delete => {
+                self.view.delete_selection();
+                self.exit_visual_mode();
+            }
+            VisualCommand::Yank => {
+                self.view.yank_selection();
+                self.exit_visual_mode();
+            }
+            VisualCommand::Indent | VisualCommand::Dedent => {
+                self.view
+                    .shift_selection(matches!(cmd, VisualCommand::Indent));
+                self.exit_visual_mode();
+            }
+            VisualCommand::ToggleComment => {
+                if !self.view.toggle_comment_selection() {
+                    self.message_bar
+                        .set_message("ERR: The file type has no line comments");
+                }
+                self.exit_visual_mode();
+            }
+            VisualCommand::Fold => {
+                if !self.view.fold_selection() {
+                    self.message_bar
+                        .set_message("ERR: A fold needs at least two lines");
+                }
+                self.exit_visual_mode();
+            }
+            VisualCommand::IncrementSequence(step) => {
+                if self.view.increment_selection(step) == 0 {
+                    self.message_bar
+                        .set_message("ERR: No numbers in the selection");
+                }
+                self.exit_visual_mode();
+            }
+            VisualCommand::Surround => {
+                self.pending_surround = Some((Surround::Add, String::new()));
+            }
+            VisualCommand::InsertBlock | VisualCommand::AppendBlock => {
+                let append = matches!(cmd, VisualCommand::AppendBlock);
+                if self.view.start_block_insert(append) {
+                    self.view.mark_insert_start();
+                    self.mode = EditorMode::Insert;
+                    self.switched_mode = true;
+                }
+            }
+            VisualCommand::Align => {
+                self.view.scope_search_to_selection();
+                self.enter_command_mode(Cmd::AlignSelection);
+            }
+            VisualCommand::Search | VisualCommand::Replace => {
+                self.view.scope_search_to_selection();
+                if matches!(cmd, VisualCommand::Search) {
+                    self.enter_command_mode(Cmd::SearchInSelection);
+                } else {
+                    self.enter_command_mode(Cmd::ReplaceInSelection);
+                }
+            }
+            VisualCommand::Resize(size) => self.resize(size),
+            VisualCommand::Exit => self.exit_visual_mode(),
+        }
+    }
+
+    /// Enters insert mode after running the command
+    /// that changes the text first, if it's one.
+    fn enter_insert(&mut self, cmd: EditorCommand) {
+        if self.view.is_read_only() {
+            self.message_bar
+                .set_message("Large files are opened read-only");
+            return;
+        }
+
+        if !matches!(cmd, EditorCommand::EnterInsert) {
+            self.view.handle_command(cmd);
+        }
+        self.view.mark_insert_start();
+        self.mode = EditorMode::Insert;
+        self.switched_mode = true;
+    }
+
+    fn enter_visual_mode(&mut self, kind: VisualKind) {
+        self.view.select(kind);
+        self.shift_selection_origin = None;
+        self.mode = EditorMode::Visual;
+        self.switched_mode = true;
+    }
+
+    /// Selects from the cursor to where the movement lands, the
+    /// arrows without Shift end the selection and go back to `mode`.
+    fn start_shift_selection(&mut self, direction: Direction) {
+        let origin = self.mode;
+        self.enter_visual_mode(VisualKind::Char);
+        self.shift_selection_origin = Some(origin);
+        self.view.handle_movement(direction);
+    }
+
+    fn reselect(&mut self) {
+        if self.view.reselect() {
+            self.shift_selection_origin = None;
+            self.mode = EditorMode::Visual;
+            self.switched_mode = true;
+        } else {
+            self.message_bar
+                .set_message("ERR: Nothing was selected yet");
+        }
+    }
+
+    fn exit_visual_mode(&mut self) {
+        self.view.clear_selection();
+        self.mode = self
+            .shift_selection_origin
+            .take()
+            .unwrap_or(EditorMode::Normal);
+        if self.mode == EditorMode::Insert {
+            self.view.mark_insert_start();
+        }
+        self.switched_mode = true;
+    }
 
-        if let EditorCommand::Resize(size) = cmd {
-            self.status_bar.resize(size);
+    /// Saves the recorded keys in their register, the `q`
+    /// that stopped the recording is left out.
+    fn stop_recording(&mut self) {
+        if let Some((register, mut events)) = self.recording.take() {
+            events.pop();
+            self.macros.insert(register, events);
+            self.message_bar
+                .set_message(&format!("Recorded @{register}"));
+        }
+    }
+
+    /// Feeds the keys of the register to the editor `count` times,
+    /// a macro can't replay other macros so it can't recurse.
+    fn replay_macro(&mut self, register: char, count: u32) {
+        if self.replaying {
+            self.message_bar
+                .set_message("ERR: A macro can't replay another macro");
+            return;
+        }
+
+        let register = if register == '@' {
+            let Some(register) = self.last_macro else {
+                self.message_bar
+                    .set_message("ERR: No macro was replayed yet");
+                return;
+            };
+            register
+        } else {
+            register
+        };
+
+        let Some(events) = self.macros.get(&register).cloned() else {
+            self.message_bar
+                .set_message(&format!("ERR: Register @{register} is empty"));
+            return;
+        };
+
+        self.last_macro = Some(register);
+        self.replaying = true;
+        for _ in 0..count {
+            for event in &events {
+                if self.should_quit {
+                    break;
+                }
+                self.evaluate_event(event.clone());
+            }
         }
+        self.replaying = false;
+    }
+
+    /// Appends a digit to the count typed before a command.
+    fn push_count_digit(&mut self, digit: u32) {
+        let count = self.pending_count.unwrap_or(0);
+        self.pending_count = Some(count.saturating_mul(10).saturating_add(digit));
     }
 
     /// Refreshes the screen in order to render correcly the events
@@ -301,12 +1516,17 @@ impl Editor {
             return;
         }
 
-        let _ = Terminal::hide_cursor();
+        let _ = self.terminal.hide_cursor();
 
         if self.switched_mode {
-            let _ = match self.mode {
-                EditorMode::Normal => Terminal::cursor_block(),
-                EditorMode::Command | EditorMode::Insert => Terminal::cursor_bar(),
+            let _ = if self.view.config().focus_mode
+                || matches!(
+                    self.mode,
+                    EditorMode::Normal | EditorMode::Visual | EditorMode::Help
+                ) {
+                self.terminal.cursor_block()
+            } else {
+                self.terminal.cursor_bar()
             };
             self.switched_mode = false;
         }
@@ -319,35 +1539,399 @@ impl Editor {
                 x: self.command_bar.cursor_location(),
                 y,
             };
-            self.command_bar.render(y);
+            self.command_bar.render(&mut self.terminal, y);
             self.message_bar.set_needs_redraw(true);
         } else {
-            self.message_bar.render(self.size.height.saturating_sub(1));
+            self.message_bar
+                .render(&mut self.terminal, self.size.height.saturating_sub(1));
         }
 
         if self.size.height > 1 {
-            self.status_bar.render(self.size.height.saturating_sub(2));
+            self.status_bar
+                .render(&mut self.terminal, self.size.height.saturating_sub(2));
         }
 
         if self.size.height > 2 {
-            self.view.render(0);
+            self.view.render(&mut self.terminal, 0);
+
+            if let EditorMode::Picker = self.mode {
+                cursor_pos = self.picker.cursor_position();
+                self.picker.set_needs_redraw(true);
+                self.picker.render(&mut self.terminal, 0);
+            }
         }
 
-        let _ = Terminal::move_cursor_to(cursor_pos);
-        let _ = Terminal::show_cursor();
-        let _ = Terminal::execute();
+        let _ = self.terminal.move_cursor_to(cursor_pos);
+        let _ = self.terminal.show_cursor();
+        let _ = self.terminal.execute();
     }
 }
 
 impl Drop for Editor {
     /// Destructor of the editor for terminating correcly when the
     /// program finishes. Since it can possibly panic a panic hook is
-    /// also implemented.
+    /// also implemented. Editors that didn't set the terminal up
+    /// leave it alone.
     fn drop(&mut self) {
-        let _ = Terminal::terminate();
-        let _ = Terminal::cursor_block();
+        if !self.owns_terminal {
+            return;
+        }
+
+        let _ = self.terminal.terminate();
+        let _ = self.terminal.cursor_block();
         if self.should_quit {
-            Terminal::print("Goodbye.\r\n").unwrap();
+            self.terminal.print("Goodbye.\r\n").unwrap();
+        }
+    }
+}
+
+/// Rapresents what the editor was launched with, the file and
+/// where to put the cursor, both counted from 1.
+#[derive(Default)]
+struct LaunchArgs {
+    file_name: Option<String>,
+    line_number: Option<usize>,
+    column: Option<usize>,
+}
+
+impl LaunchArgs {
+    /// Finds the file to open and where to start, given either as `+N`
+    /// before or after the file or as `file:line` and `file:line:col`
+    /// like compilers print locations.
+    fn parse(args: &[String]) -> Self {
+        let mut launch = Self::default();
+
+        for arg in args {
+            match arg.strip_prefix('+').map(str::parse::<usize>) {
+                Some(Ok(n)) => launch.line_number = Some(n),
+                _ if launch.file_name.is_none() => launch.file_name = Some(arg.clone()),
+                _ => {}
+            }
+        }
+
+        if let Some(path) = launch.file_name.take() {
+            launch.file_name = Some(launch.split_location(path));
+        }
+
+        launch
+    }
+
+    /// Strips the `:line` or `:line:col` suffix from the path,
+    /// unless a file with that exact name exists.
+    fn split_location(&mut self, path: String) -> String {
+        if Path::new(&path).exists() {
+            return path;
+        }
+
+        let Some((head, last)) = path
+            .rsplit_once(':')
+            .and_then(|(head, last)| Some((head, last.parse::<usize>().ok()?)))
+        else {
+            return path;
+        };
+
+        if let Some((name, line)) = head
+            .rsplit_once(':')
+            .and_then(|(name, line)| Some((name, line.parse::<usize>().ok()?)))
+        {
+            self.line_number = self.line_number.or(Some(line));
+            self.column = Some(last);
+            name.to_string()
+        } else {
+            self.line_number = self.line_number.or(Some(last));
+            head.to_string()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn editor_with(text: &str) -> Editor {
+        let mut editor = Editor::from_text(text);
+        editor.feed_event(Event::Resize(80, 24));
+        editor
+    }
+
+    fn press(editor: &mut Editor, code: KeyCode, modifiers: KeyModifiers) {
+        editor.feed_event(Event::Key(KeyEvent::new(code, modifiers)));
+    }
+
+    fn type_keys(editor: &mut Editor, keys: &str) {
+        for ch in keys.chars() {
+            press(editor, KeyCode::Char(ch), KeyModifiers::NONE);
         }
     }
+
+    #[test]
+    fn alt_and_the_arrows_move_the_line() {
+        let mut editor = editor_with("one\ntwo\nthree");
+        press(&mut editor, KeyCode::Down, KeyModifiers::ALT);
+        assert_eq!(editor.snapshot(), ["two", "one", "three"]);
+        press(&mut editor, KeyCode::Up, KeyModifiers::ALT);
+        assert_eq!(editor.snapshot(), ["one", "two", "three"]);
+    }
+
+    #[test]
+    fn shift_tab_dedents_the_line_and_cycles_completions_back() {
+        let mut editor = editor_with("\t\tlet a;");
+        press(&mut editor, KeyCode::BackTab, KeyModifiers::SHIFT);
+        assert_eq!(editor.snapshot(), ["\tlet a;"]);
+        type_keys(&mut editor, "i");
+        press(&mut editor, KeyCode::BackTab, KeyModifiers::SHIFT);
+        assert!(editor.mode == EditorMode::Insert);
+        assert_eq!(editor.snapshot(), ["let a;"]);
+        press(&mut editor, KeyCode::Esc, KeyModifiers::NONE);
+
+        type_keys(&mut editor, ":set ft=");
+        press(&mut editor, KeyCode::Tab, KeyModifiers::NONE);
+        assert_eq!(editor.command_bar.get_line(), "set ft=python");
+        press(&mut editor, KeyCode::BackTab, KeyModifiers::SHIFT);
+        assert_eq!(editor.command_bar.get_line(), "set ft=text");
+        press(&mut editor, KeyCode::BackTab, KeyModifiers::SHIFT);
+        assert_eq!(editor.command_bar.get_line(), "set ft=sh");
+    }
+
+    #[test]
+    fn zz_saves_a_modified_buffer_before_quitting() {
+        let path = std::env::temp_dir().join("beppe-zz-test.txt");
+        let mut editor = editor_with("one");
+        type_keys(&mut editor, "i!");
+        press(&mut editor, KeyCode::Esc, KeyModifiers::NONE);
+        type_keys(&mut editor, "ZZ");
+        assert!(!editor.should_quit());
+        assert!(editor.command_bar.get_command() == Some(Cmd::SaveAs));
+
+        type_keys(&mut editor, path.to_str().unwrap());
+        press(&mut editor, KeyCode::Enter, KeyModifiers::NONE);
+        let saved = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(saved, "!one");
+        assert!(editor.should_quit());
+    }
+
+    #[test]
+    fn ctrl_v_inserts_without_closing_pairs_or_expanding_abbreviations() {
+        let mut editor = editor_with("");
+        editor
+            .view
+            .set_config(Config::parse("auto_close = true\nabbrev.teh = the"));
+        type_keys(&mut editor, "iteh");
+        press(&mut editor, KeyCode::Char('v'), KeyModifiers::CONTROL);
+        type_keys(&mut editor, "(");
+        press(&mut editor, KeyCode::Char('v'), KeyModifiers::CONTROL);
+        type_keys(&mut editor, "u0029");
+        assert_eq!(editor.snapshot(), ["teh()"]);
+    }
+
+    #[test]
+    fn ctrl_k_inserts_the_digraph_without_expanding_abbreviations() {
+        let mut editor = editor_with("");
+        editor.view.set_config(Config::parse("abbrev.teh = the"));
+        type_keys(&mut editor, "iteh");
+        press(&mut editor, KeyCode::Char('k'), KeyModifiers::CONTROL);
+        type_keys(&mut editor, "<<");
+        assert_eq!(editor.snapshot(), ["teh«"]);
+    }
+
+    #[test]
+    fn zq_quits_without_saving() {
+        let mut editor = editor_with("one");
+        type_keys(&mut editor, "i!");
+        press(&mut editor, KeyCode::Esc, KeyModifiers::NONE);
+        type_keys(&mut editor, "ZQ");
+        assert!(editor.should_quit());
+        assert!(editor.view.is_file_modified());
+    }
+
+    #[test]
+    fn zz_quits_at_once_without_changes() {
+        let mut editor = editor_with("one");
+        type_keys(&mut editor, "ZZ");
+        assert!(editor.should_quit());
+    }
+
+    #[test]
+    fn star_searches_the_word_under_the_cursor() {
+        let mut editor = editor_with("foo bar\nfood\nbar foo");
+        type_keys(&mut editor, "*");
+        assert_eq!(editor.view.search_term(), "foo");
+        type_keys(&mut editor, "i!");
+        assert_eq!(editor.snapshot(), ["foo bar", "food", "bar !foo"]);
+    }
+
+    #[test]
+    fn submitting_an_empty_search_repeats_the_last_one() {
+        let mut editor = editor_with("bar foo\nfoo bar");
+        type_keys(&mut editor, "/foo");
+        press(&mut editor, KeyCode::Enter, KeyModifiers::NONE);
+        press(&mut editor, KeyCode::Right, KeyModifiers::NONE);
+
+        type_keys(&mut editor, "/");
+        press(&mut editor, KeyCode::Char('u'), KeyModifiers::CONTROL);
+        press(&mut editor, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(editor.view.search_term(), "foo");
+        type_keys(&mut editor, "i!");
+        assert_eq!(editor.snapshot(), ["bar foo", "!foo bar"]);
+    }
+
+    #[test]
+    fn j_joins_with_a_space_and_gj_without() {
+        let mut editor = editor_with("ab\n  cd\n  ef");
+        type_keys(&mut editor, "J");
+        assert_eq!(editor.snapshot(), ["ab cd", "  ef"]);
+        type_keys(&mut editor, "gJ");
+        assert_eq!(editor.snapshot(), ["ab cd  ef"]);
+        type_keys(&mut editor, "i!");
+        assert_eq!(editor.snapshot(), ["ab cd!  ef"]);
+    }
+
+    /// The first row of the text as it is drawn.
+    fn first_row(editor: &mut Editor) -> String {
+        let frame = editor.render_frame();
+        let start = frame.find("\x1b[1;1H").unwrap();
+        let end = frame.find("\x1b[2;1H").unwrap();
+        frame[start..end].to_string()
+    }
+
+    #[test]
+    fn matches_stay_highlighted_while_typing_next_to_them() {
+        let mut editor = editor_with("a foo b\nfoo");
+        editor
+            .view
+            .set_config(Config::parse("word_highlight = false"));
+        type_keys(&mut editor, "/foo");
+        press(&mut editor, KeyCode::Enter, KeyModifiers::NONE);
+        type_keys(&mut editor, "ixy");
+        assert!(
+            first_row(&mut editor)
+                .ends_with("a\u{2423}xy\x1b[38;5;0m\x1b[48;5;13mfoo\x1b[0m\u{2423}b")
+        );
+
+        press(&mut editor, KeyCode::Right, KeyModifiers::NONE);
+        type_keys(&mut editor, "z");
+        assert!(first_row(&mut editor).ends_with("a\u{2423}xyfzoo\u{2423}b"));
+    }
+
+    #[test]
+    fn shift_and_the_arrows_extend_a_selection() {
+        let mut editor = editor_with("hello world");
+        press(&mut editor, KeyCode::Right, KeyModifiers::SHIFT);
+        press(&mut editor, KeyCode::Right, KeyModifiers::SHIFT);
+        assert!(editor.mode == EditorMode::Visual);
+        assert!(editor.view.selection_kind() == Some(VisualKind::Char));
+        type_keys(&mut editor, "d");
+        assert_eq!(editor.snapshot(), ["lo world"]);
+        assert!(editor.mode == EditorMode::Normal);
+    }
+
+    #[test]
+    fn a_bare_arrow_collapses_the_selection() {
+        let mut editor = editor_with("hello world");
+        type_keys(&mut editor, "i");
+        press(&mut editor, KeyCode::Right, KeyModifiers::SHIFT);
+        press(&mut editor, KeyCode::Right, KeyModifiers::NONE);
+        assert!(editor.mode == EditorMode::Insert);
+        assert!(editor.view.selection_kind().is_none());
+        type_keys(&mut editor, "!");
+        assert_eq!(editor.snapshot(), ["he!llo world"]);
+    }
+
+    #[test]
+    fn s_replaces_the_character_under_the_cursor() {
+        let mut editor = editor_with("cat");
+        press(&mut editor, KeyCode::Right, KeyModifiers::NONE);
+        type_keys(&mut editor, "so");
+        assert!(editor.mode == EditorMode::Insert);
+        assert_eq!(editor.snapshot(), ["cot"]);
+        press(&mut editor, KeyCode::Esc, KeyModifiers::NONE);
+        type_keys(&mut editor, "p");
+        assert_eq!(editor.snapshot(), ["cota"]);
+    }
+
+    #[test]
+    fn big_s_replaces_the_line_keeping_its_indentation() {
+        let mut editor = editor_with("fn f() {\n    let a = 1;\n}");
+        press(&mut editor, KeyCode::Down, KeyModifiers::NONE);
+        type_keys(&mut editor, "Sa");
+        assert!(editor.mode == EditorMode::Insert);
+        assert_eq!(editor.snapshot(), ["fn f() {", "    a", "}"]);
+    }
+
+    #[test]
+    fn a_new_search_starts_from_the_last_term() {
+        let mut editor = editor_with("foo bar\nbar foo");
+        type_keys(&mut editor, "/foo");
+        press(&mut editor, KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(editor.view.search_term(), "foo");
+
+        type_keys(&mut editor, "/");
+        assert_eq!(editor.command_bar.get_line(), "foo");
+        assert_eq!(editor.command_bar.cursor_location(), "Search: foo".len());
+        type_keys(&mut editor, "d");
+        assert_eq!(editor.command_bar.get_line(), "food");
+
+        press(&mut editor, KeyCode::Char('u'), KeyModifiers::CONTROL);
+        assert_eq!(editor.command_bar.get_line(), "");
+        assert_eq!(editor.command_bar.cursor_location(), "Search: ".len());
+    }
+
+    fn launch(args: &[&str]) -> (Option<String>, Option<usize>, Option<usize>) {
+        let args: Vec<String> = args.iter().map(ToString::to_string).collect();
+        let launch = LaunchArgs::parse(&args);
+        (launch.file_name, launch.line_number, launch.column)
+    }
+
+    fn file(
+        name: &str,
+        line: Option<usize>,
+        column: Option<usize>,
+    ) -> (Option<String>, Option<usize>, Option<usize>) {
+        (Some(name.to_string()), line, column)
+    }
+
+    #[test]
+    fn the_location_is_split_from_the_file() {
+        assert_eq!(launch(&[]), (None, None, None));
+        assert_eq!(launch(&["missing.rs"]), file("missing.rs", None, None));
+        assert_eq!(
+            launch(&["missing.rs:42"]),
+            file("missing.rs", Some(42), None)
+        );
+        assert_eq!(
+            launch(&["missing.rs:42:10"]),
+            file("missing.rs", Some(42), Some(10))
+        );
+    }
+
+    #[test]
+    fn the_line_can_be_given_with_a_plus() {
+        assert_eq!(
+            launch(&["+7", "missing.rs"]),
+            file("missing.rs", Some(7), None)
+        );
+        assert_eq!(
+            launch(&["missing.rs", "+7"]),
+            file("missing.rs", Some(7), None)
+        );
+        assert_eq!(
+            launch(&["+7", "missing.rs:42:10"]),
+            file("missing.rs", Some(7), Some(10))
+        );
+    }
+
+    #[test]
+    fn a_suffix_that_is_not_a_number_stays_in_the_name() {
+        assert_eq!(launch(&["missing.rs:x"]), file("missing.rs:x", None, None));
+        assert_eq!(
+            launch(&["missing.rs:x:3"]),
+            file("missing.rs:x", Some(3), None)
+        );
+        assert_eq!(
+            launch(&["missing.rs:-1"]),
+            file("missing.rs:-1", None, None)
+        );
+        assert_eq!(launch(&["+x", "missing.rs"]), file("+x", None, None));
+    }
 }
@@ -4,7 +4,7 @@ use crate::editor::{
 };
 use std::ops::Range;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum AnnotationType {
     None,
     Number,
@@ -16,9 +16,19 @@ pub enum AnnotationType {
     Lifetime,
     Comment,
     SelectedMatch,
+    Selection,
+    WordOccurrence,
+    DiffAdded,
+    DiffModified,
+    DiffDeleted,
+    Fold,
+    Touched,
+    CursorColumn,
+    CursorLine,
+    Cursor,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Annotation {
     pub range: Range<ByteIndex>,
     pub ty: AnnotationType,
@@ -51,6 +61,13 @@ impl AnnotatedLine {
         }
     }
 
+    /// Adds an annotation printed below all the others.
+    pub fn push_annotation_below(&mut self, range: Range<ByteIndex>, ty: AnnotationType) {
+        if !range.is_empty() {
+            self.annotations.insert(0, Annotation { range, ty });
+        }
+    }
+
     pub fn push_annotations(&mut self, annotations: &[Annotation]) {
         for a in annotations {
             self.push_annotation(a.range.clone(), a.ty);
@@ -61,6 +78,15 @@ impl AnnotatedLine {
         self.line.push_str(str);
     }
 
+    /// Inserts the text before the line, annotated with the type.
+    pub fn prepend_str(&mut self, str: &str, ty: AnnotationType) {
+        self.line.insert_str(0, str);
+        for ann in &mut self.annotations {
+            ann.right_shift(str.len());
+        }
+        self.push_annotation(0..str.len(), ty);
+    }
+
     pub fn replace(&mut self, range: Range<ByteIndex>, replacement: &str) {
         if range.is_empty() {
             return;
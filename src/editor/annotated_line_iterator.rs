@@ -1,8 +1,9 @@
-use crate::editor::annotated_line::{AnnotatedLine, Annotation, AnnotationType};
+use crate::editor::annotated_line::{AnnotatedLine, AnnotationType};
 
 pub struct AnnotatedLinePart<'a> {
     pub str: &'a str,
-    pub ty: AnnotationType,
+    /// The types of the annotations covering the part, in the order they were pushed.
+    pub layers: Vec<AnnotationType>,
 }
 
 pub struct AnnotatedLineIterator<'a> {
@@ -10,46 +11,40 @@ pub struct AnnotatedLineIterator<'a> {
     pub index: usize,
 }
 
+/// Splits the line where any annotation starts or ends, every part
+/// keeps the types of all the annotations covering it so that the
+/// colors of the last pushed are printed over the others (like a
+/// selection over a keyword).
 impl<'a> Iterator for AnnotatedLineIterator<'a> {
     type Item = AnnotatedLinePart<'a>;
     fn next(&mut self) -> Option<Self::Item> {
         let line = self.annotated_line.get_line();
         let annotations = self.annotated_line.get_annotations();
 
-        if self.index >= line.len() {
+        let start = self.index;
+        if start >= line.len() {
             return None;
         }
 
-        let current_annotation: Vec<&Annotation> = annotations
+        let layers = annotations
             .iter()
-            .filter(|ann| ann.range.start <= self.index && self.index < ann.range.end)
+            .filter(|ann| ann.range.start <= start && start < ann.range.end)
+            .map(|ann| ann.ty)
             .collect();
-        let current_annotation = current_annotation.last();
 
-        if let Some(ann) = current_annotation {
-            self.index = ann.range.end;
-            return Some(AnnotatedLinePart {
-                str: &line[ann.range.start..ann.range.end],
-                ty: ann.ty,
-            });
-        }
-
-        for ann in annotations {
-            if ann.range.start >= self.index {
-                let start_index = self.index;
-                self.index = ann.range.start;
-                return Some(AnnotatedLinePart {
-                    str: &line[start_index..self.index],
-                    ty: AnnotationType::None,
-                });
-            }
-        }
-
-        let start_index = self.index;
-        self.index = line.len();
+        let end = annotations
+            .iter()
+            .flat_map(|ann| [ann.range.start, ann.range.end])
+            .filter(|&boundary| {
+                boundary > start && boundary < line.len() && line.is_char_boundary(boundary)
+            })
+            .min()
+            .unwrap_or(line.len());
+
+        self.index = end;
         Some(AnnotatedLinePart {
-            str: &line[start_index..],
-            ty: AnnotationType::None,
+            str: &line[start..end],
+            layers,
         })
     }
 }
@@ -1,14 +1,66 @@
+use std::{cmp, collections::HashMap};
+
 use crate::editor::{
     editor_cmd::Direction,
-    line::Line,
+    file_type::FileType,
+    line::{GraphemeIndex, Line},
+    path_completion,
     terminal::{Terminal, TerminalSize},
     ui_component::UiComponent,
 };
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Cmd {
     SaveAs,
+    ConfirmOverwrite,
+    ConfirmReload,
+    QuitConfirm,
     Search,
+    SearchBackward,
+    SearchInSelection,
+    ReplaceInSelection,
+    AlignSelection,
+    Goto,
+    Ex,
+}
+
+impl Cmd {
+    /// Whether the text typed for this command is a path
+    /// that can be completed against the filesystem.
+    pub const fn takes_path(self) -> bool {
+        matches!(self, Self::SaveAs)
+    }
+
+    /// Whether Tab completes the text typed for this command.
+    pub const fn completes(self) -> bool {
+        matches!(self, Self::SaveAs | Self::Ex)
+    }
+
+    /// The command whose history is browsed, searches share
+    /// theirs whichever direction they go.
+    const fn history_key(self) -> Self {
+        match self {
+            Self::SearchBackward | Self::SearchInSelection => Self::Search,
+            command => command,
+        }
+    }
+
+    /// Whether the typed text is searched in the file.
+    pub const fn is_search(self) -> bool {
+        matches!(
+            self,
+            Self::Search | Self::SearchBackward | Self::SearchInSelection
+        )
+    }
+
+    /// Whether the command is a question answered by a single
+    /// key press, without waiting for Enter.
+    pub const fn is_confirmation(self) -> bool {
+        matches!(
+            self,
+            Self::ConfirmOverwrite | Self::ConfirmReload | Self::QuitConfirm
+        )
+    }
 }
 
 #[derive(Default)]
@@ -16,8 +68,16 @@ pub struct CommandBar {
     prompt: String,
     line: Line,
     command: Option<Cmd>,
-    cursor_location: usize,
+    grapheme_index: GraphemeIndex,
     needs_redraw: bool,
+    size: TerminalSize,
+    completions: Vec<String>,
+    completion_index: Option<usize>,
+    history: HashMap<Cmd, Vec<String>>,
+    history_index: Option<usize>,
+    draft: String,
+    /// The matches of the search being typed, shown after it.
+    match_count: Option<usize>,
 }
 
 impl CommandBar {
@@ -26,11 +86,20 @@ impl CommandBar {
 
         self.prompt = match command {
             Cmd::SaveAs => "Save As: ",
+            Cmd::ConfirmOverwrite => "File already exists, overwrite it? (y/n) ",
+            Cmd::ConfirmReload => "Discard the changes and reload the file? (y/n) ",
+            Cmd::QuitConfirm => "Save changes? (y/n/c) ",
             Cmd::Search => "Search: ",
+            Cmd::SearchBackward => "Search backward: ",
+            Cmd::SearchInSelection => "Search in selection: ",
+            Cmd::ReplaceInSelection => "Replace in selection (old/new): ",
+            Cmd::AlignSelection => "Align on: ",
+            Cmd::Goto => "Go to line: ",
+            Cmd::Ex => ":",
         }
         .to_string();
 
-        self.cursor_location = self.prompt.len();
+        self.grapheme_index = 0;
         self.set_needs_redraw(true);
     }
 
@@ -45,28 +114,230 @@ impl CommandBar {
     pub fn clear(&mut self) {
         self.prompt.clear();
         self.line.clear();
+        self.history_index = None;
+        self.match_count = None;
+        self.reset_completion();
         self.set_needs_redraw(true);
     }
 
+    pub fn set_match_count(&mut self, count: Option<usize>) {
+        if count != self.match_count {
+            self.match_count = count;
+            self.set_needs_redraw(true);
+        }
+    }
+
+    fn match_count_to_string(&self) -> String {
+        self.match_count
+            .map(|count| format!(" [{count}]"))
+            .unwrap_or_default()
+    }
+
+    /// The columns left to the typed text.
+    fn room(&self) -> usize {
+        self.size
+            .width
+            .saturating_sub(self.prompt.len())
+            .saturating_sub(self.match_count_to_string().len())
+    }
+
+    /// Replaces the typed text moving the cursor at its end.
+    pub fn set_line(&mut self, text: &str) {
+        self.line = Line::from(text);
+        self.grapheme_index = self.line.grapheme_count();
+        self.set_needs_redraw(true);
+    }
+
+    /// Saves the typed text in the history of the current command,
+    /// consecutive duplicates are stored once.
+    pub fn push_history(&mut self) {
+        let Some(command) = self.command else {
+            return;
+        };
+
+        let entry = self.line.to_string();
+        let history = self.history.entry(command.history_key()).or_default();
+        if !entry.is_empty() && history.last() != Some(&entry) {
+            history.push(entry);
+        }
+    }
+
+    /// Replaces the typed text with the previous entry in the
+    /// history, staying on the oldest one.
+    fn history_prev(&mut self) {
+        let Some(history) = self
+            .command
+            .and_then(|command| self.history.get(&command.history_key()))
+        else {
+            return;
+        };
+
+        let index = match self.history_index {
+            Some(index) => index.saturating_sub(1),
+            None if history.is_empty() => return,
+            None => {
+                self.draft = self.line.to_string();
+                history.len().saturating_sub(1)
+            }
+        };
+
+        let entry = history[index].clone();
+        self.history_index = Some(index);
+        self.reset_completion();
+        self.set_line(&entry);
+    }
+
+    /// Replaces the typed text with the next entry in the history,
+    /// going past the newest one restores what was being typed.
+    fn history_next(&mut self) {
+        let (Some(index), Some(history)) = (
+            self.history_index,
+            self.command
+                .and_then(|command| self.history.get(&command.history_key())),
+        ) else {
+            return;
+        };
+
+        let next = index.saturating_add(1);
+        let entry = if let Some(entry) = history.get(next) {
+            self.history_index = Some(next);
+            entry.clone()
+        } else {
+            self.history_index = None;
+            std::mem::take(&mut self.draft)
+        };
+
+        self.reset_completion();
+        self.set_line(&entry);
+    }
+
+    fn reset_completion(&mut self) {
+        self.completions.clear();
+        self.completion_index = None;
+    }
+
+    /// Completes the typed path, or the file type of `:set ft=`, with the longest
+    /// prefix shared by the matching entries, further presses cycle through the entries.
+    pub fn handle_completion(&mut self) {
+        if let Some(index) = self.completion_index {
+            let next = index
+                .saturating_add(1)
+                .checked_rem(self.completions.len())
+                .unwrap_or(0);
+            self.select_completion(next);
+            return;
+        }
+
+        let input = self.line.to_string();
+        let candidates = match self.command {
+            Some(cmd) if cmd.takes_path() => path_completion::candidates(&input),
+            Some(Cmd::Ex) => Self::file_type_candidates(&input),
+            _ => Vec::new(),
+        };
+        if candidates.is_empty() {
+            return;
+        }
+
+        let prefix = path_completion::longest_common_prefix(&candidates);
+        if candidates.len() == 1 || prefix != input {
+            self.set_line(&prefix);
+            self.completions.clear();
+        } else {
+            self.set_line(&candidates[0]);
+            self.completion_index = Some(0);
+            self.completions = candidates;
+        }
+    }
+
+    /// Cycles backwards through the entries, or completes
+    /// like Tab if they aren't being cycled yet.
+    pub fn handle_completion_back(&mut self) {
+        let Some(index) = self.completion_index else {
+            self.handle_completion();
+            return;
+        };
+
+        let previous = index
+            .checked_sub(1)
+            .unwrap_or_else(|| self.completions.len().saturating_sub(1));
+        self.select_completion(previous);
+    }
+
+    fn select_completion(&mut self, index: usize) {
+        self.completion_index = Some(index);
+        let candidate = self.completions[index].clone();
+        self.set_line(&candidate);
+    }
+
+    fn file_type_candidates(input: &str) -> Vec<String> {
+        let Some((option, name)) = input.split_once('=') else {
+            return Vec::new();
+        };
+
+        if option != "set ft" && option != "set filetype" {
+            return Vec::new();
+        }
+
+        FileType::NAMES
+            .iter()
+            .filter(|candidate| candidate.starts_with(name))
+            .map(|candidate| format!("{option}={candidate}"))
+            .collect()
+    }
+
+    /// Column of the cursor on the screen, the prompt included.
     pub fn cursor_location(&self) -> usize {
-        self.cursor_location
+        self.prompt.len().saturating_add(
+            self.line
+                .width_until(self.grapheme_index)
+                .saturating_sub(self.scroll()),
+        )
+    }
+
+    /// The columns of the typed text hidden on the left, so that
+    /// the cursor stays on the screen when the text doesn't fit.
+    fn scroll(&self) -> usize {
+        let room = cmp::max(self.room(), 1);
+        self.line
+            .width_until(self.grapheme_index)
+            .saturating_add(1)
+            .saturating_sub(room)
     }
 
     pub fn handle_backspace(&mut self) {
-        if self.cursor_location != 0 {
+        if self.grapheme_index != 0 {
             self.handle_movement(Direction::Left);
             self.handle_deletion();
         }
     }
 
     pub fn handle_deletion(&mut self) {
-        self.line.pop();
+        self.reset_completion();
+        self.line.remove_at(self.grapheme_index);
+        self.set_needs_redraw(true);
+    }
+
+    /// Deletes all the typed text.
+    pub fn handle_clear(&mut self) {
+        self.reset_completion();
+        self.line.clear();
+        self.grapheme_index = 0;
+        self.set_needs_redraw(true);
+    }
+
+    /// Deletes the word before the cursor.
+    pub fn handle_delete_word_back(&mut self) {
+        self.reset_completion();
+        let start = self.line.previous_word_start(self.grapheme_index);
+        self.line.remove_range(start..self.grapheme_index);
+        self.grapheme_index = start;
         self.set_needs_redraw(true);
     }
 
     pub fn handle_insertion(&mut self, sy: char) {
+        self.reset_completion();
         let old_len = self.line.grapheme_count();
-        self.line.push_chr(sy);
+        self.line.insert_char_at(self.grapheme_index, sy);
         let new_len = self.line.grapheme_count();
 
         #[allow(clippy::arithmetic_side_effects)]
@@ -76,27 +347,39 @@ impl CommandBar {
         }
     }
 
-    fn handle_movement(&mut self, mov: Direction) {
+    pub fn handle_movement(&mut self, mov: Direction) {
         match mov {
             Direction::Left => self.move_left(),
             Direction::Right => self.move_right(),
-            _ => unreachable!(),
+            Direction::Home | Direction::LineStart => self.grapheme_index = 0,
+            Direction::End => self.grapheme_index = self.line.grapheme_count(),
+            Direction::WordLeft => {
+                self.grapheme_index = self.line.previous_word_start(self.grapheme_index);
+            }
+            Direction::WordRight => {
+                self.grapheme_index = self.line.next_word_start(self.grapheme_index);
+            }
+            Direction::Up => self.history_prev(),
+            Direction::Down => self.history_next(),
+            _ => {}
         }
     }
 
     fn move_left(&mut self) {
-        if self.cursor_location > self.prompt.len() {
-            self.cursor_location = self.cursor_location.saturating_sub(1);
-        }
+        self.grapheme_index = self.grapheme_index.saturating_sub(1);
     }
 
     fn move_right(&mut self) {
-        self.cursor_location = self.cursor_location.saturating_add(1);
+        if self.grapheme_index < self.line.grapheme_count() {
+            self.grapheme_index = self.grapheme_index.saturating_add(1);
+        }
     }
 }
 
 impl UiComponent for CommandBar {
-    fn set_size(&mut self, _size: TerminalSize) {}
+    fn set_size(&mut self, size: TerminalSize) {
+        self.size = size;
+    }
 
     fn needs_redraw(&self) -> bool {
         self.needs_redraw
@@ -106,8 +389,101 @@ impl UiComponent for CommandBar {
         self.needs_redraw = val;
     }
 
-    fn draw(&mut self, pos_y: usize) -> Result<(), std::io::Error> {
-        Terminal::print_row(pos_y, &format!("{}{}", self.prompt, self.line))?;
+    fn draw(&mut self, terminal: &mut Terminal, pos_y: usize) -> Result<(), std::io::Error> {
+        let scroll = self.scroll();
+        let shown = self
+            .line
+            .get(scroll..scroll.saturating_add(self.room()), &[]);
+        terminal.print_row(
+            pos_y,
+            &format!(
+                "{}{}{}",
+                self.prompt,
+                shown.get_line(),
+                self.match_count_to_string()
+            ),
+        )?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bar_with(text: &str) -> CommandBar {
+        let mut bar = CommandBar::default();
+        bar.set_command(Cmd::Search);
+        for ch in text.chars() {
+            bar.handle_insertion(ch);
+        }
+        bar
+    }
+
+    #[test]
+    fn typing_in_the_middle_inserts_at_the_cursor() {
+        let mut bar = bar_with("helo");
+        bar.handle_movement(Direction::Left);
+        bar.handle_insertion('l');
+        assert_eq!(bar.get_line(), "hello");
+        assert_eq!(bar.grapheme_index, 4);
+
+        bar.handle_movement(Direction::Home);
+        bar.handle_insertion('>');
+        assert_eq!(bar.get_line(), ">hello");
+        assert_eq!(bar.grapheme_index, 1);
+    }
+
+    #[test]
+    fn clearing_deletes_all_the_typed_text() {
+        let mut bar = bar_with("abc");
+        bar.handle_movement(Direction::Left);
+        bar.handle_clear();
+        assert_eq!(bar.get_line(), "");
+        assert_eq!(bar.cursor_location(), "Search: ".len());
+        bar.handle_insertion('x');
+        assert_eq!(bar.get_line(), "x");
+    }
+
+    #[test]
+    fn the_cursor_stays_inside_the_typed_text() {
+        let mut bar = bar_with("ab\u{6f22}");
+        bar.set_size(TerminalSize {
+            width: 80,
+            height: 1,
+        });
+        bar.handle_movement(Direction::Right);
+        bar.handle_movement(Direction::Right);
+        assert_eq!(bar.grapheme_index, 3);
+        assert_eq!(bar.cursor_location(), "Search: ".len().saturating_add(4));
+
+        bar.handle_movement(Direction::Home);
+        bar.handle_movement(Direction::Left);
+        assert_eq!(bar.grapheme_index, 0);
+        assert_eq!(bar.cursor_location(), "Search: ".len());
+
+        bar.handle_movement(Direction::End);
+        assert_eq!(bar.grapheme_index, 3);
+        bar.handle_deletion();
+        assert_eq!(bar.get_line(), "ab\u{6f22}");
+    }
+
+    #[test]
+    fn deleting_in_the_middle_removes_around_the_cursor() {
+        let mut bar = bar_with("abcd");
+        bar.handle_movement(Direction::Left);
+        bar.handle_movement(Direction::Left);
+        bar.handle_backspace();
+        assert_eq!(bar.get_line(), "acd");
+        assert_eq!(bar.grapheme_index, 1);
+
+        bar.handle_deletion();
+        assert_eq!(bar.get_line(), "ad");
+        assert_eq!(bar.grapheme_index, 1);
+
+        bar.handle_movement(Direction::Home);
+        bar.handle_backspace();
+        assert_eq!(bar.get_line(), "ad");
+        assert_eq!(bar.grapheme_index, 0);
+    }
+}
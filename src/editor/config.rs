@@ -0,0 +1,283 @@
+use std::{collections::HashMap, env, fs, path::PathBuf};
+
+use crate::editor::{
+    file_type::FileType,
+    theme::{self, Style, Theme},
+};
+
+const CONFIG_DIR_NAME: &str = "beppe";
+const CONFIG_FILE_NAME: &str = "config";
+/// Wider tabs are clamped, the indentation is made of that many spaces.
+const MAX_TAB_WIDTH: usize = 32;
+
+/// Rapresents the settings that can differ between file types,
+/// the ones left unset follow the global ones.
+#[derive(Clone, Copy, Default)]
+pub struct FileTypeConfig {
+    pub tab_width: Option<usize>,
+    pub expand_tab: Option<bool>,
+}
+
+/// Rapresents the user settings read from the config file.
+/// The file is made of `key = value` lines, lines starting
+/// with `#` are comments and unknown keys are ignored.
+#[derive(Clone)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct Config {
+    /// Normalizes typed and pasted text to NFC before it enters
+    /// the buffer, so files will be saved as NFC when enabled.
+    pub normalize_input: bool,
+    /// Number of columns of a level of indentation.
+    pub tab_width: usize,
+    /// Indents with spaces instead of tabs.
+    pub expand_tab: bool,
+    /// Colors used for the highlighting, chosen with `theme = <name>`
+    /// and changed with `color.<kind> = <fg>` or `<fg> on <bg>`.
+    pub theme: Theme,
+    /// Ctrl-C leaves insert mode and the command bar like Esc,
+    /// otherwise it does nothing there.
+    pub ctrl_c_exits: bool,
+    /// Ctrl-Q on a modified file asks whether to save it,
+    /// otherwise it has to be pressed again a few times.
+    pub quit_prompt: bool,
+    /// Searches ignore the case of letters unless the
+    /// searched text contains an uppercase one.
+    pub smartcase: bool,
+    /// Highlights the matches of the search term until it's cleared,
+    /// insert mode included.
+    pub highlight_search: bool,
+    /// Highlights the other occurrences of the word under the cursor.
+    pub word_highlight: bool,
+    /// Applies the settings written in a `beppe:` comment on the
+    /// first or last line of the files, off since files can't be trusted.
+    pub modelines: bool,
+    /// Shows which lines were added, modified or deleted
+    /// since the file was loaded or saved.
+    pub diff_gutter: bool,
+    /// Marks the lines edited since the file was loaded or saved,
+    /// without comparing them with the file like `diff_gutter`.
+    pub touched_gutter: bool,
+    /// Highlights the screen column the cursor is on.
+    pub cursor_column: bool,
+    /// Typing an opening bracket or quote inserts the closing one too,
+    /// outside of strings and comments.
+    pub auto_close: bool,
+    /// Rows kept between the cursor and the top or the bottom
+    /// of the screen when scrolling.
+    pub scroll_margin: usize,
+    /// Scrolls over a few frames instead of jumping at once.
+    pub smooth_scroll: bool,
+    /// Pasted lines get the indentation of the cursor line and
+    /// use tabs or spaces like `expand_tab`, otherwise they are
+    /// pasted as they are.
+    pub paste_reindent: bool,
+    /// Shows the name and the version of the editor on an empty
+    /// file, otherwise only its first line is shown, left blank.
+    pub show_welcome: bool,
+    /// The character drawn on the rows after the end of
+    /// the file, which are left blank when it's empty.
+    pub eof_fill: String,
+    /// Highlights the line and the column of the cursor and
+    /// shows it as a block in every mode, for presentations.
+    pub focus_mode: bool,
+    /// Bodies typed in place of their trigger word when Tab is pressed
+    /// after it, set with `snippet.<trigger> = <body>` where `\n` and `\t`
+    /// are a newline and a tab and `$0` is where the cursor ends up.
+    pub snippets: HashMap<String, String>,
+    /// Texts typed in place of their abbreviation when a character
+    /// that isn't part of a word follows it in insert mode, set with
+    /// `abbrev.<abbreviation> = <text>` where `$date` is today's date.
+    pub abbreviations: HashMap<String, String>,
+    /// Settings of the files of a type, set with `ft.<type>.tab_width`
+    /// and `ft.<type>.expand_tab` using the names of `:set ft=`.
+    pub file_types: HashMap<FileType, FileTypeConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            normalize_input: false,
+            tab_width: 4,
+            expand_tab: false,
+            theme: Theme::default(),
+            ctrl_c_exits: false,
+            quit_prompt: true,
+            smartcase: true,
+            highlight_search: true,
+            word_highlight: true,
+            modelines: false,
+            diff_gutter: true,
+            touched_gutter: false,
+            cursor_column: false,
+            auto_close: true,
+            scroll_margin: 0,
+            smooth_scroll: false,
+            paste_reindent: false,
+            show_welcome: true,
+            eof_fill: String::from("~"),
+            focus_mode: false,
+            snippets: HashMap::new(),
+            abbreviations: HashMap::new(),
+            file_types: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Returns the directory where the editor keeps its
+    /// configuration and state files.
+    pub fn dir() -> Option<PathBuf> {
+        env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+            .map(|dir| dir.join(CONFIG_DIR_NAME))
+    }
+
+    /// Loads the config file, falling back to the defaults
+    /// if it doesn't exist or can't be read.
+    pub fn load() -> Self {
+        Self::dir()
+            .and_then(|dir| fs::read_to_string(dir.join(CONFIG_FILE_NAME)).ok())
+            .map_or_else(Self::default, |content| Self::parse(&content))
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let mut config = Self::default();
+
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if let Some((key, value)) = line.split_once('=') {
+                config.set(key.trim(), value.trim());
+            }
+        }
+
+        config
+    }
+
+    /// Sets a single option, returns `false` if the key or
+    /// the value are not valid.
+    pub fn set(&mut self, key: &str, value: &str) -> bool {
+        match key {
+            "normalize_input" => Self::parse_bool(value).map(|v| self.normalize_input = v),
+            "tab_width" => Self::parse_tab_width(value).map(|v| self.tab_width = v),
+            "expand_tab" => Self::parse_bool(value).map(|v| self.expand_tab = v),
+            "theme" => Theme::by_name(value).map(|v| self.theme = v),
+            "ctrl_c_exits" => Self::parse_bool(value).map(|v| self.ctrl_c_exits = v),
+            "quit_prompt" => Self::parse_bool(value).map(|v| self.quit_prompt = v),
+            "smartcase" => Self::parse_bool(value).map(|v| self.smartcase = v),
+            "highlight_search" => Self::parse_bool(value).map(|v| self.highlight_search = v),
+            "word_highlight" => Self::parse_bool(value).map(|v| self.word_highlight = v),
+            "modelines" => Self::parse_bool(value).map(|v| self.modelines = v),
+            "diff_gutter" => Self::parse_bool(value).map(|v| self.diff_gutter = v),
+            "touched_gutter" => Self::parse_bool(value).map(|v| self.touched_gutter = v),
+            "cursor_column" => Self::parse_bool(value).map(|v| self.cursor_column = v),
+            "focus_mode" => Self::parse_bool(value).map(|v| self.focus_mode = v),
+            "auto_close" => Self::parse_bool(value).map(|v| self.auto_close = v),
+            "scroll_margin" => value.parse().ok().map(|v| self.scroll_margin = v),
+            "smooth_scroll" => Self::parse_bool(value).map(|v| self.smooth_scroll = v),
+            "paste_reindent" => Self::parse_bool(value).map(|v| self.paste_reindent = v),
+            "show_welcome" => Self::parse_bool(value).map(|v| self.show_welcome = v),
+            "eof_fill" => (value.chars().count() <= 1).then(|| self.eof_fill = value.to_string()),
+            _ => key
+                .strip_prefix("snippet.")
+                .filter(|trigger| Self::is_trigger(trigger))
+                .map(|trigger| {
+                    self.snippets
+                        .insert(trigger.to_string(), Self::unescape(value));
+                })
+                .or_else(|| {
+                    key.strip_prefix("abbrev.")
+                        .filter(|abbreviation| {
+                            !abbreviation.is_empty() && !abbreviation.contains(char::is_whitespace)
+                        })
+                        .map(|abbreviation| {
+                            self.abbreviations
+                                .insert(abbreviation.to_string(), value.to_string());
+                        })
+                })
+                .or_else(|| self.set_file_type_option(key, value))
+                .or_else(|| {
+                    key.strip_prefix("color.")
+                        .and_then(theme::parse_annotation_type)
+                        .zip(Style::parse(value))
+                        .map(|(ty, style)| self.theme.set_style(ty, style))
+                }),
+        }
+        .is_some()
+    }
+
+    /// Sets an option of a file type, written as `ft.<type>.<option>`.
+    fn set_file_type_option(&mut self, key: &str, value: &str) -> Option<()> {
+        let (name, option) = key.strip_prefix("ft.")?.split_once('.')?;
+        let file_type = FileType::from_name(name)?;
+        let settings = self.file_types.entry(file_type).or_default();
+        match option {
+            "tab_width" => Self::parse_tab_width(value).map(|v| settings.tab_width = Some(v)),
+            "expand_tab" => Self::parse_bool(value).map(|v| settings.expand_tab = Some(v)),
+            _ => None,
+        }
+    }
+
+    /// Triggers are single words, so they can be found before the cursor.
+    fn is_trigger(trigger: &str) -> bool {
+        !trigger.is_empty() && trigger.chars().all(|ch| ch.is_alphanumeric() || ch == '_')
+    }
+
+    /// Replaces the `\n`, `\t` and `\\` escapes of a snippet body.
+    fn unescape(value: &str) -> String {
+        let mut body = String::with_capacity(value.len());
+        let mut chars = value.chars();
+        while let Some(ch) = chars.next() {
+            if ch != '\\' {
+                body.push(ch);
+                continue;
+            }
+            match chars.next() {
+                Some('n') => body.push('\n'),
+                Some('t') => body.push('\t'),
+                Some(other) => body.push(other),
+                None => body.push('\\'),
+            }
+        }
+        body
+    }
+
+    /// Tab widths go from 1 to `MAX_TAB_WIDTH`, the wider ones are clamped.
+    pub fn parse_tab_width(value: &str) -> Option<usize> {
+        value
+            .parse()
+            .ok()
+            .filter(|&width| width > 0)
+            .map(|width: usize| width.min(MAX_TAB_WIDTH))
+    }
+
+    fn parse_bool(value: &str) -> Option<bool> {
+        match value {
+            "true" | "on" | "yes" => Some(true),
+            "false" | "off" | "no" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tab_widths_are_clamped() {
+        let config = Config::parse("tab_width = 1000\nft.rust.tab_width = 99");
+        assert_eq!(config.tab_width, MAX_TAB_WIDTH);
+        assert_eq!(
+            config.file_types[&FileType::Rust].tab_width,
+            Some(MAX_TAB_WIDTH)
+        );
+
+        let config = Config::parse("tab_width = 0\nft.rust.tab_width = 2");
+        assert_eq!(config.tab_width, 4);
+        assert_eq!(config.file_types[&FileType::Rust].tab_width, Some(2));
+    }
+}
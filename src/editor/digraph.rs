@@ -0,0 +1,116 @@
+/// Two characters typed after Ctrl-K and the character they insert,
+/// a subset of the RFC 1345 digraphs also used by Vim.
+const DIGRAPHS: &[(char, char, char)] = &[
+    ('a', '\'', 'á'),
+    ('e', '\'', 'é'),
+    ('i', '\'', 'í'),
+    ('o', '\'', 'ó'),
+    ('u', '\'', 'ú'),
+    ('A', '\'', 'Á'),
+    ('E', '\'', 'É'),
+    ('I', '\'', 'Í'),
+    ('O', '\'', 'Ó'),
+    ('U', '\'', 'Ú'),
+    ('a', '!', 'à'),
+    ('e', '!', 'è'),
+    ('i', '!', 'ì'),
+    ('o', '!', 'ò'),
+    ('u', '!', 'ù'),
+    ('A', '!', 'À'),
+    ('E', '!', 'È'),
+    ('I', '!', 'Ì'),
+    ('O', '!', 'Ò'),
+    ('U', '!', 'Ù'),
+    ('a', '>', 'â'),
+    ('e', '>', 'ê'),
+    ('i', '>', 'î'),
+    ('o', '>', 'ô'),
+    ('u', '>', 'û'),
+    ('a', ':', 'ä'),
+    ('e', ':', 'ë'),
+    ('i', ':', 'ï'),
+    ('o', ':', 'ö'),
+    ('u', ':', 'ü'),
+    ('A', ':', 'Ä'),
+    ('O', ':', 'Ö'),
+    ('U', ':', 'Ü'),
+    ('a', '?', 'ã'),
+    ('o', '?', 'õ'),
+    ('n', '?', 'ñ'),
+    ('N', '?', 'Ñ'),
+    ('c', ',', 'ç'),
+    ('C', ',', 'Ç'),
+    ('s', 's', 'ß'),
+    ('a', 'e', 'æ'),
+    ('A', 'E', 'Æ'),
+    ('o', '/', 'ø'),
+    ('O', '/', 'Ø'),
+    ('a', 'a', 'å'),
+    ('A', 'A', 'Å'),
+    ('-', '>', '→'),
+    ('<', '-', '←'),
+    ('-', '!', '↑'),
+    ('-', 'v', '↓'),
+    ('=', '>', '⇒'),
+    ('<', '=', '≤'),
+    ('>', '=', '≥'),
+    ('!', '=', '≠'),
+    ('=', '3', '≡'),
+    ('?', '2', '≈'),
+    ('+', '-', '±'),
+    ('*', 'X', '×'),
+    ('-', ':', '÷'),
+    ('0', '0', '∞'),
+    ('D', 'G', '°'),
+    ('M', 'y', 'µ'),
+    ('a', '*', 'α'),
+    ('b', '*', 'β'),
+    ('g', '*', 'γ'),
+    ('d', '*', 'δ'),
+    ('l', '*', 'λ'),
+    ('p', '*', 'π'),
+    ('s', '*', 'σ'),
+    ('E', 'u', '€'),
+    ('P', 'd', '£'),
+    ('Y', 'e', '¥'),
+    ('C', 't', '¢'),
+    ('C', 'o', '©'),
+    ('R', 'g', '®'),
+    ('T', 'M', '™'),
+    ('S', 'E', '§'),
+    ('P', 'I', '¶'),
+    ('.', 'M', '·'),
+    ('<', '<', '«'),
+    ('>', '>', '»'),
+    ('\'', '6', '‘'),
+    ('\'', '9', '’'),
+    ('"', '6', '“'),
+    ('"', '9', '”'),
+    ('-', 'N', '–'),
+    ('-', 'M', '—'),
+    ('.', '.', '‥'),
+    (',', '.', '…'),
+    ('1', '2', '½'),
+    ('1', '4', '¼'),
+    ('3', '4', '¾'),
+    ('1', 'S', '¹'),
+    ('2', 'S', '²'),
+    ('3', 'S', '³'),
+    ('!', 'I', '¡'),
+    ('?', 'I', '¿'),
+    ('N', 'S', '\u{a0}'),
+];
+
+/// Returns the character of a digraph, the two characters
+/// can be typed in either order like in Vim.
+pub fn lookup(first: char, second: char) -> Option<char> {
+    DIGRAPHS
+        .iter()
+        .find(|&&(a, b, _)| (a, b) == (first, second))
+        .or_else(|| {
+            DIGRAPHS
+                .iter()
+                .find(|&&(a, b, _)| (a, b) == (second, first))
+        })
+        .map(|&(_, _, ch)| ch)
+}
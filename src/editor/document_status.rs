@@ -1,4 +1,37 @@
-use crate::editor::file_type::FileType;
+use crate::editor::{file_type::FileType, view::Encoding};
+
+/// Rapresents how far the screen is scrolled through
+/// the file, shown like the ruler of Vim.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScrollPosition {
+    /// The whole file fits on the screen.
+    #[default]
+    All,
+    Top,
+    Bottom,
+    /// Percentage of the lines that are above the screen
+    /// out of the ones that are not on it.
+    Percent(usize),
+}
+
+impl ScrollPosition {
+    pub fn new(first_visible_line: usize, height: usize, num_of_lines: usize) -> Self {
+        let above = first_visible_line;
+        let below = num_of_lines.saturating_sub(first_visible_line.saturating_add(height));
+
+        match (above, below) {
+            (0, 0) => Self::All,
+            (0, _) => Self::Top,
+            (_, 0) => Self::Bottom,
+            _ => Self::Percent(
+                above
+                    .saturating_mul(100)
+                    .checked_div(above.saturating_add(below))
+                    .unwrap_or(0),
+            ),
+        }
+    }
+}
 
 #[derive(Clone, Default, PartialEq, Eq)]
 pub struct DocumentStatus {
@@ -6,18 +39,37 @@ pub struct DocumentStatus {
     pub file_name: String,
     pub num_of_lines: usize,
     pub current_line: usize,
+    pub scroll_position: ScrollPosition,
     pub modified: bool,
+    pub read_only: bool,
+    pub encoding: Encoding,
+    pub final_newline: bool,
 }
 
 impl DocumentStatus {
     pub fn modified_indicator_to_string(&self) -> String {
-        if self.modified {
+        if self.read_only {
+            String::from("[large file, read-only]")
+        } else if self.modified {
             String::from("(modified)")
         } else {
             String::new()
         }
     }
 
+    /// The file type, followed by the encoding unless it's UTF-8
+    /// and by `[noeol]` when the last line has no line ending.
+    pub fn file_type_to_string(&self) -> String {
+        let mut file_type = self.file_type.to_string();
+        if self.encoding != Encoding::Utf8 {
+            file_type = format!("{file_type} [{}]", self.encoding);
+        }
+        if !self.final_newline {
+            file_type.push_str(" [noeol]");
+        }
+        file_type
+    }
+
     pub fn line_count_to_string(&self) -> String {
         format!("{} lines", self.num_of_lines)
     }
@@ -29,4 +81,13 @@ impl DocumentStatus {
             self.num_of_lines
         )
     }
+
+    pub fn scroll_position_to_string(&self) -> String {
+        match self.scroll_position {
+            ScrollPosition::All => String::from("All"),
+            ScrollPosition::Top => String::from("Top"),
+            ScrollPosition::Bottom => String::from("Bot"),
+            ScrollPosition::Percent(percent) => format!("{percent}%"),
+        }
+    }
 }
@@ -1,13 +1,26 @@
-use super::terminal::TerminalSize;
+use super::{file_type::FileType, terminal::TerminalSize, view::Encoding};
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 
+/// Rapresents the commands accepted while typing text, in insert
+/// mode and in the command bar. Esc exits, Ctrl-C is an `Interrupt`
+/// that only exits when `ctrl_c_exits` is set in the config.
 #[derive(Clone, Copy)]
 pub enum TextCommand {
     Exit,
+    Interrupt,
     Write(char),
+    Move(Direction),
+    /// A movement with Shift held, which selects the text it moves over.
+    SelectMove(Direction),
+    Tab,
+    BackTab,
     Enter,
     Deletion,
     Backspace,
+    DeleteToLineStart,
+    DeleteWordBack,
+    Literal,
+    Digraph,
 }
 
 impl TryFrom<Event> for TextCommand {
@@ -15,18 +28,41 @@ impl TryFrom<Event> for TextCommand {
 
     fn try_from(event: Event) -> Result<Self, Self::Error> {
         match event {
+            Event::Key(KeyEvent {
+                code, modifiers, ..
+            }) if let Some(direction) = shift_movement(code, modifiers) => {
+                Ok(TextCommand::SelectMove(direction))
+            }
             Event::Key(KeyEvent {
                 code, modifiers, ..
             }) => match (code, modifiers) {
-                (KeyCode::Esc, _) | (KeyCode::Char('c'), KeyModifiers::CONTROL) => {
-                    Ok(TextCommand::Exit)
-                }
+                (KeyCode::Esc, _) => Ok(TextCommand::Exit),
+                (KeyCode::Char('c'), KeyModifiers::CONTROL) => Ok(TextCommand::Interrupt),
                 (KeyCode::Backspace, _) => Ok(TextCommand::Backspace),
+                (KeyCode::Char('u'), KeyModifiers::CONTROL) => Ok(TextCommand::DeleteToLineStart),
+                (KeyCode::Char('w'), KeyModifiers::CONTROL) => Ok(TextCommand::DeleteWordBack),
+                (KeyCode::Char('v'), KeyModifiers::CONTROL) => Ok(TextCommand::Literal),
+                (KeyCode::Char('k'), KeyModifiers::CONTROL) => Ok(TextCommand::Digraph),
                 (KeyCode::Delete, _) => Ok(TextCommand::Deletion),
                 (KeyCode::Char(symbol), _) => Ok(TextCommand::Write(symbol)),
-                (KeyCode::Tab, _) => Ok(TextCommand::Write('\t')),
+                (KeyCode::Tab, _) => Ok(TextCommand::Tab),
+                (KeyCode::BackTab, _) => Ok(TextCommand::BackTab),
                 (KeyCode::Enter, _) => Ok(TextCommand::Enter),
-                _ => Err(String::from("todo!")),
+                (KeyCode::Left, KeyModifiers::CONTROL) => {
+                    Ok(TextCommand::Move(Direction::WordLeft))
+                }
+                (KeyCode::Right, KeyModifiers::CONTROL) => {
+                    Ok(TextCommand::Move(Direction::WordRight))
+                }
+                (KeyCode::Up, _) => Ok(TextCommand::Move(Direction::Up)),
+                (KeyCode::Down, _) => Ok(TextCommand::Move(Direction::Down)),
+                (KeyCode::Left, _) => Ok(TextCommand::Move(Direction::Left)),
+                (KeyCode::Right, _) => Ok(TextCommand::Move(Direction::Right)),
+                (KeyCode::Home, _) => Ok(TextCommand::Move(Direction::Home)),
+                (KeyCode::End, _) => Ok(TextCommand::Move(Direction::End)),
+                (KeyCode::PageUp, _) => Ok(TextCommand::Move(Direction::PageUp)),
+                (KeyCode::PageDown, _) => Ok(TextCommand::Move(Direction::PageDown)),
+                _ => Err(String::from("KeyEvent is not convertible in TextCommand")),
             },
 
             _ => Err(String::from(
@@ -36,6 +72,139 @@ impl TryFrom<Event> for TextCommand {
     }
 }
 
+/// Rapresents the commands that can be typed in the
+/// command bar after pressing `:`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExCommand<'a> {
+    Stats,
+    Recent,
+    Write,
+    ForceWrite,
+    /// Appends the lines to a file, typed as `w >>file`.
+    Append(&'a str),
+    Reload,
+    Format,
+    SetFileType(FileType),
+    /// Changes the encoding the file is saved with,
+    /// typed as `set enc=<name>`.
+    SetEncoding(Encoding),
+    /// Adds or removes the line ending after the last line,
+    /// typed as `set eol` or `set noeol`.
+    SetFinalNewline(bool),
+    /// Turns the highlighting of the search matches on or off,
+    /// typed as `set hlsearch` or `set nohlsearch`.
+    SetHighlightSearch(bool),
+    /// Hides the matches until the next search, typed as `noh`.
+    HideMatches,
+    /// Turns the highlighting of the word under the cursor
+    /// on or off, typed as `set wordhl` or `set nowordhl`.
+    SetWordHighlight(bool),
+    /// Turns the highlighting of the cursor column on or off,
+    /// typed as `set cursorcolumn` or `set nocursorcolumn`.
+    SetCursorColumn(bool),
+    /// Turns the focus mode on or off, typed as `focus`.
+    ToggleFocus,
+    /// Pipes the whole buffer through a shell command,
+    /// typed as `!cmd` or `%!cmd`.
+    Filter(&'a str),
+    Help,
+}
+
+impl<'a> TryFrom<&'a str> for ExCommand<'a> {
+    type Error = String;
+
+    fn try_from(input: &'a str) -> Result<Self, Self::Error> {
+        match input.trim() {
+            "stats" => Ok(Self::Stats),
+            "recent" => Ok(Self::Recent),
+            "w" | "write" => Ok(Self::Write),
+            "w!" | "write!" => Ok(Self::ForceWrite),
+            "e!" | "edit!" => Ok(Self::Reload),
+            "fmt" => Ok(Self::Format),
+            "help" | "h" => Ok(Self::Help),
+            "set eol" | "set endofline" => Ok(Self::SetFinalNewline(true)),
+            "set noeol" | "set noendofline" => Ok(Self::SetFinalNewline(false)),
+            "set hlsearch" => Ok(Self::SetHighlightSearch(true)),
+            "set nohlsearch" => Ok(Self::SetHighlightSearch(false)),
+            "noh" | "nohlsearch" => Ok(Self::HideMatches),
+            "set wordhl" => Ok(Self::SetWordHighlight(true)),
+            "set nowordhl" => Ok(Self::SetWordHighlight(false)),
+            "set cursorcolumn" => Ok(Self::SetCursorColumn(true)),
+            "set nocursorcolumn" => Ok(Self::SetCursorColumn(false)),
+            "focus" => Ok(Self::ToggleFocus),
+            cmd => {
+                if let Some(command) = cmd.strip_prefix('!').or_else(|| cmd.strip_prefix("%!")) {
+                    return if command.trim().is_empty() {
+                        Err(String::from("Missing shell command"))
+                    } else {
+                        Ok(Self::Filter(command.trim()))
+                    };
+                }
+
+                if let Some(file_name) = cmd
+                    .strip_prefix("write")
+                    .or_else(|| cmd.strip_prefix('w'))
+                    .and_then(|rest| rest.trim_start().strip_prefix(">>"))
+                {
+                    return if file_name.trim().is_empty() {
+                        Err(String::from("Missing file name"))
+                    } else {
+                        Ok(Self::Append(file_name.trim()))
+                    };
+                }
+
+                if let Some(name) = cmd
+                    .strip_prefix("set enc=")
+                    .or_else(|| cmd.strip_prefix("set encoding="))
+                {
+                    return Encoding::from_name(name)
+                        .map(Self::SetEncoding)
+                        .ok_or_else(|| format!("Unknown encoding: {name}"));
+                }
+
+                if let Some(name) = cmd
+                    .strip_prefix("set ft=")
+                    .or_else(|| cmd.strip_prefix("set filetype="))
+                {
+                    return FileType::from_name(name)
+                        .map(Self::SetFileType)
+                        .ok_or_else(|| format!("Unknown file type: {name}"));
+                }
+
+                Err(format!("Not an editor command: {cmd}"))
+            }
+        }
+    }
+}
+
+/// Rapresents the commands accepted while a `Picker`
+/// overlay is open.
+#[derive(Clone, Copy)]
+pub enum PickerCommand {
+    Up,
+    Down,
+    Select,
+    Exit,
+}
+
+impl TryFrom<Event> for PickerCommand {
+    type Error = String;
+
+    fn try_from(event: Event) -> Result<Self, Self::Error> {
+        match event {
+            Event::Key(KeyEvent { code, .. }) => match code {
+                KeyCode::Up | KeyCode::Char('k') => Ok(Self::Up),
+                KeyCode::Down | KeyCode::Char('j') => Ok(Self::Down),
+                KeyCode::Enter => Ok(Self::Select),
+                KeyCode::Esc | KeyCode::Char('q') => Ok(Self::Exit),
+                _ => Err(String::from("KeyEvent is not convertible in PickerCommand")),
+            },
+
+            _ => Err(String::from("Event is not convertible in PickerCommand")),
+        }
+    }
+}
+
 /// Rapresents the different directions we
 /// can take on the view.
 #[derive(Clone, Copy)]
@@ -47,7 +216,79 @@ pub enum Direction {
     Right,
     Down,
     Home,
+    LineStart,
     End,
+    WordLeft,
+    WordRight,
+    /// The start of the text typed in the last insertion, typed as `` `[ ``.
+    InsertStart,
+    /// The end of the text typed in the last insertion, typed as `` `] ``.
+    InsertEnd,
+}
+
+/// Rapresents which way a search goes, `n` repeats
+/// it the same way and `N` the opposite way.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum SearchDirection {
+    #[default]
+    Forward,
+    Backward,
+}
+
+/// Rapresents what a selection covers, the text between the anchor
+/// and the cursor, all of the lines they are on or the rectangle
+/// with the anchor and the cursor at its corners.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum VisualKind {
+    Char,
+    Line,
+    Block,
+}
+
+/// Rapresents the edits on the characters surrounding some text,
+/// the characters are typed after the command.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Surround {
+    /// Typed as `ys<char>` on a word or `S<char>` on a selection.
+    Add,
+    /// Typed as `cs<old><new>`.
+    Change,
+    /// Typed as `ds<char>`.
+    Delete,
+}
+
+impl Surround {
+    /// How many characters are typed after the command.
+    pub const fn chars_needed(self) -> usize {
+        match self {
+            Self::Add | Self::Delete => 1,
+            Self::Change => 2,
+        }
+    }
+}
+
+/// Rapresents the case conversions applied to the text.
+#[derive(Clone, Copy)]
+pub enum Case {
+    Upper,
+    Lower,
+    Toggle,
+}
+
+impl Case {
+    /// Converts the text, characters without a case are left
+    /// unchanged and a character can map to more than one.
+    pub fn apply(self, text: &str) -> String {
+        text.chars()
+            .map(|ch| match self {
+                Self::Upper => ch.to_uppercase().collect(),
+                Self::Lower => ch.to_lowercase().collect(),
+                Self::Toggle if ch.is_lowercase() => ch.to_uppercase().collect(),
+                Self::Toggle if ch.is_uppercase() => ch.to_lowercase().collect(),
+                Self::Toggle => ch.to_string(),
+            })
+            .collect()
+    }
 }
 
 /// Rapresents the commands on the editor that we
@@ -55,14 +296,92 @@ pub enum Direction {
 #[derive(Clone, Copy)]
 pub enum EditorCommand {
     Move(Direction),
+    /// A movement with Shift held, which starts a selection.
+    SelectMove(Direction),
+    Prefix(char),
+    Count(u32),
+    Increment(i64),
+    Indent,
+    Dedent,
+    DuplicateLine,
+    MoveLineUp,
+    MoveLineDown,
+    TransposeChars,
+    ToggleCase,
+    ChangeWordCase(Case),
+    BlockStart,
+    BlockEnd,
+    JoinLines,
+    /// Joins the lines without adding or removing spaces, typed as `gJ`.
+    JoinWithoutSpace,
+    DeleteLine,
+    /// Deletes from the cursor to the end of the line, typed as `D`.
+    DeleteToEol,
+    /// Deletes to the end of the line and enters insert mode, typed as `C`.
+    ChangeToEol,
+    /// Deletes the character under the cursor and enters
+    /// insert mode, typed as `s`.
+    SubstituteChar,
+    /// Deletes the text of the line after its indentation and
+    /// enters insert mode, typed as `S`.
+    SubstituteLine,
     Resize(TerminalSize),
     EnterInsert,
     Search,
+    SearchBackward,
+    ExCommand,
     Save,
     Quit,
+    SaveAndQuit,
+    ForceQuit,
     ExitSearch,
     NextOccurrence,
     PrevOccurrence,
+    /// Searches the word under the cursor, `*` forward and `#` backward.
+    SearchWord(SearchDirection),
+    /// Adds a cursor on the next occurrence of the word
+    /// under the cursor, typed as `Ctrl-D`.
+    AddCursor,
+    Visual(VisualKind),
+    Paste,
+    /// Starts recording the keys into the register, typed as `q<register>`.
+    Record(char),
+    /// Replays the keys recorded in the register, typed as `@<register>`,
+    /// `@@` replays the last replayed register.
+    Replay(char),
+    /// Opens the list of the commands, typed as Ctrl-P.
+    Palette,
+    /// Opens or closes the fold under the cursor, typed as `za`.
+    ToggleFold,
+    /// Shows the key bindings, typed as F1 or `:help`.
+    Help,
+    Surround(Surround),
+    /// Selects again the last selection, typed as `gv`.
+    Reselect,
+}
+
+/// The movement of an arrow, Home or End pressed with Shift,
+/// Ctrl-Shift moves by words.
+fn shift_movement(code: KeyCode, modifiers: KeyModifiers) -> Option<Direction> {
+    if modifiers == KeyModifiers::SHIFT {
+        match code {
+            KeyCode::Up => Some(Direction::Up),
+            KeyCode::Down => Some(Direction::Down),
+            KeyCode::Left => Some(Direction::Left),
+            KeyCode::Right => Some(Direction::Right),
+            KeyCode::Home => Some(Direction::Home),
+            KeyCode::End => Some(Direction::End),
+            _ => None,
+        }
+    } else if modifiers == KeyModifiers::SHIFT | KeyModifiers::CONTROL {
+        match code {
+            KeyCode::Left => Some(Direction::WordLeft),
+            KeyCode::Right => Some(Direction::WordRight),
+            _ => None,
+        }
+    } else {
+        None
+    }
 }
 
 impl TryFrom<Event> for EditorCommand {
@@ -72,24 +391,71 @@ impl TryFrom<Event> for EditorCommand {
     /// we support if it exists one.
     fn try_from(event: Event) -> Result<Self, Self::Error> {
         match event {
+            Event::Key(KeyEvent {
+                code, modifiers, ..
+            }) if let Some(direction) = shift_movement(code, modifiers) => {
+                Ok(Self::SelectMove(direction))
+            }
             Event::Key(KeyEvent {
                 code, modifiers, ..
             }) => match (code, modifiers) {
                 (KeyCode::Esc, _) => Ok(Self::ExitSearch),
+                (KeyCode::F(1), _) => Ok(Self::Help),
                 (KeyCode::Char('s'), KeyModifiers::CONTROL) => Ok(Self::Save),
                 (KeyCode::Char('q'), KeyModifiers::CONTROL) => Ok(Self::Quit),
+                (KeyCode::Char('t'), KeyModifiers::CONTROL) => Ok(Self::TransposeChars),
+                (KeyCode::Char('a'), KeyModifiers::CONTROL) => Ok(Self::Increment(1)),
+                (KeyCode::Char('x'), KeyModifiers::CONTROL) => Ok(Self::Increment(-1)),
+                (KeyCode::Char('d'), KeyModifiers::CONTROL) => Ok(Self::AddCursor),
                 (KeyCode::Char('i'), _) => Ok(Self::EnterInsert),
+                (KeyCode::Char('v'), KeyModifiers::NONE) => Ok(Self::Visual(VisualKind::Char)),
+                (KeyCode::Char('v'), KeyModifiers::CONTROL) => Ok(Self::Visual(VisualKind::Block)),
+                (KeyCode::Char('V'), _) => Ok(Self::Visual(VisualKind::Line)),
+                (KeyCode::Char('D'), _) => Ok(Self::DeleteToEol),
+                (KeyCode::Char('C'), _) => Ok(Self::ChangeToEol),
+                (KeyCode::Char('s'), KeyModifiers::NONE) => Ok(Self::SubstituteChar),
+                (KeyCode::Char('S'), _) => Ok(Self::SubstituteLine),
+                (KeyCode::Char('p'), KeyModifiers::NONE) => Ok(Self::Paste),
+                (KeyCode::Char('p'), KeyModifiers::CONTROL) => Ok(Self::Palette),
                 (KeyCode::Char('n'), _) => Ok(Self::NextOccurrence),
                 (KeyCode::Char('N'), _) => Ok(Self::PrevOccurrence),
+                (KeyCode::Char('*'), _) => Ok(Self::SearchWord(SearchDirection::Forward)),
+                (KeyCode::Char('#'), _) => Ok(Self::SearchWord(SearchDirection::Backward)),
+
+                (KeyCode::Down, m) if m == KeyModifiers::ALT | KeyModifiers::SHIFT => {
+                    Ok(Self::DuplicateLine)
+                }
+
+                (KeyCode::Up, KeyModifiers::ALT) => Ok(Self::MoveLineUp),
+                (KeyCode::Down, KeyModifiers::ALT) => Ok(Self::MoveLineDown),
+
+                (KeyCode::Left, KeyModifiers::CONTROL) => Ok(Self::Move(Direction::WordLeft)),
+                (KeyCode::Right, KeyModifiers::CONTROL) => Ok(Self::Move(Direction::WordRight)),
 
                 (KeyCode::Up | KeyCode::Char('k'), _) => Ok(Self::Move(Direction::Up)),
                 (KeyCode::Right | KeyCode::Char('l'), _) => Ok(Self::Move(Direction::Right)),
                 (KeyCode::Left | KeyCode::Char('h'), _) => Ok(Self::Move(Direction::Left)),
                 (KeyCode::Down | KeyCode::Char('j'), _) => Ok(Self::Move(Direction::Down)),
 
-                (KeyCode::Home | KeyCode::Char('0'), _) => Ok(Self::Move(Direction::Home)),
+                (KeyCode::Home, _) => Ok(Self::Move(Direction::Home)),
+                (KeyCode::Char('0'), _) => Ok(Self::Move(Direction::LineStart)),
                 (KeyCode::End | KeyCode::Char('$'), _) => Ok(Self::Move(Direction::End)),
                 (KeyCode::Char('/'), _) => Ok(Self::Search),
+                (KeyCode::Char('?'), _) => Ok(Self::SearchBackward),
+                (KeyCode::Char(':'), _) => Ok(Self::ExCommand),
+                (KeyCode::Char('~'), _) => Ok(Self::ToggleCase),
+                (KeyCode::Char('J'), _) => Ok(Self::JoinLines),
+                (KeyCode::BackTab, _) => Ok(Self::Dedent),
+                (KeyCode::Char(digit @ '1'..='9'), _) => {
+                    Ok(Self::Count(digit.to_digit(10).unwrap_or(0)))
+                }
+                (
+                    KeyCode::Char(
+                        prefix @ ('>' | '<' | 'g' | '[' | ']' | 'd' | 'c' | 'y' | 'z' | 'Z' | 'q'
+                        | '@' | '`'),
+                    ),
+                    _,
+                ) => Ok(Self::Prefix(prefix)),
 
                 (KeyCode::PageUp, _) | (KeyCode::Char('b'), KeyModifiers::CONTROL) => {
                     Ok(Self::Move(Direction::PageUp))
@@ -110,3 +476,181 @@ impl TryFrom<Event> for EditorCommand {
         }
     }
 }
+
+impl TryFrom<(char, Event)> for EditorCommand {
+    type Error = String;
+
+    /// Allows conversion of a key sequence made of a prefix
+    /// and the following `Event` to a `EditorCommand`.
+    fn try_from((prefix, event): (char, Event)) -> Result<Self, Self::Error> {
+        match event {
+            Event::Key(KeyEvent { code, .. }) => match (prefix, code) {
+                ('>', KeyCode::Char('>')) => Ok(Self::Indent),
+                ('<', KeyCode::Char('<')) => Ok(Self::Dedent),
+                ('g', KeyCode::Char('u')) => Ok(Self::ChangeWordCase(Case::Lower)),
+                ('g', KeyCode::Char('U')) => Ok(Self::ChangeWordCase(Case::Upper)),
+                ('g', KeyCode::Char('~')) => Ok(Self::ChangeWordCase(Case::Toggle)),
+                ('g', KeyCode::Char('v')) => Ok(Self::Reselect),
+                ('g', KeyCode::Char('J')) => Ok(Self::JoinWithoutSpace),
+                ('`', KeyCode::Char('[')) => Ok(Self::Move(Direction::InsertStart)),
+                ('`', KeyCode::Char(']')) => Ok(Self::Move(Direction::InsertEnd)),
+                ('d', KeyCode::Char('d')) => Ok(Self::DeleteLine),
+                ('y', KeyCode::Char('s')) => Ok(Self::Surround(Surround::Add)),
+                ('c', KeyCode::Char('s')) => Ok(Self::Surround(Surround::Change)),
+                ('d', KeyCode::Char('s')) => Ok(Self::Surround(Surround::Delete)),
+                ('z', KeyCode::Char('a')) => Ok(Self::ToggleFold),
+                ('Z', KeyCode::Char('Z')) => Ok(Self::SaveAndQuit),
+                ('Z', KeyCode::Char('Q')) => Ok(Self::ForceQuit),
+                ('[', KeyCode::Char('{')) => Ok(Self::BlockStart),
+                (']', KeyCode::Char('}')) => Ok(Self::BlockEnd),
+                ('q', KeyCode::Char(register)) if register.is_ascii_alphanumeric() => {
+                    Ok(Self::Record(register))
+                }
+                ('@', KeyCode::Char(register)) if register.is_ascii_alphanumeric() => {
+                    Ok(Self::Replay(register))
+                }
+                ('@', KeyCode::Char('@')) => Ok(Self::Replay('@')),
+                _ => Err(String::from(
+                    "Key sequence is not convertible in EditorCommand",
+                )),
+            },
+
+            _ => Err(String::from("Event is not convertible in EditorCommand")),
+        }
+    }
+}
+
+/// Rapresents the commands accepted while a selection is active.
+#[derive(Clone, Copy)]
+pub enum VisualCommand {
+    Move(Direction),
+    /// A movement by an arrow key, Home or End without modifiers,
+    /// which ends a selection started with Shift.
+    Arrow(Direction),
+    Prefix(char),
+    Select(VisualKind),
+    Delete,
+    Yank,
+    Indent,
+    Dedent,
+    ToggleComment,
+    Fold,
+    Surround,
+    /// Adds the step times its ordinal to the first
+    /// number of every selected line.
+    IncrementSequence(i64),
+    Align,
+    InsertBlock,
+    AppendBlock,
+    Search,
+    Replace,
+    Resize(TerminalSize),
+    Exit,
+}
+
+impl TryFrom<Event> for VisualCommand {
+    type Error = String;
+
+    /// The movements are the same of `EditorCommand`.
+    fn try_from(event: Event) -> Result<Self, Self::Error> {
+        if let Event::Key(KeyEvent {
+            code, modifiers, ..
+        }) = event
+        {
+            match (code, modifiers) {
+                (KeyCode::Esc, _) => return Ok(Self::Exit),
+                (KeyCode::Char('v'), KeyModifiers::NONE) => {
+                    return Ok(Self::Select(VisualKind::Char));
+                }
+                (KeyCode::Char('v'), KeyModifiers::CONTROL) => {
+                    return Ok(Self::Select(VisualKind::Block));
+                }
+                (KeyCode::Char('V'), _) => return Ok(Self::Select(VisualKind::Line)),
+                (KeyCode::Char('I'), _) => return Ok(Self::InsertBlock),
+                (KeyCode::Char('A'), _) => return Ok(Self::AppendBlock),
+                (KeyCode::Char('S'), _) => return Ok(Self::Surround),
+                (KeyCode::Char('d' | 'x'), _) => return Ok(Self::Delete),
+                (KeyCode::Char('y'), _) => return Ok(Self::Yank),
+                (KeyCode::Char('>'), _) => return Ok(Self::Indent),
+                (KeyCode::Char('<'), _) => return Ok(Self::Dedent),
+                (KeyCode::Char(prefix @ ('g' | 'z' | '`')), _) => return Ok(Self::Prefix(prefix)),
+                (KeyCode::Char('/'), _) => return Ok(Self::Search),
+                (KeyCode::Char(':'), _) => return Ok(Self::Replace),
+                _ => {}
+            }
+        }
+
+        let arrow = matches!(
+            event,
+            Event::Key(KeyEvent {
+                code: KeyCode::Up
+                    | KeyCode::Down
+                    | KeyCode::Left
+                    | KeyCode::Right
+                    | KeyCode::Home
+                    | KeyCode::End,
+                modifiers: KeyModifiers::NONE,
+                ..
+            })
+        );
+        match EditorCommand::try_from(event) {
+            Ok(EditorCommand::Move(direction)) if arrow => Ok(Self::Arrow(direction)),
+            Ok(EditorCommand::Move(direction) | EditorCommand::SelectMove(direction)) => {
+                Ok(Self::Move(direction))
+            }
+            Ok(EditorCommand::Resize(size)) => Ok(Self::Resize(size)),
+            _ => Err(String::from("Event is not convertible in VisualCommand")),
+        }
+    }
+}
+
+impl TryFrom<(char, Event)> for VisualCommand {
+    type Error = String;
+
+    fn try_from((prefix, event): (char, Event)) -> Result<Self, Self::Error> {
+        match event {
+            Event::Key(KeyEvent {
+                code, modifiers, ..
+            }) => match (prefix, code) {
+                ('g', KeyCode::Char('a')) if modifiers == KeyModifiers::CONTROL => {
+                    Ok(Self::IncrementSequence(1))
+                }
+                ('g', KeyCode::Char('x')) if modifiers == KeyModifiers::CONTROL => {
+                    Ok(Self::IncrementSequence(-1))
+                }
+                ('g', KeyCode::Char('a')) => Ok(Self::Align),
+                ('g', KeyCode::Char('c')) => Ok(Self::ToggleComment),
+                ('z', KeyCode::Char('f')) => Ok(Self::Fold),
+                ('`', KeyCode::Char('[')) => Ok(Self::Move(Direction::InsertStart)),
+                ('`', KeyCode::Char(']')) => Ok(Self::Move(Direction::InsertEnd)),
+                _ => Err(String::from(
+                    "Key sequence is not convertible in VisualCommand",
+                )),
+            },
+            _ => Err(String::from(
+                "Key sequence is not convertible in VisualCommand",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn back_tab() -> Event {
+        Event::Key(KeyEvent::new(KeyCode::BackTab, KeyModifiers::SHIFT))
+    }
+
+    #[test]
+    fn back_tab_dedents() {
+        assert!(matches!(
+            EditorCommand::try_from(back_tab()),
+            Ok(EditorCommand::Dedent)
+        ));
+        assert!(matches!(
+            TextCommand::try_from(back_tab()),
+            Ok(TextCommand::BackTab)
+        ));
+    }
+}
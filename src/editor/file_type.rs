@@ -1,10 +1,53 @@
 use std::fmt::Display;
 
-#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
 pub enum FileType {
     #[default]
     PlainText,
     Rust,
+    Python,
+    Shell,
+}
+
+impl FileType {
+    /// The names accepted by `:set ft=`.
+    pub const NAMES: [&str; 4] = ["python", "rust", "sh", "text"];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "rust" => Some(Self::Rust),
+            "python" => Some(Self::Python),
+            "sh" => Some(Self::Shell),
+            "text" => Some(Self::PlainText),
+            _ => None,
+        }
+    }
+
+    /// The prefix of a line comment, if the language has one.
+    pub const fn line_comment(self) -> Option<&'static str> {
+        match self {
+            Self::Rust => Some("//"),
+            Self::Python | Self::Shell => Some("#"),
+            Self::PlainText => None,
+        }
+    }
+
+    /// Picks the type from the interpreter named in a `#!` line,
+    /// like `#!/bin/sh` or `#!/usr/bin/env python3`.
+    pub fn from_shebang(line: &str) -> Option<Self> {
+        let mut words = line.strip_prefix("#!")?.split_whitespace();
+        let mut interpreter = words.next()?.rsplit('/').next()?;
+        if interpreter == "env" {
+            interpreter = words.find(|word| !word.starts_with('-'))?;
+        }
+
+        match interpreter.trim_end_matches(|ch: char| ch.is_ascii_digit() || ch == '.') {
+            "python" => Some(Self::Python),
+            "sh" | "bash" | "zsh" | "dash" | "ksh" => Some(Self::Shell),
+            "cargo" | "rust-script" => Some(Self::Rust),
+            _ => None,
+        }
+    }
 }
 
 impl Display for FileType {
@@ -15,7 +58,43 @@ impl Display for FileType {
             match self {
                 FileType::PlainText => "Text",
                 FileType::Rust => "Rust",
+                FileType::Python => "Python",
+                FileType::Shell => "Shell",
             }
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_interpreter_of_the_shebang_picks_the_type() {
+        assert_eq!(FileType::from_shebang("#!/bin/sh"), Some(FileType::Shell));
+        assert_eq!(
+            FileType::from_shebang("#! /bin/bash -e"),
+            Some(FileType::Shell)
+        );
+        assert_eq!(
+            FileType::from_shebang("#!/usr/bin/env python3"),
+            Some(FileType::Python)
+        );
+        assert_eq!(
+            FileType::from_shebang("#!/usr/bin/env -S python3.12 -u"),
+            Some(FileType::Python)
+        );
+        assert_eq!(
+            FileType::from_shebang("#!/usr/bin/env cargo +nightly -Zscript"),
+            Some(FileType::Rust)
+        );
+    }
+
+    #[test]
+    fn other_lines_pick_no_type() {
+        assert_eq!(FileType::from_shebang("#!/usr/bin/perl"), None);
+        assert_eq!(FileType::from_shebang("# /bin/sh"), None);
+        assert_eq!(FileType::from_shebang("#!"), None);
+        assert_eq!(FileType::from_shebang("#!/usr/bin/env"), None);
+    }
+}
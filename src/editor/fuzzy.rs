@@ -0,0 +1,125 @@
+/// Score of every matched character.
+const MATCH_SCORE: i64 = 4;
+/// Bonus for a character matched right after the previous one.
+const CONSECUTIVE_BONUS: i64 = 8;
+/// Bonus for a character matched at the start of a word.
+const WORD_START_BONUS: i64 = 6;
+/// Penalty for every character skipped before a matched one.
+const GAP_PENALTY: i64 = 1;
+
+/// Matches the characters of the pattern against the candidate, in order
+/// and ignoring their case. Returns the score, higher when the characters
+/// are close to each other or start words, and the indices of the matched
+/// characters of the candidate, so that they can be highlighted.
+pub fn fuzzy_match(pattern: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let pattern: Vec<char> = pattern.chars().flat_map(char::to_lowercase).collect();
+    let chars: Vec<char> = candidate.chars().collect();
+    if pattern.is_empty() {
+        return Some((0, Vec::new()));
+    }
+
+    // The best score of the pattern up to `i` with its character `i`
+    // on the candidate character `j`, along with the previous one.
+    let mut best: Vec<Vec<Option<(i64, usize)>>> = vec![vec![None; chars.len()]; pattern.len()];
+    for (i, &wanted) in pattern.iter().enumerate() {
+        for (j, &ch) in chars.iter().enumerate() {
+            if !ch.to_lowercase().eq([wanted]) {
+                continue;
+            }
+
+            let bonus = if is_word_start(&chars, j) {
+                MATCH_SCORE.saturating_add(WORD_START_BONUS)
+            } else {
+                MATCH_SCORE
+            };
+
+            best[i][j] = if i == 0 {
+                Some((bonus.saturating_sub(penalty(j)), j))
+            } else {
+                best[i.saturating_sub(1)][..j]
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(k, previous)| {
+                        let (score, _) = (*previous)?;
+                        let gap = j.saturating_sub(k).saturating_sub(1);
+                        let score = if gap == 0 {
+                            score.saturating_add(CONSECUTIVE_BONUS)
+                        } else {
+                            score.saturating_sub(penalty(gap))
+                        };
+                        Some((score.saturating_add(bonus), k))
+                    })
+                    .max_by_key(|&(score, _)| score)
+            };
+        }
+    }
+
+    let (mut j, score) = best
+        .last()?
+        .iter()
+        .enumerate()
+        .filter_map(|(j, cell)| cell.map(|(score, _)| (j, score)))
+        .max_by_key(|&(_, score)| score)?;
+
+    let mut indices = vec![j];
+    for i in (1..pattern.len()).rev() {
+        j = best[i][j]?.1;
+        indices.push(j);
+    }
+    indices.reverse();
+
+    Some((score, indices))
+}
+
+/// Whether the character starts a word, after a separator
+/// or as an uppercase letter following a lowercase one.
+fn is_word_start(chars: &[char], index: usize) -> bool {
+    let Some(previous) = index.checked_sub(1).and_then(|i| chars.get(i)) else {
+        return true;
+    };
+    let ch = chars.get(index).copied().unwrap_or_default();
+
+    !previous.is_alphanumeric() || previous.is_lowercase() && ch.is_uppercase()
+}
+
+fn penalty(skipped: usize) -> i64 {
+    i64::try_from(skipped)
+        .unwrap_or(i64::MAX)
+        .saturating_mul(GAP_PENALTY)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(pattern: &str, candidate: &str) -> i64 {
+        fuzzy_match(pattern, candidate).map_or(i64::MIN, |(score, _)| score)
+    }
+
+    fn indices(pattern: &str, candidate: &str) -> Option<Vec<usize>> {
+        fuzzy_match(pattern, candidate).map(|(_, indices)| indices)
+    }
+
+    #[test]
+    fn the_characters_must_all_match_in_order() {
+        assert_eq!(indices("", "abc"), Some(Vec::new()));
+        assert_eq!(indices("acb", "abc"), None);
+        assert_eq!(indices("abcd", "abc"), None);
+        assert_eq!(indices("FB", "foo_bar"), Some(vec![0, 4]));
+    }
+
+    #[test]
+    fn the_indices_are_the_best_placed_characters() {
+        assert_eq!(indices("ab", "a_ab"), Some(vec![2, 3]));
+        assert_eq!(indices("fb", "fooBar"), Some(vec![0, 3]));
+        assert_eq!(indices("\u{e9}c", "caf\u{e9}_c"), Some(vec![3, 5]));
+    }
+
+    #[test]
+    fn close_matches_score_higher() {
+        assert!(score("save", "save_file") > score("save", "s_a_v_e"));
+        assert!(score("of", "open_file") > score("of", "proof"));
+        assert!(score("rf", "recent_files") > score("rf", "rust_perf"));
+        assert!(score("pal", "palette") > score("pal", "open_all"));
+    }
+}
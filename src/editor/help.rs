@@ -0,0 +1,215 @@
+use std::fmt::Write;
+
+use unicode_width::UnicodeWidthStr;
+
+/// Rapresents the key bindings of a mode listed by the help.
+struct Section {
+    mode: &'static str,
+    bindings: &'static [(&'static str, &'static str)],
+}
+
+/// The key bindings of every mode, they have to follow the
+/// conversions of the events in `editor_cmd`.
+const SECTIONS: &[Section] = &[
+    Section {
+        mode: "Normal mode",
+        bindings: &[
+            ("h j k l, arrows", "Move the cursor"),
+            ("Ctrl-Left, Ctrl-Right", "Move by words"),
+            (
+                "Home",
+                "Go to the first non blank, or to the start of the line",
+            ),
+            ("0", "Go to the start of the line"),
+            ("$, End", "Go to the end of the line"),
+            ("PageUp, Ctrl-B", "Scroll up by a page"),
+            ("PageDown, Ctrl-F", "Scroll down by a page"),
+            ("[{  ]}", "Go to the start or the end of the block"),
+            (
+                "`[  `]",
+                "Go to the start or the end of the last inserted text",
+            ),
+            ("1-9", "Type a count for the next command"),
+            ("i", "Enter insert mode"),
+            ("v, V, Ctrl-V", "Select characters, lines or a block"),
+            (
+                "Shift-arrows",
+                "Select characters, the arrows alone end the selection",
+            ),
+            ("gv", "Select again the last selection"),
+            ("/  ?", "Search forward or backward"),
+            ("n  N", "Go to the next or the previous match"),
+            (
+                "*  #",
+                "Search the word under the cursor forward or backward",
+            ),
+            ("Esc", "Stop highlighting the matches, remove the cursors"),
+            (
+                "Ctrl-D",
+                "Add a cursor on the next occurrence of the word, then type in insert mode",
+            ),
+            (":", "Type an editor command"),
+            ("Ctrl-P", "Open the command palette"),
+            ("F1", "Show this help"),
+            ("p", "Paste"),
+            ("dd", "Delete the line"),
+            ("D  C", "Delete to the end of the line, C then inserts"),
+            (
+                "s  S",
+                "Change the character, or the line keeping its indentation",
+            ),
+            ("J", "Join the line with the next one"),
+            ("gJ", "Join the lines without adding or removing spaces"),
+            (">>  <<, Shift-Tab", "Indent or dedent the line"),
+            ("~", "Toggle the case of the character"),
+            (
+                "gu  gU  g~",
+                "Lowercase, uppercase or toggle the case of the word",
+            ),
+            ("Ctrl-A  Ctrl-X", "Increment or decrement the number"),
+            ("Ctrl-T", "Transpose the characters"),
+            ("Alt-Up  Alt-Down", "Move the line up or down"),
+            ("Alt-Shift-Down", "Duplicate the line"),
+            ("ys<char>", "Surround the word with the pair of char"),
+            ("cs<old><new>", "Change the surrounding pair"),
+            ("ds<char>", "Delete the surrounding pair"),
+            ("za", "Open or close the fold"),
+            ("q<register>", "Record a macro, q again stops it"),
+            (
+                "@<register>  @@",
+                "Replay a macro, or the last replayed one",
+            ),
+            ("Ctrl-S", "Save"),
+            ("Ctrl-Q", "Quit"),
+            ("ZZ  ZQ", "Save and quit, or quit without saving"),
+        ],
+    },
+    Section {
+        mode: "Insert mode",
+        bindings: &[
+            ("Esc", "Go back to normal mode"),
+            ("arrows, Home, End", "Move the cursor"),
+            ("Ctrl-Left, Ctrl-Right", "Move by words"),
+            (
+                "Shift-arrows",
+                "Select characters, the arrows alone go back to insert mode",
+            ),
+            ("Backspace  Delete", "Delete before or under the cursor"),
+            ("Ctrl-W", "Delete the word before the cursor"),
+            ("Ctrl-U", "Delete up to the start of the line"),
+            (
+                "Tab  Shift-Tab",
+                "Expand a snippet or insert a tab, or dedent the line",
+            ),
+            (
+                "Ctrl-V",
+                "Insert the next key as it is, or u and a code point",
+            ),
+            ("Ctrl-K", "Insert a digraph"),
+            (
+                "space, punctuation",
+                "Expand the abbreviation typed before the cursor",
+            ),
+        ],
+    },
+    Section {
+        mode: "Visual mode",
+        bindings: &[
+            ("Esc", "Go back to normal mode"),
+            ("v, V, Ctrl-V", "Switch the kind of selection"),
+            (
+                "`[  `]",
+                "Extend to the start or the end of the last inserted text",
+            ),
+            ("d, x", "Delete the selection"),
+            ("y", "Copy the selection"),
+            (">  <", "Indent or dedent the selected lines"),
+            ("gc", "Comment or uncomment the selected lines"),
+            ("zf", "Fold the selected lines"),
+            (
+                "g Ctrl-A  g Ctrl-X",
+                "Turn the first numbers of the lines into a sequence",
+            ),
+            ("S<char>", "Surround the selection with the pair of char"),
+            ("I  A", "Insert before or append after a block"),
+            ("/", "Search in the selection"),
+            (":", "Replace in the selection, typed as old/new"),
+            ("ga", "Align the selected lines on a text, like = or :"),
+        ],
+    },
+    Section {
+        mode: "Command bar",
+        bindings: &[
+            ("Enter", "Run the command"),
+            ("Esc", "Cancel"),
+            ("Up  Down", "Browse the history"),
+            ("Tab  Shift-Tab", "Complete the file names and types"),
+        ],
+    },
+    Section {
+        mode: "Editor commands",
+        bindings: &[
+            (":w  :w!", "Save, overwriting the file if it changed"),
+            (":w >>file", "Append the lines to the file"),
+            (":e!", "Reload the file"),
+            (":recent", "Open a recent file"),
+            (":stats", "Count the lines, words and characters"),
+            (":fmt", "Format the file"),
+            (":!cmd  :%!cmd", "Replace the file with the output of cmd"),
+            (":set ft=<type>", "Change the file type"),
+            (":set enc=<name>", "Save as utf-8 or latin1"),
+            (
+                ":set [no]eol",
+                "End the last line with a newline, [noeol] when it doesn't",
+            ),
+            (":noh", "Hide the matches until the next search"),
+            (":set [no]hlsearch", "Highlight the search matches"),
+            (":set [no]wordhl", "Highlight the word under the cursor"),
+            (":set [no]cursorcolumn", "Highlight the cursor column"),
+            (
+                ":focus",
+                "Highlight the cursor line and column, for presentations",
+            ),
+            (":help  :h", "Show this help"),
+        ],
+    },
+    Section {
+        mode: "Pickers",
+        bindings: &[
+            ("typing", "Filter the items"),
+            ("Up  Down", "Select an item"),
+            ("Enter", "Choose the item"),
+            ("Esc", "Close"),
+        ],
+    },
+    Section {
+        mode: "Help",
+        bindings: &[
+            ("h j k l, arrows", "Scroll"),
+            ("q, Esc, Ctrl-Q", "Go back to the file"),
+        ],
+    },
+];
+
+/// The text shown by the help, every mode with its
+/// key bindings aligned in a column.
+pub fn text() -> String {
+    let keys_width = SECTIONS
+        .iter()
+        .flat_map(|section| section.bindings)
+        .map(|(keys, _)| keys.width())
+        .max()
+        .unwrap_or(0);
+
+    let mut text = String::from("Key bindings, press q or Esc to go back\n");
+    for section in SECTIONS {
+        text.push('\n');
+        text.push_str(section.mode);
+        text.push('\n');
+        for (keys, description) in section.bindings {
+            let padding = " ".repeat(keys_width.saturating_sub(keys.width()));
+            let _ = writeln!(text, "  {keys}{padding}  {description}");
+        }
+    }
+    text
+}
@@ -1,13 +1,18 @@
-use std::ops::{Not, Range};
-use unicode_segmentation::UnicodeSegmentation;
+use std::{cmp, collections::HashMap, mem, ops::Range};
 
 use crate::editor::{
     annotated_line::{Annotation, AnnotationType},
     file_type::FileType,
-    line::{ByteIndex, GraphemeIndex, Line},
+    line::{ByteIndex, GraphemeIndex, Line, MatchOptions},
     view::Location,
 };
 
+mod plain;
+mod rust;
+
+use plain::PlainHighlighter;
+use rust::RustHighlighter;
+
 // fn identifier(str: &str) -> Self {
 //     match str {
 //         "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "u8" | "u16" | "u32" | "u64"
@@ -24,267 +29,277 @@ use crate::editor::{
 //     }
 // }
 
+/// The state carried from one line to the next, so that constructs
+/// spanning multiple lines (like block comments) are highlighted correctly.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct HighlightState {
+    pub comment_depth: usize,
+}
+
+/// Rapresents the syntax highlighting of a language. Every line is highlighted
+/// starting from the state returned by the previous one.
+pub trait SyntaxHighlighter {
+    fn highlight_line(
+        &self,
+        line: &Line,
+        state: HighlightState,
+    ) -> (Vec<Annotation>, HighlightState);
+}
+
+/// Tells if the word is a number literal, recognized
+/// the same way the Rust highlighter does.
+pub fn is_number(word: &str) -> bool {
+    word.starts_with(|ch: char| ch.is_ascii_digit()) && RustHighlighter::number(word).is_some()
+}
+
+/// Returns the syntax highlighter used for the supplied file type.
+pub fn for_file_type(file_type: FileType) -> Box<dyn SyntaxHighlighter> {
+    match file_type {
+        FileType::PlainText | FileType::Python | FileType::Shell => Box::new(PlainHighlighter),
+        FileType::Rust => Box::new(RustHighlighter),
+    }
+}
+
+/// The kind of syntax the byte of the line is inside of, the lines
+/// before it are scanned from `state` to find the state the line starts
+/// from. A construct reaching the end of the line contains the end too.
+pub fn syntax_at<'l>(
+    file_type: FileType,
+    state: HighlightState,
+    before: impl Iterator<Item = &'l Line>,
+    line: &Line,
+    byte: ByteIndex,
+) -> Option<AnnotationType> {
+    let syntax = for_file_type(file_type);
+    let state = before.fold(state, |state, line| syntax.highlight_line(line, state).1);
+
+    let end = line.get_string().len();
+    syntax
+        .highlight_line(line, state)
+        .0
+        .into_iter()
+        .find(|annotation| {
+            annotation.range.start < byte
+                && (byte < annotation.range.end || annotation.range.end == end)
+        })
+        .map(|annotation| annotation.ty)
+}
+
+/// The syntax annotations of a line, valid as long as the line
+/// is highlighted again starting from the same state.
+struct CachedLine {
+    state: HighlightState,
+    annotations: Vec<Annotation>,
+    next_state: HighlightState,
+}
+
+/// Keeps the syntax annotations of the lines highlighted during the last
+/// draw keyed by their revision, so that the lines that didn't change
+/// aren't scanned again. When a line ends in a different state the
+/// following lines no longer match their cached state and get highlighted again.
+/// The state every row started from is kept as well, so that a draw resumes
+/// from the first row whose lines above changed instead of the top of the file.
 #[derive(Default)]
-pub struct Highlighter<'a> {
+pub struct HighlightCache {
     file_type: FileType,
+    current: HashMap<u64, CachedLine>,
+    previous: HashMap<u64, CachedLine>,
+    checkpoints: Vec<(u64, HighlightState)>,
+}
+
+impl HighlightCache {
+    /// Starts a new draw, the lines that weren't highlighted
+    /// during the previous one are dropped.
+    fn start(&mut self, file_type: FileType) {
+        if self.file_type != file_type {
+            self.file_type = file_type;
+            self.current.clear();
+            self.checkpoints.clear();
+        }
+
+        self.previous = mem::take(&mut self.current);
+    }
+
+    fn highlight(
+        &mut self,
+        syntax: &dyn SyntaxHighlighter,
+        line: &Line,
+        state: HighlightState,
+    ) -> &CachedLine {
+        let revision = line.revision();
+        let cached = self
+            .current
+            .remove(&revision)
+            .or_else(|| self.previous.remove(&revision))
+            .filter(|cached| cached.state == state)
+            .unwrap_or_else(|| {
+                let (annotations, next_state) = syntax.highlight_line(line, state);
+                CachedLine {
+                    state,
+                    annotations,
+                    next_state,
+                }
+            });
+
+        self.current.entry(revision).or_insert(cached)
+    }
+
+    /// Remembers the state the row starts from. The rows after it are
+    /// dropped, they are recorded again as the draw goes on.
+    fn checkpoint(&mut self, row: usize, line: &Line, state: HighlightState) {
+        self.checkpoints.truncate(row);
+        if self.checkpoints.len() == row {
+            self.checkpoints.push((line.revision(), state));
+        }
+    }
+
+    /// The nearest row at or above `row` whose starting state is still
+    /// known, along with that state. A checkpoint holds as long as the
+    /// lines above it keep the revisions they had when it was recorded.
+    pub fn resume_point(
+        &self,
+        file_type: FileType,
+        row: usize,
+        revision_of: impl Fn(usize) -> Option<u64>,
+    ) -> (usize, HighlightState) {
+        if self.file_type != file_type {
+            return (0, HighlightState::default());
+        }
+
+        let last = cmp::min(row, self.checkpoints.len().saturating_sub(1));
+        let resume = self.checkpoints[..last]
+            .iter()
+            .enumerate()
+            .position(|(i, &(revision, _))| revision_of(i) != Some(revision))
+            .unwrap_or(last);
+
+        let state = self
+            .checkpoints
+            .get(resume)
+            .map_or_else(HighlightState::default, |&(_, state)| state);
+        (resume, state)
+    }
+}
+
+/// Highlights the rows in a range, the lines above it can be passed to
+/// `look_back` to find the state the first row starts from.
+pub struct Highlighter<'a> {
+    syntax: Box<dyn SyntaxHighlighter>,
+    cache: &'a mut HighlightCache,
+    state: HighlightState,
     query: Option<&'a str>,
     selected_match: Option<Location>,
+    scope: Option<(Location, Location)>,
+    options: MatchOptions,
+    word: Option<&'a str>,
+    first_row: usize,
     highlighting: Vec<Vec<Annotation>>,
-    ml_counter: usize,
 }
 
 impl<'a> Highlighter<'a> {
     pub fn new(
-        len: usize,
+        rows: Range<usize>,
         query: Option<&'a str>,
         selected_match: Option<Location>,
         file_type: FileType,
+        cache: &'a mut HighlightCache,
     ) -> Self {
-        let mut highlighting = Vec::with_capacity(len);
+        let mut highlighting = Vec::with_capacity(rows.len());
 
-        for _ in 0..len {
+        for _ in rows.clone() {
             highlighting.push(Vec::new());
         }
 
+        cache.start(file_type);
+
         Self {
-            file_type,
+            syntax: for_file_type(file_type),
+            cache,
+            state: HighlightState::default(),
             query,
             selected_match,
+            scope: None,
+            options: MatchOptions::default(),
+            word: None,
+            first_row: rows.start,
             highlighting,
-            ml_counter: 0,
         }
     }
 
-    pub fn highlight(&mut self, row: usize, line: &Line) {
-        self.matches(row, line);
-        if self.file_type == FileType::Rust {
-            self.rust_highlighting(row, line);
-        }
+    /// Only the matches between `start` and `end` excluded are highlighted.
+    pub fn limit_matches(&mut self, (start, end): (Location, Location)) {
+        self.scope = Some((start, end));
     }
 
-    fn rust_highlighting(&mut self, row: usize, line: &Line) {
-        let string = line.get_string();
-        let mut iter = string.split_word_bound_indices().peekable();
-
-        let mut ignore = 0;
-        while let Some(&(i, word)) = iter.peek() {
-            if i < ignore {
-                iter.next();
-                continue;
-            }
-
-            let ann = if self.ml_counter > 0 {
-                self.continue_comment(&string)
-            } else {
-                match word {
-                    "/" => self.comment(&string[i..]),
-                    "'" => Self::char_or_lifetime(&string[i..]),
-                    _ => match Self::first_char_of(word) {
-                        ch if ch.is_ascii_digit() => Self::number(word),
-                        _ => None,
-                    },
-                }
-            };
-
-            if let Some(ann) = ann {
-                // TODO: Use right shift here
-                let start = ann.range.start.saturating_add(i);
-                let end = ann.range.end.saturating_add(i);
-                ignore = end;
-                self.push_annotation(row, start..end, ann.ty);
-            }
-
-            iter.next();
-        }
+    /// How the query is compared with the lines.
+    pub fn set_match_options(&mut self, options: MatchOptions) {
+        self.options = options;
     }
 
-    fn continue_comment(&mut self, line: &str) -> Option<Annotation> {
-        let mut might_close = false;
-        let mut might_open = false;
-        for (i, ch) in line.char_indices() {
-            match ch {
-                '*' if might_open => {
-                    self.ml_counter = self.ml_counter.saturating_add(1);
-                    might_open = false;
-                }
-                '/' if might_close => {
-                    self.ml_counter = self.ml_counter.saturating_sub(1);
-                    if self.ml_counter == 0 {
-                        return Some(Annotation {
-                            range: 0..i.saturating_add(1),
-                            ty: AnnotationType::Comment,
-                        });
-                    }
-                }
-                '/' => might_open = true,
-                '*' => might_close = true,
-                _ => might_close = false,
-            }
-        }
-
-        Some(Annotation {
-            range: 0..line.len(),
-            ty: AnnotationType::Comment,
-        })
+    /// Every occurrence of the whole word is highlighted
+    /// below the matches of the query.
+    pub fn highlight_word(&mut self, word: &'a str) {
+        self.word = Some(word);
     }
 
-    fn comment(&mut self, line: &str) -> Option<Annotation> {
-        let mut might_close = false;
-        let mut might_open = false;
-        for (i, ch) in line.char_indices().skip(1) {
-            match ch {
-                '/' if i == 1 => {
-                    return Some(Annotation {
-                        range: 0..line.len(),
-                        ty: AnnotationType::Comment,
-                    });
-                }
-
-                '*' if i == 1 => {
-                    self.ml_counter = self.ml_counter.saturating_add(1);
-                }
-
-                '*' if might_open => {
-                    self.ml_counter = self.ml_counter.saturating_add(1);
-                    might_open = false;
-                }
-
-                '/' if might_close => {
-                    self.ml_counter = self.ml_counter.saturating_sub(1);
-
-                    if self.ml_counter == 0 {
-                        return Some(Annotation {
-                            range: 0..i.saturating_add(1),
-                            ty: AnnotationType::Comment,
-                        });
-                    }
-
-                    might_close = false;
-                }
-
-                '/' => might_open = true,
-                '*' => might_close = true,
-
-                _ if i == 1 => return None,
-                _ => {
-                    might_close = false;
-                    might_open = false;
-                }
-            }
-        }
-
-        Some(Annotation {
-            range: 0..line.len(),
-            ty: AnnotationType::Comment,
-        })
+    /// The first row the highlighting is kept for.
+    pub const fn first_row(&self) -> usize {
+        self.first_row
     }
 
-    fn char(line: &str) -> Option<Annotation> {
-        let mut escaped = false;
-        for (i, ch) in line.char_indices().skip(1) {
-            match ch {
-                '\\' => escaped = escaped.not(),
-                '\'' if !escaped => {
-                    return Some(Annotation {
-                        range: 0..i.saturating_add(1),
-                        ty: AnnotationType::Char,
-                    });
-                }
-                _ => escaped = false,
-            }
-        }
-
-        Some(Annotation {
-            range: 0..line.len(),
-            ty: AnnotationType::Char,
-        })
+    /// Starts from the nearest row above the highlighted ones whose state
+    /// is known, and returns it. The rows from there on are looked back at.
+    pub fn resume(&mut self, revision_of: impl Fn(usize) -> Option<u64>) -> usize {
+        let (row, state) =
+            self.cache
+                .resume_point(self.cache.file_type, self.first_row, revision_of);
+        self.state = state;
+        row
     }
 
-    fn lifetime(line: &str) -> Option<Annotation> {
-        for (i, ch) in line.char_indices().skip(1) {
-            match ch {
-                '\'' => return None,
-                ch if !ch.is_ascii_alphanumeric() && ch != '_' && i == 1 => return None,
-                ch if !ch.is_ascii_alphanumeric() && ch != '_' => {
-                    return Some(Annotation {
-                        range: 0..i,
-                        ty: AnnotationType::Lifetime,
-                    });
-                }
-                _ => {}
-            }
-        }
-
-        Some(Annotation {
-            range: 0..line.len(),
-            ty: AnnotationType::Lifetime,
-        })
+    /// Scans a line above the highlighted rows only to carry its state
+    /// forward, the annotations are not kept.
+    pub fn look_back(&mut self, row: usize, line: &Line) {
+        self.cache.checkpoint(row, line, self.state);
+        let cached = self.cache.highlight(self.syntax.as_ref(), line, self.state);
+        self.state = cached.next_state;
     }
 
-    fn char_or_lifetime(line: &str) -> Option<Annotation> {
-        let ach = Self::char(line);
-        let lch = Self::lifetime(line);
-        if lch.is_some() { lch } else { ach }
-    }
-
-    fn number(num: &str) -> Option<Annotation> {
-        let mut base = 10;
-        let mut dot = false;
-        let mut one_more = false;
-
-        let iter = num.chars().enumerate();
-        for (i, ch) in iter {
-            match ch {
-                '_' => {}
-                '.' if dot => return None,
-                '.' => dot = true,
-                'e' => {
-                    dot = true;
-                    one_more = true;
-                }
-                'b' | 'B' => {
-                    if i != 1 {
-                        return None;
-                    }
-
-                    base = 2;
-                    one_more = true;
-                }
-                'o' | 'O' => {
-                    if i != 1 {
-                        return None;
-                    }
-
-                    base = 8;
-                    one_more = true;
-                }
-                'x' | 'X' => {
-                    if i != 1 {
-                        return None;
-                    }
-
-                    base = 16;
-                    one_more = true;
-                }
-                ch if !ch.is_digit(base) => return None,
-                _ => one_more = false,
-            }
-        }
+    /// Annotations pushed later are printed above the earlier ones,
+    /// so the syntax comes first and the matches last.
+    pub fn highlight(&mut self, row: usize, line: &Line) {
+        self.cache.checkpoint(row, line, self.state);
+        let cached = self.cache.highlight(self.syntax.as_ref(), line, self.state);
+        let index = row.saturating_sub(self.first_row);
+        self.highlighting[index].extend_from_slice(&cached.annotations);
+        self.state = cached.next_state;
 
-        (!one_more).then_some(Annotation {
-            range: 0..num.len(),
-            ty: AnnotationType::Number,
-        })
+        self.word_occurrences(row, line);
+        self.matches(row, line);
     }
 
-    fn first_char_of(word: &str) -> char {
-        word.chars().next().unwrap_or_else(|| unreachable!())
+    fn word_occurrences(&mut self, row: usize, line: &Line) {
+        let Some(word) = self.word else {
+            return;
+        };
+
+        let options = MatchOptions {
+            ignore_case: false,
+            whole_word: true,
+        };
+        for (from, _) in line.find_all(word, 0..line.get_string().len(), options) {
+            self.push_annotation(
+                row,
+                from..from.saturating_add(word.len()),
+                AnnotationType::WordOccurrence,
+            );
+        }
     }
 
     fn matches(&mut self, row: usize, line: &Line) {
         if let Some(needle) = self.query {
             let end = line.get_string().len();
-            let matches = line.find_all(needle, 0..end);
+            let matches = line.find_all(needle, 0..end, self.options);
 
             for mat in matches {
                 let from: ByteIndex = mat.0;
@@ -296,6 +311,14 @@ impl<'a> Highlighter<'a> {
                 // TODO: there might be graphemes in the search term
                 let to_gr: GraphemeIndex = from_gr.saturating_add(len);
 
+                if let Some((start, end)) = self.scope {
+                    let before_start = (row, from_gr) < (start.line_index, start.grapheme_index);
+                    let after_end = (row, to_gr) > (end.line_index, end.grapheme_index);
+                    if before_start || after_end {
+                        continue;
+                    }
+                }
+
                 if let Some(on) = self.selected_match
                     && on.line_index == row
                     && on.grapheme_index >= from_gr
@@ -309,11 +332,132 @@ impl<'a> Highlighter<'a> {
         }
     }
 
+    /// Marks the bytes of the row covered by the selection, pushed
+    /// after the other annotations so that it is printed above them.
+    pub fn select(&mut self, row: usize, range: Range<ByteIndex>) {
+        self.push_annotation(row, range, AnnotationType::Selection);
+    }
+
+    /// Marks the graphemes of the row under the cursors added next
+    /// to the main one, printed above the other annotations.
+    pub fn mark_cursors(&mut self, row: usize, line: &Line, cursors: &[Location]) {
+        for cursor in cursors.iter().filter(|cursor| cursor.line_index == row) {
+            let at = cursor.grapheme_index;
+            if at < line.grapheme_count() {
+                self.push_annotation(
+                    row,
+                    line.byte_range(at..at.saturating_add(1)),
+                    AnnotationType::Cursor,
+                );
+            }
+        }
+    }
+
     pub fn get_annotations(&self, row: usize) -> &[Annotation] {
-        &self.highlighting[row]
+        &self.highlighting[row.saturating_sub(self.first_row)]
     }
 
     fn push_annotation(&mut self, row: usize, range: Range<ByteIndex>, ty: AnnotationType) {
-        self.highlighting[row].push(Annotation { range, ty });
+        let index = row.saturating_sub(self.first_row);
+        self.highlighting[index].push(Annotation { range, ty });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    /// Highlights as Rust counting the lines it is asked for.
+    #[derive(Default)]
+    struct CountingHighlighter {
+        calls: Cell<usize>,
+    }
+
+    impl SyntaxHighlighter for CountingHighlighter {
+        fn highlight_line(
+            &self,
+            line: &Line,
+            state: HighlightState,
+        ) -> (Vec<Annotation>, HighlightState) {
+            self.calls.set(self.calls.get().saturating_add(1));
+            RustHighlighter.highlight_line(line, state)
+        }
+    }
+
+    /// Highlights all of the lines the way a draw does, returning how
+    /// many of them weren't in the cache.
+    fn draw(cache: &mut HighlightCache, lines: &[Line]) -> usize {
+        let syntax = CountingHighlighter::default();
+        cache.start(FileType::Rust);
+        let mut state = HighlightState::default();
+        for line in lines {
+            state = cache.highlight(&syntax, line, state).next_state;
+        }
+        syntax.calls.get()
+    }
+
+    #[test]
+    fn only_the_edited_line_is_highlighted_again() {
+        let mut cache = HighlightCache::default();
+        let mut lines: Vec<Line> = ["fn main() {", "    let a = 1;", "    a", "}"]
+            .into_iter()
+            .map(Line::from)
+            .collect();
+        assert_eq!(draw(&mut cache, &lines), 4);
+        assert_eq!(draw(&mut cache, &lines), 0);
+
+        lines[1].insert_str_at(14, " // one");
+        assert_eq!(draw(&mut cache, &lines), 1);
+    }
+
+    #[test]
+    fn opening_a_comment_highlights_the_lines_after_it_again() {
+        let mut cache = HighlightCache::default();
+        let mut lines: Vec<Line> = ["fn main() {", "    let a = 1;", "    a", "}"]
+            .into_iter()
+            .map(Line::from)
+            .collect();
+        draw(&mut cache, &lines);
+
+        lines[1].insert_str_at(0, "/*");
+        assert_eq!(draw(&mut cache, &lines), 3);
+        lines[1].remove_range(0..2);
+        assert_eq!(draw(&mut cache, &lines), 3);
+    }
+
+    #[test]
+    fn a_draw_resumes_from_the_last_row_whose_lines_above_are_unchanged() {
+        let mut cache = HighlightCache::default();
+        let mut lines = vec![Line::from("/*")];
+        lines.extend((0..500).map(|_| Line::from("a")));
+
+        let mut highlighter = Highlighter::new(0..10, None, None, FileType::Rust, &mut cache);
+        for (row, line) in lines.iter().enumerate().take(10) {
+            highlighter.highlight(row, line);
+        }
+
+        let mut highlighter = Highlighter::new(450..460, None, None, FileType::Rust, &mut cache);
+        let start = highlighter.resume(|row| lines.get(row).map(Line::revision));
+        assert_eq!(start, 9);
+        for (row, line) in lines.iter().enumerate().take(460).skip(start) {
+            if row < 450 {
+                highlighter.look_back(row, line);
+            } else {
+                highlighter.highlight(row, line);
+            }
+        }
+        assert_eq!(
+            highlighter.get_annotations(455)[0].ty,
+            AnnotationType::Comment
+        );
+
+        lines[0].remove_range(0..2);
+        let revision_of = |row: usize| lines.get(row).map(Line::revision);
+        assert_eq!(cache.resume_point(FileType::Rust, 455, revision_of).0, 0);
+        assert_eq!(
+            cache.resume_point(FileType::PlainText, 455, revision_of).0,
+            0
+        );
     }
 }
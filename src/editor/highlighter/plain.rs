@@ -0,0 +1,18 @@
+use crate::editor::{
+    annotated_line::Annotation,
+    highlighter::{HighlightState, SyntaxHighlighter},
+    line::Line,
+};
+
+/// Used for the files without a known type, it doesn't annotate anything.
+pub struct PlainHighlighter;
+
+impl SyntaxHighlighter for PlainHighlighter {
+    fn highlight_line(
+        &self,
+        _line: &Line,
+        state: HighlightState,
+    ) -> (Vec<Annotation>, HighlightState) {
+        (Vec::new(), state)
+    }
+}
@@ -0,0 +1,252 @@
+use std::ops::Not;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::editor::{
+    annotated_line::{Annotation, AnnotationType},
+    highlighter::{HighlightState, SyntaxHighlighter},
+    line::Line,
+};
+
+/// Highlights comments, chars, lifetimes and numbers of Rust source files.
+pub struct RustHighlighter;
+
+impl SyntaxHighlighter for RustHighlighter {
+    fn highlight_line(
+        &self,
+        line: &Line,
+        mut state: HighlightState,
+    ) -> (Vec<Annotation>, HighlightState) {
+        let annotations = Self::highlight(line, &mut state);
+        (annotations, state)
+    }
+}
+
+impl RustHighlighter {
+    fn highlight(line: &Line, state: &mut HighlightState) -> Vec<Annotation> {
+        let string = line.get_string();
+        let mut annotations = Vec::new();
+        let mut iter = string.split_word_bound_indices().peekable();
+
+        let mut ignore = 0;
+        while let Some(&(i, word)) = iter.peek() {
+            if i < ignore {
+                iter.next();
+                continue;
+            }
+
+            let ann = if state.comment_depth > 0 {
+                Some(Self::continue_comment(string, state))
+            } else {
+                match word {
+                    "/" => Self::comment(&string[i..], state),
+                    "'" => Self::char_or_lifetime(&string[i..]),
+                    _ => match Self::first_char_of(word) {
+                        ch if ch.is_ascii_digit() => Self::number(word),
+                        _ => None,
+                    },
+                }
+            };
+
+            if let Some(ann) = ann {
+                // TODO: Use right shift here
+                let start = ann.range.start.saturating_add(i);
+                let end = ann.range.end.saturating_add(i);
+                ignore = end;
+                annotations.push(Annotation {
+                    range: start..end,
+                    ty: ann.ty,
+                });
+            }
+
+            iter.next();
+        }
+
+        annotations
+    }
+
+    fn continue_comment(line: &str, state: &mut HighlightState) -> Annotation {
+        let mut might_close = false;
+        let mut might_open = false;
+        for (i, ch) in line.char_indices() {
+            match ch {
+                '*' if might_open => {
+                    state.comment_depth = state.comment_depth.saturating_add(1);
+                    might_open = false;
+                }
+                '/' if might_close => {
+                    state.comment_depth = state.comment_depth.saturating_sub(1);
+                    if state.comment_depth == 0 {
+                        return Annotation {
+                            range: 0..i.saturating_add(1),
+                            ty: AnnotationType::Comment,
+                        };
+                    }
+                }
+                '/' => might_open = true,
+                '*' => might_close = true,
+                _ => might_close = false,
+            }
+        }
+
+        Annotation {
+            range: 0..line.len(),
+            ty: AnnotationType::Comment,
+        }
+    }
+
+    fn comment(line: &str, state: &mut HighlightState) -> Option<Annotation> {
+        let mut might_close = false;
+        let mut might_open = false;
+        for (i, ch) in line.char_indices().skip(1) {
+            match ch {
+                '/' if i == 1 => {
+                    return Some(Annotation {
+                        range: 0..line.len(),
+                        ty: AnnotationType::Comment,
+                    });
+                }
+
+                '*' if i == 1 => {
+                    state.comment_depth = state.comment_depth.saturating_add(1);
+                }
+
+                '*' if might_open => {
+                    state.comment_depth = state.comment_depth.saturating_add(1);
+                    might_open = false;
+                }
+
+                '/' if might_close => {
+                    state.comment_depth = state.comment_depth.saturating_sub(1);
+
+                    if state.comment_depth == 0 {
+                        return Some(Annotation {
+                            range: 0..i.saturating_add(1),
+                            ty: AnnotationType::Comment,
+                        });
+                    }
+
+                    might_close = false;
+                }
+
+                '/' => might_open = true,
+                '*' => might_close = true,
+
+                _ if i == 1 => return None,
+                _ => {
+                    might_close = false;
+                    might_open = false;
+                }
+            }
+        }
+
+        Some(Annotation {
+            range: 0..line.len(),
+            ty: AnnotationType::Comment,
+        })
+    }
+
+    fn char(line: &str) -> Annotation {
+        let mut escaped = false;
+        for (i, ch) in line.char_indices().skip(1) {
+            match ch {
+                '\\' => escaped = escaped.not(),
+                '\'' if !escaped => {
+                    return Annotation {
+                        range: 0..i.saturating_add(1),
+                        ty: AnnotationType::Char,
+                    };
+                }
+                _ => escaped = false,
+            }
+        }
+
+        Annotation {
+            range: 0..line.len(),
+            ty: AnnotationType::Char,
+        }
+    }
+
+    fn lifetime(line: &str) -> Option<Annotation> {
+        for (i, ch) in line.char_indices().skip(1) {
+            match ch {
+                '\'' => return None,
+                ch if !ch.is_ascii_alphanumeric() && ch != '_' && i == 1 => return None,
+                ch if !ch.is_ascii_alphanumeric() && ch != '_' => {
+                    return Some(Annotation {
+                        range: 0..i,
+                        ty: AnnotationType::Lifetime,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        Some(Annotation {
+            range: 0..line.len(),
+            ty: AnnotationType::Lifetime,
+        })
+    }
+
+    fn char_or_lifetime(line: &str) -> Option<Annotation> {
+        let lch = Self::lifetime(line);
+        if lch.is_some() {
+            lch
+        } else {
+            Some(Self::char(line))
+        }
+    }
+
+    pub fn number(num: &str) -> Option<Annotation> {
+        let mut base = 10;
+        let mut dot = false;
+        let mut one_more = false;
+
+        let iter = num.chars().enumerate();
+        for (i, ch) in iter {
+            match ch {
+                '_' => {}
+                '.' if dot => return None,
+                '.' => dot = true,
+                'e' => {
+                    dot = true;
+                    one_more = true;
+                }
+                'b' | 'B' => {
+                    if i != 1 {
+                        return None;
+                    }
+
+                    base = 2;
+                    one_more = true;
+                }
+                'o' | 'O' => {
+                    if i != 1 {
+                        return None;
+                    }
+
+                    base = 8;
+                    one_more = true;
+                }
+                'x' | 'X' => {
+                    if i != 1 {
+                        return None;
+                    }
+
+                    base = 16;
+                    one_more = true;
+                }
+                ch if !ch.is_digit(base) => return None,
+                _ => one_more = false,
+            }
+        }
+
+        (!one_more).then_some(Annotation {
+            range: 0..num.len(),
+            ty: AnnotationType::Number,
+        })
+    }
+
+    fn first_char_of(word: &str) -> char {
+        word.chars().next().unwrap_or_else(|| unreachable!())
+    }
+}
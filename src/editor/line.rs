@@ -1,12 +1,36 @@
-use std::{fmt::Display, ops::Range};
-use unicode_segmentation::UnicodeSegmentation;
+use std::{
+    cmp,
+    fmt::Display,
+    ops::Range,
+    sync::atomic::{AtomicU64, Ordering},
+};
+use unicode_normalization::{UnicodeNormalization, is_nfc};
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
 use unicode_width::UnicodeWidthStr;
 
-use crate::editor::annotated_line::{AnnotatedLine, Annotation};
+use crate::editor::{
+    annotated_line::{AnnotatedLine, Annotation},
+    editor_cmd::Case,
+    highlighter::is_number,
+};
 
 pub type GraphemeIndex = usize;
 pub type ByteIndex = usize;
 
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(1);
+
+/// Every change to the content of a line gets a revision that
+/// is unique across all the lines.
+fn next_revision() -> u64 {
+    NEXT_REVISION.fetch_add(1, Ordering::Relaxed)
+}
+
+/// The last revision given to a line, the lines
+/// changed afterwards get a greater one.
+pub fn last_revision() -> u64 {
+    NEXT_REVISION.load(Ordering::Relaxed).saturating_sub(1)
+}
+
 #[derive(Clone, Copy)]
 pub enum GraphemeWidth {
     Zero,
@@ -34,6 +58,7 @@ impl From<GraphemeWidth> for usize {
 
 /// Rapresents a single grapheme width its width and
 /// replacement character if needed.
+#[derive(Clone)]
 pub struct TextFragment {
     grapheme: String,
     width: GraphemeWidth,
@@ -82,14 +107,36 @@ impl TextFragment {
     pub fn replacement(&self) -> Option<char> {
         self.replacement
     }
+
+    pub fn is_blank(&self) -> bool {
+        self.grapheme.trim().is_empty()
+    }
+
+    pub fn is_word(&self) -> bool {
+        self.grapheme
+            .chars()
+            .next()
+            .is_some_and(|ch| ch.is_alphanumeric() || ch == '_')
+    }
+}
+
+/// Rapresents how a searched text is compared with a line.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchOptions {
+    /// Letters match whatever their case is.
+    pub ignore_case: bool,
+    /// Only matches that are a whole word count.
+    pub whole_word: bool,
 }
 
 /// Rapresents a Line in our text with a
 /// Vector of `TextFragments`.
-#[derive(Default)]
+#[derive(Clone)]
+#[allow(clippy::struct_field_names)]
 pub struct Line {
     line: Vec<TextFragment>,
     string: String,
+    revision: u64,
 }
 
 impl Line {
@@ -100,6 +147,7 @@ impl Line {
         Self {
             line,
             string: line_str.to_string(),
+            revision: next_revision(),
         }
     }
 
@@ -147,19 +195,29 @@ impl Line {
         result
     }
 
+    /// Splits the line between two graphemes, so both
+    /// parts keep their fragments.
     pub fn split_off(&mut self, at: GraphemeIndex) -> Self {
-        if let Some(fragment) = self.line.get(at) {
-            let rem = self.string.split_off(fragment.start_index);
-            self.rebuild_fragments();
-            Self::from(&rem)
-        } else {
-            Self::default()
+        let Some(start) = self.line.get(at).map(|fragment| fragment.start_index) else {
+            return Self::default();
+        };
+
+        let string = self.string.split_off(start);
+        let mut line = self.line.split_off(at);
+        for fragment in &mut line {
+            fragment.start_index = fragment.start_index.saturating_sub(start);
+        }
+        self.revision = next_revision();
+        Self {
+            line,
+            string,
+            revision: next_revision(),
         }
     }
 
     pub fn append(&mut self, other: &Self) {
-        self.string.push_str(&other.string);
-        self.rebuild_fragments();
+        let end = self.string.len();
+        self.splice(end..end, &other.string);
     }
 
     /// Calculates the width of the characters until a
@@ -176,20 +234,139 @@ impl Line {
     }
 
     pub fn insert_char_at(&mut self, index: GraphemeIndex, tf: char) {
-        if let Some(fragment) = self.line.get(index) {
-            self.string.insert(fragment.start_index, tf);
-        } else {
-            self.string.push(tf);
+        self.insert_str_at(index, tf.encode_utf8(&mut [0; 4]));
+    }
+
+    pub fn insert_str_at(&mut self, index: GraphemeIndex, string: &str) {
+        let at = self.grapheme_start_byte(index);
+        self.splice(at..at, string);
+    }
+
+    /// Removes the graphemes in the supplied range, the range
+    /// is clamped to the length of the line.
+    pub fn remove_range(&mut self, range: Range<GraphemeIndex>) {
+        let end = cmp::min(range.end, self.line.len());
+        if range.start >= end {
+            return;
         }
-        self.rebuild_fragments();
+
+        let start_byte = self.grapheme_start_byte(range.start);
+        let end_byte = self.grapheme_start_byte(end);
+        self.splice(start_byte..end_byte, "");
+    }
+
+    /// Replaces the graphemes in the supplied range with a
+    /// new text, the range is clamped to the length of the line.
+    pub fn replace_range(&mut self, range: Range<GraphemeIndex>, text: &str) {
+        let start = cmp::min(range.start, self.line.len());
+        let end = cmp::min(cmp::max(range.end, start), self.line.len());
+        let start_byte = self.grapheme_start_byte(start);
+        let end_byte = self.grapheme_start_byte(end);
+        self.splice(start_byte..end_byte, text);
+    }
+
+    /// Converts the case of the graphemes in the supplied range.
+    pub fn change_case(&mut self, range: Range<GraphemeIndex>, case: Case) {
+        let start = cmp::min(range.start, self.line.len());
+        let end = cmp::min(cmp::max(range.end, start), self.line.len());
+        let converted: String = self.line[start..end]
+            .iter()
+            .map(|fragment| case.apply(&fragment.grapheme))
+            .collect();
+
+        self.replace_range(start..end, &converted);
+    }
+
+    /// Returns the range of the word containing the grapheme at
+    /// `index`, a word is made of alphanumeric characters and `_`.
+    pub fn word_range_at(&self, index: GraphemeIndex) -> Option<Range<GraphemeIndex>> {
+        if !self.line.get(index).is_some_and(TextFragment::is_word) {
+            return None;
+        }
+
+        let start = self.line[..index]
+            .iter()
+            .rposition(|fragment| !fragment.is_word())
+            .map_or(0, |i| i.saturating_add(1));
+        let end = self.line[index..]
+            .iter()
+            .position(|fragment| !fragment.is_word())
+            .map_or(self.line.len(), |i| i.saturating_add(index));
+
+        Some(start..end)
+    }
+
+    /// Returns the range of the first number that ends
+    /// after the grapheme at `index`.
+    pub fn number_range_from(&self, index: GraphemeIndex) -> Option<Range<GraphemeIndex>> {
+        let from = self.grapheme_start_byte(index);
+        let (start, word) = self
+            .string
+            .split_word_bound_indices()
+            .find(|&(i, word)| i.saturating_add(word.len()) > from && is_number(word))?;
+
+        let start = self.byte_index_to_grapheme_index(start);
+        Some(start..start.saturating_add(word.graphemes(true).count()))
+    }
+
+    /// Returns where the word before `index` starts, skipping the
+    /// whitespace in between. Runs of punctuation count as words.
+    pub fn previous_word_start(&self, index: GraphemeIndex) -> GraphemeIndex {
+        let before = &self.line[..cmp::min(index, self.line.len())];
+        let Some(last) = before.iter().rposition(|fragment| !fragment.is_blank()) else {
+            return 0;
+        };
+
+        let is_word = before[last].is_word();
+        before[..last]
+            .iter()
+            .rposition(|fragment| fragment.is_blank() || fragment.is_word() != is_word)
+            .map_or(0, |i| i.saturating_add(1))
+    }
+
+    /// Returns where the word after the one at `index` starts, or
+    /// the end of the line if there is none.
+    pub fn next_word_start(&self, index: GraphemeIndex) -> GraphemeIndex {
+        let Some(current) = self.line.get(index) else {
+            return self.line.len();
+        };
+
+        let word_end = if current.is_blank() {
+            index
+        } else {
+            let is_word = current.is_word();
+            self.line[index..]
+                .iter()
+                .position(|fragment| fragment.is_blank() || fragment.is_word() != is_word)
+                .map_or(self.line.len(), |i| i.saturating_add(index))
+        };
+
+        self.line[word_end..]
+            .iter()
+            .position(|fragment| !fragment.is_blank())
+            .map_or(self.line.len(), |i| i.saturating_add(word_end))
+    }
+
+    /// Swaps the grapheme at `index` with the one before it.
+    pub fn transpose_at(&mut self, index: GraphemeIndex) {
+        let (Some(prev), Some(current)) = (
+            index.checked_sub(1).and_then(|prev| self.line.get(prev)),
+            self.line.get(index),
+        ) else {
+            return;
+        };
+
+        let start = prev.start_index;
+        let swapped = format!("{}{}", current.grapheme, prev.grapheme);
+        let end = start.saturating_add(swapped.len());
+        self.splice(start..end, &swapped);
     }
 
     pub fn remove_at(&mut self, index: GraphemeIndex) {
         if let Some(fragment) = self.line.get(index) {
             let start = fragment.start_index;
             let end = start.saturating_add(fragment.grapheme.len());
-            self.string.drain(start..end);
-            self.rebuild_fragments();
+            self.splice(start..end, "");
         }
     }
 
@@ -197,25 +374,71 @@ impl Line {
         self.line.len()
     }
 
-    pub fn pop(&mut self) {
-        self.remove_at(self.line.len().saturating_sub(1));
+    pub fn grapheme_at(&self, index: GraphemeIndex) -> Option<&str> {
+        self.line
+            .get(index)
+            .map(|fragment| fragment.grapheme.as_str())
     }
 
-    pub fn push_chr(&mut self, ch: char) {
-        self.string.push(ch);
-        self.rebuild_fragments();
+    /// Returns the index of the first grapheme that is not
+    /// whitespace, or the length of the line if there is none.
+    pub fn first_non_blank(&self) -> GraphemeIndex {
+        self.line
+            .iter()
+            .position(|fragment| !fragment.grapheme.trim().is_empty())
+            .unwrap_or(self.line.len())
     }
 
     pub fn clear(&mut self) {
         self.string.clear();
-        self.rebuild_fragments();
+        self.line.clear();
+        self.revision = next_revision();
+    }
+
+    /// Converts the line to the NFC form, so that decomposed
+    /// characters compare equal to their composed counterparts.
+    pub fn normalize(&mut self) {
+        if !is_nfc(&self.string) {
+            self.string = self.string.nfc().collect();
+            self.rebuild_fragments();
+        }
+    }
+
+    /// Gives the line a new revision as if it was edited, for
+    /// a line copied or moved that has to be told apart.
+    pub fn renew(&mut self) {
+        self.revision = next_revision();
+    }
+
+    /// Changes every time the content of the line changes, two lines
+    /// with the same revision always have the same content.
+    pub const fn revision(&self) -> u64 {
+        self.revision
     }
 
     pub fn get_string(&self) -> &str {
         &self.string
     }
 
-    pub fn search_backwards(&self, needle: &str, mut to: GraphemeIndex) -> Option<GraphemeIndex> {
+    /// The text of the graphemes in the range, clamped to the line.
+    pub fn get_str(&self, range: Range<GraphemeIndex>) -> &str {
+        &self.string[self.byte_range(range)]
+    }
+
+    /// Converts a range of graphemes to the bytes they span,
+    /// the range is clamped to the length of the line.
+    pub fn byte_range(&self, range: Range<GraphemeIndex>) -> Range<ByteIndex> {
+        let start = self.grapheme_start_byte(range.start);
+        let end = self.grapheme_start_byte(range.end);
+        start..cmp::max(start, end)
+    }
+
+    pub fn search_backwards(
+        &self,
+        needle: &str,
+        mut to: GraphemeIndex,
+        options: MatchOptions,
+    ) -> Option<GraphemeIndex> {
         if self.line.is_empty() {
             return None;
         }
@@ -224,32 +447,63 @@ impl Line {
         let (to_byte, grapheme_len) = self.grapheme_index_to_byte_index(to);
         to = to_byte.saturating_add(grapheme_len);
 
-        self.find_all(needle, 0..to)
+        self.find_all(needle, 0..to, options)
             .last()
             .map(|(_, grapheme_index)| *grapheme_index)
     }
 
-    pub fn search_forward(&self, needle: &str, from: GraphemeIndex) -> Option<GraphemeIndex> {
+    pub fn search_forward(
+        &self,
+        needle: &str,
+        from: GraphemeIndex,
+        options: MatchOptions,
+    ) -> Option<GraphemeIndex> {
         if self.line.is_empty() {
             return None;
         }
 
-        let (start, _) = self.grapheme_index_to_byte_index(from);
+        let start = self.grapheme_start_byte(from);
         let end = self.string.len();
 
-        self.find_all(needle, start..end)
+        self.find_all(needle, start..end, options)
             .first()
             .map(|(_, grapheme_index)| *grapheme_index)
     }
 
+    /// The matches of the needle lying entirely in the byte range,
+    /// compared as the options ask.
     pub fn find_all(
         &self,
         needle: &str,
         range: Range<ByteIndex>,
+        options: MatchOptions,
     ) -> Vec<(ByteIndex, GraphemeIndex)> {
-        let start = range.start;
         let count = Self::string_to_fragments(needle).len();
+        let matches = if options.ignore_case {
+            self.find_all_ignoring_case(needle, range, count)
+        } else {
+            self.find_all_exact(needle, range, count)
+        };
 
+        if !options.whole_word {
+            return matches;
+        }
+
+        matches
+            .into_iter()
+            .filter(|&(_, index)| {
+                self.word_range_at(index) == Some(index..index.saturating_add(count))
+            })
+            .collect()
+    }
+
+    fn find_all_exact(
+        &self,
+        needle: &str,
+        range: Range<ByteIndex>,
+        count: usize,
+    ) -> Vec<(ByteIndex, GraphemeIndex)> {
+        let start = range.start;
         self.string.get(range).map_or_else(Vec::new, |haystack| {
             haystack
                 .match_indices(needle)
@@ -269,6 +523,44 @@ impl Line {
         })
     }
 
+    /// Compares the graphemes starting at each index in the range
+    /// with the needle once both are lowercased, the matches
+    /// don't overlap like the exact ones.
+    fn find_all_ignoring_case(
+        &self,
+        needle: &str,
+        range: Range<ByteIndex>,
+        count: usize,
+    ) -> Vec<(ByteIndex, GraphemeIndex)> {
+        let needle = needle.to_lowercase();
+        let mut matches = Vec::new();
+        if count == 0 {
+            return matches;
+        }
+
+        let mut index = 0;
+        while let Some(fragment) = self.line.get(index) {
+            let Some(candidate) = self.line.get(index..index.saturating_add(count)) else {
+                break;
+            };
+
+            let from = fragment.start_index;
+            let to = from.saturating_add(candidate.iter().map(|frag| frag.grapheme.len()).sum());
+            let text: String = candidate
+                .iter()
+                .map(|frag| frag.grapheme.as_str())
+                .collect();
+
+            if from >= range.start && to <= range.end && text.to_lowercase() == needle {
+                matches.push((from, index));
+                index = index.saturating_add(count);
+            } else {
+                index = index.saturating_add(1);
+            }
+        }
+        matches
+    }
+
     fn byte_index_to_grapheme_index(&self, index: ByteIndex) -> GraphemeIndex {
         for (i, fragment) in self.line.iter().enumerate() {
             if index <= fragment.start_index {
@@ -285,8 +577,73 @@ impl Line {
         })
     }
 
+    /// Byte index where the grapheme starts, or the length of
+    /// the string if the index is past the end.
+    fn grapheme_start_byte(&self, index: GraphemeIndex) -> ByteIndex {
+        self.line
+            .get(index)
+            .map_or(self.string.len(), |fragment| fragment.start_index)
+    }
+
+    /// Replaces the bytes in the range with the text, only the graphemes
+    /// around the edit are segmented again so that typing on a long line
+    /// doesn't go through all of it. The fragments before the edit are
+    /// kept and the ones after it are shifted.
+    fn splice(&mut self, range: Range<ByteIndex>, text: &str) {
+        // An edit can join or split the graphemes next to it, like a
+        // combining mark typed after a letter, so they are taken too.
+        let mut first = self
+            .line
+            .partition_point(|fragment| fragment.start_index < range.start)
+            .saturating_sub(1);
+        let mut last = cmp::min(
+            self.line
+                .partition_point(|fragment| fragment.start_index < range.end)
+                .saturating_add(1),
+            self.line.len(),
+        );
+        let shifted =
+            |index: ByteIndex| index.saturating_sub(range.len()).saturating_add(text.len());
+
+        self.string.replace_range(range.clone(), text);
+        let is_boundary = |string: &str, index: ByteIndex| {
+            GraphemeCursor::new(index, string.len(), true)
+                .is_boundary(string, 0)
+                .unwrap_or(true)
+        };
+        let (start, end) = loop {
+            let start = self
+                .line
+                .get(first)
+                .map_or(0, |fragment| fragment.start_index);
+            let end = self
+                .line
+                .get(last)
+                .map_or(self.string.len(), |fragment| shifted(fragment.start_index));
+            if first > 0 && !is_boundary(&self.string, start) {
+                first = first.saturating_sub(1);
+            } else if last < self.line.len() && !is_boundary(&self.string, end) {
+                last = last.saturating_add(1);
+            } else {
+                break (start, end);
+            }
+        };
+
+        for fragment in &mut self.line[last..] {
+            fragment.start_index = shifted(fragment.start_index);
+        }
+        let fragments = self.string[start..end]
+            .grapheme_indices(true)
+            .map(|(i, grapheme)| TextFragment::from(grapheme, start.saturating_add(i)));
+        self.line.splice(first..last, fragments);
+        self.revision = next_revision();
+    }
+
+    /// Segments the whole string again, for the changes
+    /// that go through all of it.
     fn rebuild_fragments(&mut self) {
         self.line = Self::string_to_fragments(&self.string);
+        self.revision = next_revision();
     }
 
     fn string_to_fragments(string: &str) -> Vec<TextFragment> {
@@ -297,8 +654,198 @@ impl Line {
     }
 }
 
+/// An empty line, with a new revision like the lines made from a text.
+impl Default for Line {
+    fn default() -> Self {
+        Self {
+            line: Vec::new(),
+            string: String::new(),
+            revision: next_revision(),
+        }
+    }
+}
+
 impl Display for Line {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self.string)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The fragments of the line are the ones segmenting its text again.
+    fn assert_segmented(line: &Line) {
+        let fragments = |line: &Line| {
+            line.line
+                .iter()
+                .map(|fragment| (fragment.grapheme.clone(), fragment.start_index))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(fragments(line), fragments(&Line::from(line.get_string())));
+    }
+
+    fn matches(line: &str, needle: &str, ignore_case: bool, whole_word: bool) -> Vec<usize> {
+        let line = Line::from(line);
+        let options = MatchOptions {
+            ignore_case,
+            whole_word,
+        };
+        line.find_all(needle, 0..line.get_string().len(), options)
+            .into_iter()
+            .map(|(_, index)| index)
+            .collect()
+    }
+
+    #[test]
+    fn matches_can_ignore_the_case_and_be_whole_words() {
+        assert_eq!(matches("Foo foo food", "foo", false, false), [4, 8]);
+        assert_eq!(matches("Foo foo food", "foo", true, false), [0, 4, 8]);
+        assert_eq!(matches("Foo foo food", "foo", true, true), [0, 4]);
+        assert_eq!(
+            matches("\u{c9}t\u{e9} \u{e9}t\u{e9}", "\u{e9}t\u{e9}", true, true),
+            [0, 4]
+        );
+        assert_eq!(matches("a_foo foo_ (foo)", "foo", false, true), [12]);
+    }
+
+    #[test]
+    fn searches_start_from_the_index_in_their_direction() {
+        let line = Line::from("ab ab ab");
+        let options = MatchOptions::default();
+        assert_eq!(line.search_forward("ab", 0, options), Some(0));
+        assert_eq!(line.search_forward("ab", 1, options), Some(3));
+        assert_eq!(line.search_forward("ab", 7, options), None);
+        assert_eq!(line.search_backwards("ab", 8, options), Some(6));
+        assert_eq!(line.search_backwards("ab", 6, options), Some(3));
+        assert_eq!(line.search_backwards("ab", 1, options), None);
+    }
+
+    #[test]
+    fn a_combining_mark_joins_the_grapheme_before_it() {
+        let mut line = Line::from("cafe!");
+        line.insert_str_at(4, "\u{301}");
+        assert_eq!(line.get_string(), "cafe\u{301}!");
+        assert_eq!(line.grapheme_count(), 5);
+        assert_segmented(&line);
+
+        line.remove_range(0..1);
+        assert_segmented(&line);
+        line.insert_char_at(0, '\u{308}');
+        assert_eq!(line.grapheme_count(), 5);
+        assert_segmented(&line);
+    }
+
+    #[test]
+    fn a_joiner_merges_the_emoji_around_it() {
+        let mut line = Line::from("a\u{1f468}\u{1f469}b");
+        line.insert_char_at(2, '\u{200d}');
+        assert_eq!(line.grapheme_count(), 3);
+        assert_segmented(&line);
+
+        line.remove_range(1..2);
+        assert_eq!(line.get_string(), "ab");
+        assert_segmented(&line);
+    }
+
+    #[test]
+    fn a_line_feed_joins_the_carriage_return_before_it() {
+        let mut line = Line::from("a\rb");
+        line.replace_range(2..3, "\n");
+        assert_eq!(line.grapheme_count(), 2);
+        assert_segmented(&line);
+
+        line.remove_at(1);
+        line.insert_str_at(1, "\r\r\n");
+        assert_eq!(line.grapheme_count(), 3);
+        assert_segmented(&line);
+    }
+
+    #[test]
+    fn edits_at_the_ends_of_the_line_keep_the_fragments() {
+        let mut line = Line::from("\u{e9}t\u{e9}");
+        line.insert_str_at(0, "\u{1f600} ");
+        line.insert_str_at(line.grapheme_count(), "\u{6f22}");
+        assert_eq!(line.get_string(), "\u{1f600} \u{e9}t\u{e9}\u{6f22}");
+        assert_segmented(&line);
+
+        line.remove_at(0);
+        line.remove_at(line.grapheme_count().saturating_sub(1));
+        line.transpose_at(1);
+        assert_eq!(line.get_string(), "\u{e9} t\u{e9}");
+        assert_segmented(&line);
+    }
+
+    #[test]
+    fn transposing_moves_a_combining_mark_with_its_letter() {
+        let mut line = Line::from("xe\u{301}");
+        line.transpose_at(1);
+        assert_eq!(line.get_string(), "e\u{301}x");
+        assert_eq!(line.grapheme_count(), 2);
+        assert_segmented(&line);
+
+        line.transpose_at(1);
+        assert_eq!(line.get_string(), "xe\u{301}");
+        assert_segmented(&line);
+    }
+
+    #[test]
+    fn edits_in_a_long_line_keep_the_fragments() {
+        let mut line = Line::from(&"a".repeat(10_000));
+        line.insert_char_at(5_000, 'b');
+        line.insert_char_at(5_001, '\u{301}');
+        line.remove_range(0..1);
+        line.insert_str_at(line.grapheme_count(), "\u{1f600}");
+        assert_eq!(line.grapheme_count(), 10_001);
+        assert_segmented(&line);
+    }
+
+    /// Run with `cargo test -- --ignored`, the edits should stay
+    /// well below segmenting the whole line again.
+    #[test]
+    #[ignore = "timing"]
+    fn an_edit_in_a_long_line_is_faster_than_segmenting_it_again() {
+        use std::time::Instant;
+
+        let mut line = Line::from(&"a\u{301}".repeat(20_000));
+        let started = Instant::now();
+        for i in 0..100_usize {
+            line.insert_char_at(i.saturating_mul(100), 'b');
+        }
+        let spliced = started.elapsed();
+
+        let started = Instant::now();
+        for _ in 0..100 {
+            line.rebuild_fragments();
+        }
+        let rebuilt = started.elapsed();
+
+        assert_segmented(&line);
+        assert!(
+            spliced.saturating_mul(10) < rebuilt,
+            "{spliced:?} vs {rebuilt:?}"
+        );
+    }
+
+    #[test]
+    fn split_off_and_append_keep_the_fragments() {
+        let mut line = Line::from("ab\u{301}\u{1f468}\u{200d}\u{1f469}cd");
+        let mut tail = line.split_off(2);
+        assert_eq!(line.get_string(), "ab\u{301}");
+        assert_eq!(tail.get_string(), "\u{1f468}\u{200d}\u{1f469}cd");
+        assert_segmented(&line);
+        assert_segmented(&tail);
+
+        assert_eq!(tail.split_off(4).get_string(), "");
+        line.append(&tail);
+        line.append(&Line::from("\u{301}"));
+        assert_eq!(line.grapheme_count(), 5);
+        assert_segmented(&line);
+
+        let head = line.split_off(0);
+        assert_eq!(line.get_string(), "");
+        assert_segmented(&line);
+        assert_segmented(&head);
+    }
+}
@@ -37,12 +37,12 @@ impl UiComponent for MessageBar {
         self.needs_redraw || (self.is_message_expired() && !self.cleared_after_expired)
     }
 
-    fn draw(&mut self, pos_y: usize) -> Result<(), std::io::Error> {
+    fn draw(&mut self, terminal: &mut Terminal, pos_y: usize) -> Result<(), std::io::Error> {
         if self.is_message_expired() {
             self.cleared_after_expired = true;
-            Terminal::print_row(pos_y, "")
+            terminal.print_row(pos_y, "")
         } else {
-            Terminal::print_row(pos_y, &self.message)
+            terminal.print_row(pos_y, &self.message)
         }
     }
 }
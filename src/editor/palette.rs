@@ -0,0 +1,196 @@
+use crate::editor::{command_bar::Cmd, editor_cmd::EditorCommand};
+
+/// Rapresents what is run when a command of the palette is chosen.
+#[derive(Clone, Copy)]
+pub enum PaletteAction {
+    /// A command of normal mode, as if its keys were pressed.
+    Normal(EditorCommand),
+    /// A command typed after `:`.
+    Ex(&'static str),
+    /// Asks for the argument of the command in the command bar.
+    Prompt(Cmd),
+}
+
+/// Rapresents a command listed in the palette, with
+/// the keys that run it outside of the palette.
+pub struct PaletteEntry {
+    pub name: &'static str,
+    pub keys: &'static str,
+    pub action: PaletteAction,
+}
+
+impl PaletteEntry {
+    /// The text shown in the palette and matched with what is typed.
+    pub fn label(&self) -> String {
+        if self.keys.is_empty() {
+            self.name.to_string()
+        } else {
+            format!("{} ({})", self.name, self.keys)
+        }
+    }
+}
+
+/// Keeps the commands shown by the palette in the order they are listed.
+pub struct Palette {
+    entries: Vec<PaletteEntry>,
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        let mut palette = Self {
+            entries: Vec::new(),
+        };
+
+        palette.register("Save", "Ctrl-S", PaletteAction::Normal(EditorCommand::Save));
+        palette.register("Quit", "Ctrl-Q", PaletteAction::Normal(EditorCommand::Quit));
+        palette.register(
+            "Save and quit",
+            "ZZ",
+            PaletteAction::Normal(EditorCommand::SaveAndQuit),
+        );
+        palette.register(
+            "Quit without saving",
+            "ZQ",
+            PaletteAction::Normal(EditorCommand::ForceQuit),
+        );
+        palette.register("Search", "/", PaletteAction::Normal(EditorCommand::Search));
+        palette.register(
+            "Search backward",
+            "?",
+            PaletteAction::Normal(EditorCommand::SearchBackward),
+        );
+        palette.register(
+            "Next match",
+            "n",
+            PaletteAction::Normal(EditorCommand::NextOccurrence),
+        );
+        palette.register(
+            "Previous match",
+            "N",
+            PaletteAction::Normal(EditorCommand::PrevOccurrence),
+        );
+        palette.register(
+            "Show the key bindings",
+            "F1",
+            PaletteAction::Normal(EditorCommand::Help),
+        );
+        palette.register("Go to line", "", PaletteAction::Prompt(Cmd::Goto));
+        palette.register("Open a recent file", ":recent", PaletteAction::Ex("recent"));
+        palette.register("Show the statistics", ":stats", PaletteAction::Ex("stats"));
+        palette.register("Format the file", ":fmt", PaletteAction::Ex("fmt"));
+        palette.register("Reload the file", ":e!", PaletteAction::Ex("e!"));
+        palette.register(
+            "Toggle the fold",
+            "za",
+            PaletteAction::Normal(EditorCommand::ToggleFold),
+        );
+        palette.register("Paste", "p", PaletteAction::Normal(EditorCommand::Paste));
+        palette.register(
+            "Add a cursor on the next occurrence",
+            "Ctrl-D",
+            PaletteAction::Normal(EditorCommand::AddCursor),
+        );
+        palette.register(
+            "Delete the line",
+            "dd",
+            PaletteAction::Normal(EditorCommand::DeleteLine),
+        );
+        palette.register(
+            "Delete to the end of the line",
+            "D",
+            PaletteAction::Normal(EditorCommand::DeleteToEol),
+        );
+        palette.register(
+            "Change to the end of the line",
+            "C",
+            PaletteAction::Normal(EditorCommand::ChangeToEol),
+        );
+        palette.register(
+            "Duplicate the line",
+            "Alt-Shift-Down",
+            PaletteAction::Normal(EditorCommand::DuplicateLine),
+        );
+        palette.register(
+            "Move the line up",
+            "Alt-Up",
+            PaletteAction::Normal(EditorCommand::MoveLineUp),
+        );
+        palette.register(
+            "Move the line down",
+            "Alt-Down",
+            PaletteAction::Normal(EditorCommand::MoveLineDown),
+        );
+        palette.register(
+            "Join the lines",
+            "J",
+            PaletteAction::Normal(EditorCommand::JoinLines),
+        );
+        palette.register(
+            "Join the lines without spaces",
+            "gJ",
+            PaletteAction::Normal(EditorCommand::JoinWithoutSpace),
+        );
+        palette.register_settings();
+
+        palette
+    }
+}
+
+impl Palette {
+    /// The settings of the view turned on and off by the commands.
+    fn register_settings(&mut self) {
+        self.register(
+            "Stop highlighting the search matches until the next search",
+            ":noh",
+            PaletteAction::Ex("noh"),
+        );
+        self.register(
+            "Highlight the search matches",
+            ":set hlsearch",
+            PaletteAction::Ex("set hlsearch"),
+        );
+        self.register(
+            "Never highlight the search matches",
+            ":set nohlsearch",
+            PaletteAction::Ex("set nohlsearch"),
+        );
+        self.register(
+            "Highlight the word under the cursor",
+            ":set wordhl",
+            PaletteAction::Ex("set wordhl"),
+        );
+        self.register(
+            "Stop highlighting the word under the cursor",
+            ":set nowordhl",
+            PaletteAction::Ex("set nowordhl"),
+        );
+        self.register(
+            "Highlight the cursor column",
+            ":set cursorcolumn",
+            PaletteAction::Ex("set cursorcolumn"),
+        );
+        self.register(
+            "Stop highlighting the cursor column",
+            ":set nocursorcolumn",
+            PaletteAction::Ex("set nocursorcolumn"),
+        );
+        self.register(
+            "Toggle the focus mode",
+            ":focus",
+            PaletteAction::Ex("focus"),
+        );
+    }
+
+    /// Adds a command at the end of the list.
+    pub fn register(&mut self, name: &'static str, keys: &'static str, action: PaletteAction) {
+        self.entries.push(PaletteEntry { name, keys, action });
+    }
+
+    pub fn labels(&self) -> Vec<String> {
+        self.entries.iter().map(PaletteEntry::label).collect()
+    }
+
+    pub fn action(&self, index: usize) -> Option<PaletteAction> {
+        self.entries.get(index).map(|entry| entry.action)
+    }
+}
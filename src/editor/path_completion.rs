@@ -0,0 +1,51 @@
+use std::fs;
+
+/// Lists the paths that could complete the supplied input by
+/// reading the directory it points to. Directories end with `/`.
+pub fn candidates(input: &str) -> Vec<String> {
+    let (dir, prefix) = match input.rfind('/') {
+        Some(index) => input.split_at(index.saturating_add(1)),
+        None => ("", input),
+    };
+
+    let read_from = if dir.is_empty() { "." } else { dir };
+    let Ok(entries) = fs::read_dir(read_from) else {
+        return Vec::new();
+    };
+
+    let mut candidates: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+
+            let is_dir = entry.file_type().is_ok_and(|ty| ty.is_dir());
+            let suffix = if is_dir { "/" } else { "" };
+            Some(format!("{dir}{name}{suffix}"))
+        })
+        .collect();
+
+    candidates.sort();
+    candidates
+}
+
+/// Returns the longest prefix shared by all the candidates.
+pub fn longest_common_prefix(candidates: &[String]) -> String {
+    let Some(first) = candidates.first() else {
+        return String::new();
+    };
+
+    let mut prefix = first.as_str();
+    for candidate in candidates.iter().skip(1) {
+        let common = prefix
+            .char_indices()
+            .zip(candidate.chars())
+            .find(|((_, a), b)| a != b)
+            .map_or(prefix.len().min(candidate.len()), |((i, _), _)| i);
+        prefix = &prefix[..common];
+    }
+
+    prefix.to_string()
+}
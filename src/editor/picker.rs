@@ -0,0 +1,263 @@
+use std::cmp;
+
+use unicode_width::UnicodeWidthStr;
+
+use crate::editor::{
+    fuzzy::fuzzy_match,
+    terminal::{Position, Terminal, TerminalSize},
+    ui_component::UiComponent,
+};
+
+/// An overlay drawn centered over the view which lists
+/// some items and lets the user select one of them.
+/// With a query only the items matching it are shown, the best
+/// matches first, along with the characters they matched.
+#[derive(Default)]
+pub struct Picker {
+    title: String,
+    items: Vec<String>,
+    query: Option<String>,
+    shown: Vec<(usize, Vec<usize>)>,
+    selected: usize,
+    scroll_offset: usize,
+    size: TerminalSize,
+    needs_redraw: bool,
+}
+
+impl Picker {
+    pub fn set_items(&mut self, title: &str, items: Vec<String>) {
+        self.title = title.to_string();
+        self.shown = (0..items.len()).map(|index| (index, Vec::new())).collect();
+        self.items = items;
+        self.query = None;
+        self.selected = 0;
+        self.scroll_offset = 0;
+        self.set_needs_redraw(true);
+    }
+
+    /// Shows the items filtered by what is typed, starting from all of them.
+    pub fn set_filtered_items(&mut self, title: &str, items: Vec<String>) {
+        self.set_items(title, items);
+        self.query = Some(String::new());
+    }
+
+    pub fn is_filtered(&self) -> bool {
+        self.query.is_some()
+    }
+
+    pub fn push_query(&mut self, ch: char) {
+        if let Some(query) = &mut self.query {
+            query.push(ch);
+            self.filter();
+        }
+    }
+
+    pub fn pop_query(&mut self) {
+        if let Some(query) = &mut self.query {
+            query.pop();
+            self.filter();
+        }
+    }
+
+    /// Keeps the items matching the query sorted by their score,
+    /// the ones scoring the same stay in their order.
+    fn filter(&mut self) {
+        let query = self.query.as_deref().unwrap_or_default();
+        let mut matches: Vec<(i64, usize, Vec<usize>)> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| {
+                let (score, chars) = fuzzy_match(query, item)?;
+                Some((score, index, chars))
+            })
+            .collect();
+        matches.sort_by_key(|&(score, _, _)| cmp::Reverse(score));
+
+        self.shown = matches
+            .into_iter()
+            .map(|(_, index, chars)| (index, chars))
+            .collect();
+        self.selected = 0;
+        self.scroll_offset = 0;
+        self.set_needs_redraw(true);
+    }
+
+    /// The index of the selected item among all the items.
+    pub fn selected_index(&self) -> Option<usize> {
+        self.shown.get(self.selected).map(|&(index, _)| index)
+    }
+
+    pub fn selected_item(&self) -> Option<&str> {
+        self.selected_index()
+            .and_then(|index| self.items.get(index))
+            .map(String::as_str)
+    }
+
+    pub fn move_up(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+        self.scroll_to_selected();
+    }
+
+    pub fn move_down(&mut self) {
+        if self.selected.saturating_add(1) < self.shown.len() {
+            self.selected = self.selected.saturating_add(1);
+        }
+        self.scroll_to_selected();
+    }
+
+    /// Position of the selected item on the screen,
+    /// or of the end of the query if there is one.
+    pub fn cursor_position(&self) -> Position {
+        let (origin, width, _) = self.frame();
+        if let Some(query) = &self.query {
+            return Position {
+                x: cmp::min(
+                    origin.x.saturating_add(3).saturating_add(query.width()),
+                    origin.x.saturating_add(width).saturating_sub(2),
+                ),
+                y: origin.y.saturating_add(1),
+            };
+        }
+
+        Position {
+            x: origin.x.saturating_add(1),
+            y: origin
+                .y
+                .saturating_add(1)
+                .saturating_add(self.selected.saturating_sub(self.scroll_offset)),
+        }
+    }
+
+    /// The rows taken by the query, if there is one.
+    fn query_rows(&self) -> usize {
+        usize::from(self.query.is_some())
+    }
+
+    fn visible_rows(&self) -> usize {
+        self.size
+            .height
+            .saturating_sub(2)
+            .saturating_sub(self.query_rows())
+    }
+
+    fn scroll_to_selected(&mut self) {
+        let rows = self.visible_rows();
+        if self.selected < self.scroll_offset {
+            self.scroll_offset = self.selected;
+        } else if self.selected >= self.scroll_offset.saturating_add(rows) {
+            self.scroll_offset = self.selected.saturating_sub(rows).saturating_add(1);
+        }
+        self.set_needs_redraw(true);
+    }
+
+    /// Computes the top left corner, the width and the height
+    /// of the box surrounding the items.
+    fn frame(&self) -> (Position, usize, usize) {
+        let longest = self
+            .items
+            .iter()
+            .map(|item| item.width())
+            .chain(std::iter::once(self.title.width()))
+            .max()
+            .unwrap_or(0);
+
+        let width = cmp::min(longest.saturating_add(4), self.size.width);
+        let height = cmp::min(
+            self.items
+                .len()
+                .saturating_add(2)
+                .saturating_add(self.query_rows()),
+            self.size.height,
+        );
+
+        let origin = Position {
+            x: self.size.width.saturating_sub(width).div_ceil(2),
+            y: self.size.height.saturating_sub(height).div_ceil(2),
+        };
+
+        (origin, width, height)
+    }
+
+    /// Pads with `fill` or truncates the text so that it takes
+    /// exactly `width` columns.
+    fn fit(text: &str, width: usize, fill: &str) -> String {
+        let mut result = String::new();
+        let mut used: usize = 0;
+        for ch in text.chars() {
+            let ch_width = ch.to_string().width();
+            if used.saturating_add(ch_width) > width {
+                break;
+            }
+            used = used.saturating_add(ch_width);
+            result.push(ch);
+        }
+
+        result.push_str(&fill.repeat(width.saturating_sub(used)));
+        result
+    }
+}
+
+impl UiComponent for Picker {
+    fn set_needs_redraw(&mut self, val: bool) {
+        self.needs_redraw = val;
+    }
+
+    fn needs_redraw(&self) -> bool {
+        self.needs_redraw
+    }
+
+    fn set_size(&mut self, size: TerminalSize) {
+        self.size = size;
+    }
+
+    fn draw(&mut self, terminal: &mut Terminal, pos_y: usize) -> Result<(), std::io::Error> {
+        let (origin, width, height) = self.frame();
+        if width < 4 || height < 3 {
+            return Ok(());
+        }
+
+        let inner = width.saturating_sub(2);
+        let x = origin.x;
+        let mut y = origin.y.saturating_add(pos_y);
+
+        let title = Self::fit(&format!(" {} ", self.title), inner, "─");
+        terminal.print_at(Position { x, y }, &format!("┌{title}┐"))?;
+
+        if let Some(query) = &self.query {
+            y = y.saturating_add(1);
+            let text = Self::fit(&format!(" > {query}"), inner, " ");
+            terminal.print_at(Position { x, y }, &format!("│{text}│"))?;
+        }
+
+        let rows = cmp::min(self.visible_rows(), self.items.len());
+        let mut shown = self
+            .shown
+            .iter()
+            .enumerate()
+            .skip(self.scroll_offset)
+            .filter_map(|(i, (index, chars))| Some((i, self.items.get(*index)?, chars)));
+        for _ in 0..rows {
+            y = y.saturating_add(1);
+            let Some((i, item, chars)) = shown.next() else {
+                let blank = " ".repeat(inner);
+                terminal.print_at(Position { x, y }, &format!("│{blank}│"))?;
+                continue;
+            };
+
+            terminal.print_at(Position { x, y }, "│")?;
+            let text = Self::fit(&format!(" {item}"), inner, " ");
+            let pos = Position {
+                x: x.saturating_add(1),
+                y,
+            };
+            // The item is shifted by the space before it.
+            let marked: Vec<usize> = chars.iter().map(|ch| ch.saturating_add(1)).collect();
+            terminal.print_marked_at(pos, &text, &marked, i == self.selected)?;
+            terminal.print("│")?;
+        }
+
+        y = y.saturating_add(1);
+        terminal.print_at(Position { x, y }, &format!("└{}┘", "─".repeat(inner)))
+    }
+}
@@ -0,0 +1,71 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::editor::config::Config;
+
+const RECENT_FILE_NAME: &str = "recent";
+const MAX_RECENT_FILES: usize = 20;
+
+/// Keeps track of the files opened recently, the most recent
+/// first, persisting them in the config directory.
+#[derive(Default)]
+pub struct RecentFiles {
+    paths: Vec<PathBuf>,
+}
+
+impl RecentFiles {
+    pub fn load() -> Self {
+        let paths = Self::file_path()
+            .and_then(|path| fs::read_to_string(path).ok())
+            .map(|content| {
+                content
+                    .lines()
+                    .filter(|line| !line.is_empty())
+                    .map(PathBuf::from)
+                    .take(MAX_RECENT_FILES)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self { paths }
+    }
+
+    /// Moves the file to the top of the list, removing the
+    /// duplicates by comparing the canonical paths.
+    pub fn push(&mut self, path: &str) {
+        let Ok(path) = fs::canonicalize(path) else {
+            return;
+        };
+
+        self.paths.retain(|recent| *recent != path);
+        self.paths.insert(0, path);
+        self.paths.truncate(MAX_RECENT_FILES);
+    }
+
+    pub fn save(&self) -> Result<(), std::io::Error> {
+        let Some(path) = Self::file_path() else {
+            return Ok(());
+        };
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut content = String::new();
+        for recent in self.paths.iter().filter_map(|path| path.to_str()) {
+            content.push_str(recent);
+            content.push('\n');
+        }
+        fs::write(path, content)
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.paths.iter().map(PathBuf::as_path)
+    }
+
+    fn file_path() -> Option<PathBuf> {
+        Config::dir().map(|dir| dir.join(RECENT_FILE_NAME))
+    }
+}
@@ -0,0 +1,106 @@
+use std::{
+    io::{Error, ErrorKind, Write},
+    process::{Command, Output, Stdio},
+    sync::mpsc::{self, Receiver, TryRecvError},
+    thread,
+};
+
+/// Runs a command on a background thread, so that the
+/// editor keeps responding until its output is ready.
+pub struct Job {
+    receiver: Receiver<Result<String, String>>,
+}
+
+impl Job {
+    pub fn start(run: impl FnOnce() -> Result<String, String> + Send + 'static) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = sender.send(run());
+        });
+
+        Self { receiver }
+    }
+
+    /// The output of the command once it finished.
+    pub fn poll(&self) -> Option<Result<String, String>> {
+        match self.receiver.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(String::from("The command crashed"))),
+        }
+    }
+
+    /// Blocks until the command finished.
+    pub fn wait(self) -> Result<String, String> {
+        self.receiver
+            .recv()
+            .unwrap_or_else(|_| Err(String::from("The command crashed")))
+    }
+}
+
+/// Runs the command with `sh -c` feeding it the input,
+/// returns what it printed or the reason it failed.
+pub fn filter(command: &str, input: &str) -> Result<String, String> {
+    let output = pipe(Command::new("sh").arg("-c").arg(command), input)
+        .map_err(|err| format!("Could not run {command}: {err}"))?;
+
+    if !output.status.success() {
+        return Err(failure(command, &output));
+    }
+
+    String::from_utf8(output.stdout).map_err(|_| format!("{command} printed invalid UTF-8"))
+}
+
+/// Formats Rust code with `rustfmt`, the code is returned
+/// untouched in the error if it can't be parsed.
+pub fn rustfmt(input: &str) -> Result<String, String> {
+    let output = pipe(
+        Command::new("rustfmt").args(["--edition", "2024", "--emit", "stdout"]),
+        input,
+    )
+    .map_err(|err| match err.kind() {
+        ErrorKind::NotFound => String::from("rustfmt is not installed"),
+        _ => format!("Could not run rustfmt: {err}"),
+    })?;
+
+    if !output.status.success() {
+        return Err(failure("rustfmt", &output));
+    }
+
+    String::from_utf8(output.stdout).map_err(|_| String::from("rustfmt printed invalid UTF-8"))
+}
+
+/// Spawns the command with its standard streams piped and writes
+/// the input on another thread, so that a command printing a lot
+/// before reading everything doesn't block.
+pub fn pipe(command: &mut Command, input: &str) -> Result<Output, Error> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let stdin = child.stdin.take();
+    let input = input.to_string();
+    let writer = thread::spawn(move || {
+        if let Some(mut stdin) = stdin {
+            let _ = stdin.write_all(input.as_bytes());
+        }
+    });
+
+    let output = child.wait_with_output()?;
+    let _ = writer.join();
+    Ok(output)
+}
+
+/// The first line the command printed on stderr, or its exit status.
+pub fn failure(command: &str, output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map_or_else(
+            || format!("{command} failed with {}", output.status),
+            str::to_string,
+        )
+}
@@ -1,3 +1,5 @@
+use std::time::{Duration, Instant};
+
 use crate::editor::{
     EditorMode,
     document_status::DocumentStatus,
@@ -5,10 +7,23 @@ use crate::editor::{
     ui_component::UiComponent,
 };
 
+/// The frames of the spinner shown while the editor is busy.
+const SPINNER: [char; 4] = ['|', '/', '-', '\\'];
+/// How long a frame of the spinner stays on the screen.
+const SPINNER_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Rapresents an operation running on the background, shown with a spinner.
+struct Busy {
+    message: String,
+    frame: usize,
+    last_tick: Instant,
+}
+
 #[derive(Default)]
 pub struct StatusBar {
     editor_mode: EditorMode,
     doc_status: DocumentStatus,
+    busy: Option<Busy>,
     needs_redraw: bool,
     size: TerminalSize,
 }
@@ -21,6 +36,35 @@ impl StatusBar {
         }
     }
 
+    /// Shows the spinner with the message, or hides it.
+    pub fn set_busy(&mut self, message: Option<&str>) {
+        self.busy = message.map(|message| Busy {
+            message: message.to_string(),
+            frame: 0,
+            last_tick: Instant::now(),
+        });
+        self.needs_redraw = true;
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.busy.is_some()
+    }
+
+    /// Moves the spinner to its next frame once its interval passed.
+    pub fn tick(&mut self) {
+        if let Some(busy) = &mut self.busy
+            && busy.last_tick.elapsed() >= SPINNER_INTERVAL
+        {
+            busy.frame = busy
+                .frame
+                .saturating_add(1)
+                .checked_rem(SPINNER.len())
+                .unwrap_or(0);
+            busy.last_tick = Instant::now();
+            self.needs_redraw = true;
+        }
+    }
+
     pub fn update_status(&mut self, new_status: DocumentStatus) {
         if new_status != self.doc_status {
             self.doc_status = new_status;
@@ -42,18 +86,26 @@ impl UiComponent for StatusBar {
         self.size = size;
     }
 
-    fn draw(&mut self, pos_y: usize) -> Result<(), std::io::Error> {
+    fn draw(&mut self, terminal: &mut Terminal, pos_y: usize) -> Result<(), std::io::Error> {
         let line_count = self.doc_status.line_count_to_string();
         let modified_indicator = self.doc_status.modified_indicator_to_string();
 
-        let line = format!(
+        let mut line = format!(
             "{} - {} - {line_count} {modified_indicator}",
             self.doc_status.file_name, self.editor_mode,
         );
+        if let Some(busy) = &self.busy {
+            let spinner = SPINNER.get(busy.frame).copied().unwrap_or_default();
+            line = format!("{line} {spinner} {}", busy.message);
+        }
 
         let separator = " | ";
-        let position_indicator = self.doc_status.position_indicator_to_string();
-        let ty = self.doc_status.file_type.to_string();
+        let position_indicator = format!(
+            "{} {}",
+            self.doc_status.position_indicator_to_string(),
+            self.doc_status.scroll_position_to_string()
+        );
+        let ty = self.doc_status.file_type_to_string();
         let remainder_len = self
             .size
             .width
@@ -62,13 +114,13 @@ impl UiComponent for StatusBar {
             .saturating_sub(separator.len())
             .saturating_sub(1);
 
-        let status = format!("{line} {ty:>remainder_len$}{separator}{position_indicator}",);
+        let status = format!("{line} {ty:>remainder_len$}{separator}{position_indicator}");
         let to_print = if status.len() <= self.size.width {
             status
         } else {
             String::default()
         };
 
-        Terminal::print_inverted_row(pos_y, &to_print)
+        terminal.print_inverted_row(pos_y, &to_print)
     }
 }
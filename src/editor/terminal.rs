@@ -1,15 +1,18 @@
 use crossterm::cursor;
+use crossterm::event::{DisableBracketedPaste, EnableBracketedPaste};
 use crossterm::queue;
 use crossterm::style;
 use crossterm::style::Attribute;
 use crossterm::style::Color;
 use crossterm::terminal::{self, ClearType, disable_raw_mode, enable_raw_mode, size};
+use std::env;
 use std::io::Error;
 use std::io::Write;
 use std::io::stdout;
+use std::sync::OnceLock;
 
 use crate::editor::annotated_line::AnnotatedLine;
-use crate::editor::annotated_line::AnnotationType;
+use crate::editor::theme::{self, Style, Theme};
 
 #[derive(Clone, Copy, Default)]
 pub struct TerminalSize {
@@ -35,144 +38,215 @@ impl Position {
     }
 }
 
-pub struct Terminal;
+/// Writes the output of the editor, to stdout unless it was
+/// given another writer. The output can also be captured.
+pub struct Terminal {
+    out: Box<dyn Write>,
+    captured: Option<Vec<u8>>,
+}
+
+impl Default for Terminal {
+    fn default() -> Self {
+        Self::new(stdout())
+    }
+}
+
+impl Write for Terminal {
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Error> {
+        match &mut self.captured {
+            Some(captured) => captured.write(buf),
+            None => self.out.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> Result<(), Error> {
+        if self.captured.is_some() {
+            Ok(())
+        } else {
+            self.out.flush()
+        }
+    }
+}
+
 impl Terminal {
+    pub fn new(out: impl Write + 'static) -> Self {
+        Self {
+            out: Box::new(out),
+            captured: None,
+        }
+    }
+
     /// Initializes the terminal entering the [raw mode](https://docs.rs/crossterm/0.28.1/crossterm/terminal/index.html#raw-mode)
     /// and also entering the alternate screen in order to preserve
     /// precedent output on the terminal (and for visualizing panic outputs)
-    pub fn initialize() -> Result<(), Error> {
+    pub fn initialize(&mut self) -> Result<(), Error> {
         enable_raw_mode()?;
         queue!(
-            stdout(),
+            self,
             terminal::EnterAlternateScreen,
-            terminal::DisableLineWrap
+            terminal::DisableLineWrap,
+            EnableBracketedPaste
         )?;
-        Self::clear_screen()?;
-        Self::execute()
+        self.clear_screen()?;
+        self.execute()
     }
 
     /// Terminates the terminal leaving the alternate screen and
     /// disabling raw mode.
-    pub fn terminate() -> Result<(), Error> {
+    pub fn terminate(&mut self) -> Result<(), Error> {
         queue!(
-            stdout(),
+            self,
+            DisableBracketedPaste,
             terminal::EnableLineWrap,
             terminal::LeaveAlternateScreen
         )?;
-        Self::show_cursor()?;
-        Self::execute()?;
+        self.show_cursor()?;
+        self.execute()?;
         disable_raw_mode()
     }
 
-    pub fn set_title(title: &str) -> Result<(), Error> {
-        queue!(stdout(), terminal::SetTitle(title))
+    pub fn set_title(&mut self, title: &str) -> Result<(), Error> {
+        queue!(self, terminal::SetTitle(title))
     }
 
-    pub fn clear_screen() -> Result<(), Error> {
-        queue!(stdout(), terminal::Clear(ClearType::All))
+    pub fn clear_screen(&mut self) -> Result<(), Error> {
+        queue!(self, terminal::Clear(ClearType::All))
     }
 
-    pub fn clear_line() -> Result<(), Error> {
-        queue!(stdout(), terminal::Clear(ClearType::CurrentLine))
+    pub fn clear_line(&mut self) -> Result<(), Error> {
+        queue!(self, terminal::Clear(ClearType::CurrentLine))
     }
 
-    pub fn move_cursor_to(pos: Position) -> Result<(), Error> {
+    pub fn move_cursor_to(&mut self, pos: Position) -> Result<(), Error> {
         let (x, y): (u16, u16) = (pos.x.try_into().unwrap(), pos.y.try_into().unwrap());
-        queue!(stdout(), cursor::MoveTo(x, y))
+        queue!(self, cursor::MoveTo(x, y))
     }
 
-    pub fn cursor_bar() -> Result<(), Error> {
-        queue!(stdout(), cursor::SetCursorStyle::SteadyBar)
+    pub fn cursor_bar(&mut self) -> Result<(), Error> {
+        queue!(self, cursor::SetCursorStyle::SteadyBar)
     }
 
-    pub fn cursor_block() -> Result<(), Error> {
-        queue!(stdout(), cursor::SetCursorStyle::SteadyBlock)
+    pub fn cursor_block(&mut self) -> Result<(), Error> {
+        queue!(self, cursor::SetCursorStyle::SteadyBlock)
     }
 
-    pub fn hide_cursor() -> Result<(), Error> {
-        queue!(stdout(), cursor::Hide)
+    pub fn hide_cursor(&mut self) -> Result<(), Error> {
+        queue!(self, cursor::Hide)
     }
 
-    pub fn show_cursor() -> Result<(), Error> {
-        queue!(stdout(), cursor::Show)
+    pub fn show_cursor(&mut self) -> Result<(), Error> {
+        queue!(self, cursor::Show)
     }
 
-    pub fn print(string: &str) -> Result<(), Error> {
-        queue!(stdout(), style::Print(string))
+    pub fn print(&mut self, string: &str) -> Result<(), Error> {
+        queue!(self, style::Print(string))
     }
 
-    pub fn print_inverted_row(row: usize, text: &str) -> Result<(), Error> {
-        Self::move_cursor_to(Position { x: 0, y: row })?;
-        Self::clear_line()?;
+    pub fn print_inverted_row(&mut self, row: usize, text: &str) -> Result<(), Error> {
+        self.move_cursor_to(Position { x: 0, y: row })?;
+        self.clear_line()?;
         let string = &format!("{}{}{}", Attribute::Reverse, text, Attribute::Reset);
-        Self::print(string)
+        self.print(string)
+    }
+
+    /// Prints a string starting from a specific cell without
+    /// clearing the rest of the row.
+    pub fn print_at(&mut self, pos: Position, text: &str) -> Result<(), Error> {
+        self.move_cursor_to(pos)?;
+        self.print(text)
+    }
+
+    /// Prints a string starting from a specific cell with the characters
+    /// at the indices underlined, the whole string can be inverted.
+    pub fn print_marked_at(
+        &mut self,
+        pos: Position,
+        text: &str,
+        marked: &[usize],
+        inverted: bool,
+    ) -> Result<(), Error> {
+        self.move_cursor_to(pos)?;
+        if inverted {
+            queue!(self, style::SetAttribute(Attribute::Reverse))?;
+        }
+
+        for (index, ch) in text.chars().enumerate() {
+            if marked.contains(&index) {
+                queue!(
+                    self,
+                    style::SetAttribute(Attribute::Underlined),
+                    style::Print(ch),
+                    style::SetAttribute(Attribute::NoUnderline)
+                )?;
+            } else {
+                queue!(self, style::Print(ch))?;
+            }
+        }
+
+        queue!(self, style::SetAttribute(Attribute::Reset))
     }
 
     /// Prints a string on a specific row.
-    pub fn print_row(row: usize, text: &str) -> Result<(), Error> {
-        Self::move_cursor_to(Position { x: 0, y: row })?;
-        Self::clear_line()?;
-        Self::print(text)
+    pub fn print_row(&mut self, row: usize, text: &str) -> Result<(), Error> {
+        self.move_cursor_to(Position { x: 0, y: row })?;
+        self.clear_line()?;
+        self.print(text)
     }
 
-    pub fn set_background(color: Color) -> Result<(), Error> {
-        queue!(stdout(), style::SetBackgroundColor(color))
+    pub fn set_background(&mut self, color: Color) -> Result<(), Error> {
+        queue!(self, style::SetBackgroundColor(Self::adapt_color(color)))
     }
 
-    pub fn set_foreground(color: Color) -> Result<(), Error> {
-        queue!(stdout(), style::SetForegroundColor(color))
+    pub fn set_foreground(&mut self, color: Color) -> Result<(), Error> {
+        queue!(self, style::SetForegroundColor(Self::adapt_color(color)))
     }
 
-    pub fn reset_colors() -> Result<(), Error> {
-        queue!(stdout(), style::ResetColor)
+    /// Whether the terminal advertises 24-bit colors through `COLORTERM`.
+    fn supports_truecolor() -> bool {
+        static TRUECOLOR: OnceLock<bool> = OnceLock::new();
+        *TRUECOLOR.get_or_init(|| {
+            env::var("COLORTERM").is_ok_and(|value| value == "truecolor" || value == "24bit")
+        })
     }
 
-    /// Prints a string on a specific row.
-    pub fn print_annotated_row(row: usize, text: &AnnotatedLine) -> Result<(), Error> {
-        Self::move_cursor_to(Position { x: 0, y: row })?;
-        Self::clear_line()?;
+    /// Degrades RGB colors to the nearest of the 256-color palette
+    /// when the terminal doesn't support true colors.
+    fn adapt_color(color: Color) -> Color {
+        match color {
+            Color::Rgb { r, g, b } if !Self::supports_truecolor() => {
+                Color::AnsiValue(theme::rgb_to_ansi256(r, g, b))
+            }
+            color => color,
+        }
+    }
+
+    pub fn reset_colors(&mut self) -> Result<(), Error> {
+        queue!(self, style::ResetColor)
+    }
+
+    /// Prints an annotated string on a specific row, using the
+    /// colors of the theme for every annotation.
+    pub fn print_annotated_row(
+        &mut self,
+        row: usize,
+        text: &AnnotatedLine,
+        theme: &Theme,
+    ) -> Result<(), Error> {
+        self.move_cursor_to(Position { x: 0, y: row })?;
+        self.clear_line()?;
 
         for i in text {
-            match i.ty {
-                AnnotationType::None => {}
-                AnnotationType::Match => {
-                    Self::set_foreground(Color::Black)?;
-                    Self::set_background(Color::Cyan)?;
-                }
-                AnnotationType::SelectedMatch => {
-                    Self::set_foreground(Color::Black)?;
-                    Self::set_background(Color::Magenta)?;
-                }
-                AnnotationType::Number => {
-                    Self::set_foreground(Color::Rgb {
-                        r: 243,
-                        g: 112,
-                        b: 102,
-                    })?;
-                }
-                AnnotationType::Keyword => {
-                    Self::set_foreground(Color::Blue)?;
-                }
-                AnnotationType::Type => {
-                    Self::set_foreground(Color::Green)?;
-                }
-                AnnotationType::Char => {
-                    Self::set_foreground(Color::Yellow)?;
-                }
-                AnnotationType::String => {
-                    Self::set_foreground(Color::DarkRed)?;
-                }
-                AnnotationType::Lifetime => {
-                    Self::set_foreground(Color::Cyan)?;
-                }
-                AnnotationType::Comment => {
-                    Self::set_foreground(Color::DarkGrey)?;
-                }
+            let style = theme.layered_style(&i.layers);
+            if let Some(color) = style.foreground {
+                self.set_foreground(color)?;
+            }
+            if let Some(color) = style.background {
+                self.set_background(color)?;
             }
 
-            Self::print(i.str)?;
-            if i.ty != AnnotationType::None {
-                Self::reset_colors()?;
+            self.print(i.str)?;
+            if style != Style::default() {
+                self.reset_colors()?;
             }
         }
 
@@ -183,8 +257,19 @@ impl Terminal {
     /// We do this becouse execute!() is inefficient since
     /// writing is an expensive operation and we need to execute
     /// the operations only after evalutating the keypresses.
-    pub fn execute() -> Result<(), Error> {
-        stdout().flush()
+    pub fn execute(&mut self) -> Result<(), Error> {
+        self.flush()
+    }
+
+    /// Collects the output from now on instead of writing
+    /// it out, so that it can be inspected.
+    pub fn start_capture(&mut self) {
+        self.captured = Some(Vec::new());
+    }
+
+    /// Stops collecting the output, returning what was collected.
+    pub fn stop_capture(&mut self) -> Vec<u8> {
+        self.captured.take().unwrap_or_default()
     }
 
     pub fn size() -> Result<TerminalSize, Error> {
@@ -193,3 +278,32 @@ impl Terminal {
         Ok(TerminalSize { width, height })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::{annotated_line::AnnotationType, config::Config};
+
+    fn printed(theme: &Theme) -> String {
+        let mut line = AnnotatedLine::from("fn main");
+        line.push_annotation(0..2, AnnotationType::Keyword);
+
+        let mut terminal = Terminal::new(std::io::sink());
+        terminal.start_capture();
+        terminal.print_annotated_row(0, &line, theme).unwrap();
+        String::from_utf8(terminal.stop_capture()).unwrap()
+    }
+
+    #[test]
+    fn the_colors_of_the_theme_are_printed() {
+        let default = printed(&Theme::default());
+        assert!(default.contains("\x1b[38;5;12mfn\x1b[0m main"));
+
+        let config = Config::parse("color.keyword = red on black");
+        let custom = printed(&config.theme);
+        assert!(custom.contains("\x1b[38;5;9m\x1b[48;5;0mfn\x1b[0m main"));
+
+        let config = Config::parse("theme = dark");
+        assert!(printed(&config.theme).contains("\x1b[38;5;14mfn"));
+    }
+}
@@ -0,0 +1,416 @@
+use std::collections::HashMap;
+
+use crossterm::style::Color;
+
+use crate::editor::annotated_line::AnnotationType;
+
+/// The colors used to print a kind of annotation, `None`
+/// leaves the terminal color unchanged.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct Style {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+}
+
+impl Style {
+    const fn fg(color: Color) -> Self {
+        Self {
+            foreground: Some(color),
+            background: None,
+        }
+    }
+
+    const fn bg(color: Color) -> Self {
+        Self {
+            foreground: None,
+            background: Some(color),
+        }
+    }
+
+    const fn fg_bg(foreground: Color, background: Color) -> Self {
+        Self {
+            foreground: Some(foreground),
+            background: Some(background),
+        }
+    }
+
+    /// Parses a style written as `<fg>`, `<fg> on <bg>` or `on <bg>`.
+    pub fn parse(value: &str) -> Option<Self> {
+        if let Some(bg) = value.strip_prefix("on ") {
+            return Some(Self::bg(parse_color(bg)?));
+        }
+
+        match value.split_once(" on ") {
+            Some((fg, bg)) => Some(Self::fg_bg(parse_color(fg)?, parse_color(bg)?)),
+            None => Some(Self::fg(parse_color(value)?)),
+        }
+    }
+}
+
+/// Maps every `AnnotationType` to the colors used to print it.
+#[derive(Clone)]
+pub struct Theme {
+    styles: HashMap<AnnotationType, Style>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::from_styles(&[
+            (
+                AnnotationType::Match,
+                Style::fg_bg(Color::Black, Color::Cyan),
+            ),
+            (
+                AnnotationType::SelectedMatch,
+                Style::fg_bg(Color::Black, Color::Magenta),
+            ),
+            (
+                AnnotationType::Number,
+                Style::fg(Color::Rgb {
+                    r: 243,
+                    g: 112,
+                    b: 102,
+                }),
+            ),
+            (AnnotationType::Keyword, Style::fg(Color::Blue)),
+            (AnnotationType::Type, Style::fg(Color::Green)),
+            (AnnotationType::Char, Style::fg(Color::Yellow)),
+            (AnnotationType::String, Style::fg(Color::DarkRed)),
+            (AnnotationType::Lifetime, Style::fg(Color::Cyan)),
+            (AnnotationType::Comment, Style::fg(Color::DarkGrey)),
+            (
+                AnnotationType::Selection,
+                Style::fg_bg(Color::Black, Color::Grey),
+            ),
+            (
+                AnnotationType::WordOccurrence,
+                Style::fg_bg(Color::White, Color::DarkGrey),
+            ),
+            (AnnotationType::DiffAdded, Style::fg(Color::Green)),
+            (AnnotationType::DiffModified, Style::fg(Color::Yellow)),
+            (AnnotationType::DiffDeleted, Style::fg(Color::Red)),
+            (AnnotationType::Fold, Style::fg(Color::Cyan)),
+            (AnnotationType::Touched, Style::fg(Color::DarkGrey)),
+            (
+                AnnotationType::CursorColumn,
+                Style::bg(Color::Rgb {
+                    r: 48,
+                    g: 48,
+                    b: 48,
+                }),
+            ),
+            (
+                AnnotationType::CursorLine,
+                Style::bg(Color::Rgb {
+                    r: 48,
+                    g: 48,
+                    b: 48,
+                }),
+            ),
+            (
+                AnnotationType::Cursor,
+                Style::fg_bg(Color::Black, Color::White),
+            ),
+        ])
+    }
+}
+
+impl Theme {
+    /// Returns one of the themes shipped with the editor.
+    pub fn by_name(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::default()),
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            _ => None,
+        }
+    }
+
+    fn dark() -> Self {
+        Self::from_styles(&[
+            (
+                AnnotationType::Match,
+                Style::fg_bg(Color::Black, Color::Yellow),
+            ),
+            (
+                AnnotationType::SelectedMatch,
+                Style::fg_bg(Color::Black, Color::DarkYellow),
+            ),
+            (AnnotationType::Number, Style::fg(Color::Magenta)),
+            (AnnotationType::Keyword, Style::fg(Color::Cyan)),
+            (AnnotationType::Type, Style::fg(Color::Green)),
+            (AnnotationType::Char, Style::fg(Color::Yellow)),
+            (AnnotationType::String, Style::fg(Color::Yellow)),
+            (AnnotationType::Lifetime, Style::fg(Color::Blue)),
+            (AnnotationType::Comment, Style::fg(Color::Grey)),
+            (
+                AnnotationType::Selection,
+                Style::fg_bg(Color::White, Color::DarkGrey),
+            ),
+            (
+                AnnotationType::WordOccurrence,
+                Style::fg_bg(Color::White, Color::DarkBlue),
+            ),
+            (AnnotationType::DiffAdded, Style::fg(Color::Green)),
+            (AnnotationType::DiffModified, Style::fg(Color::Yellow)),
+            (AnnotationType::DiffDeleted, Style::fg(Color::Red)),
+            (AnnotationType::Fold, Style::fg(Color::Cyan)),
+            (AnnotationType::Touched, Style::fg(Color::DarkGrey)),
+            (
+                AnnotationType::CursorColumn,
+                Style::bg(Color::Rgb {
+                    r: 48,
+                    g: 48,
+                    b: 48,
+                }),
+            ),
+            (
+                AnnotationType::CursorLine,
+                Style::bg(Color::Rgb {
+                    r: 48,
+                    g: 48,
+                    b: 48,
+                }),
+            ),
+            (
+                AnnotationType::Cursor,
+                Style::fg_bg(Color::Black, Color::White),
+            ),
+        ])
+    }
+
+    fn light() -> Self {
+        Self::from_styles(&[
+            (
+                AnnotationType::Match,
+                Style::fg_bg(Color::White, Color::DarkBlue),
+            ),
+            (
+                AnnotationType::SelectedMatch,
+                Style::fg_bg(Color::White, Color::DarkMagenta),
+            ),
+            (AnnotationType::Number, Style::fg(Color::DarkMagenta)),
+            (AnnotationType::Keyword, Style::fg(Color::DarkBlue)),
+            (AnnotationType::Type, Style::fg(Color::DarkGreen)),
+            (AnnotationType::Char, Style::fg(Color::DarkYellow)),
+            (AnnotationType::String, Style::fg(Color::DarkRed)),
+            (AnnotationType::Lifetime, Style::fg(Color::DarkCyan)),
+            (AnnotationType::Comment, Style::fg(Color::DarkGrey)),
+            (
+                AnnotationType::Selection,
+                Style::fg_bg(Color::Black, Color::Grey),
+            ),
+            (
+                AnnotationType::WordOccurrence,
+                Style::fg_bg(Color::White, Color::DarkGrey),
+            ),
+            (AnnotationType::DiffAdded, Style::fg(Color::DarkGreen)),
+            (AnnotationType::DiffModified, Style::fg(Color::DarkYellow)),
+            (AnnotationType::DiffDeleted, Style::fg(Color::DarkRed)),
+            (AnnotationType::Fold, Style::fg(Color::DarkCyan)),
+            (AnnotationType::Touched, Style::fg(Color::Grey)),
+            (
+                AnnotationType::CursorColumn,
+                Style::bg(Color::Rgb {
+                    r: 230,
+                    g: 230,
+                    b: 230,
+                }),
+            ),
+            (
+                AnnotationType::CursorLine,
+                Style::bg(Color::Rgb {
+                    r: 230,
+                    g: 230,
+                    b: 230,
+                }),
+            ),
+            (
+                AnnotationType::Cursor,
+                Style::fg_bg(Color::White, Color::Black),
+            ),
+        ])
+    }
+
+    fn from_styles(styles: &[(AnnotationType, Style)]) -> Self {
+        Self {
+            styles: styles.iter().copied().collect(),
+        }
+    }
+
+    pub fn style(&self, ty: AnnotationType) -> Style {
+        self.styles.get(&ty).copied().unwrap_or_default()
+    }
+
+    /// The colors of annotations printed one over the other, a color
+    /// left unset by an annotation is taken from the ones below it.
+    pub fn layered_style(&self, layers: &[AnnotationType]) -> Style {
+        layers.iter().fold(Style::default(), |below, &ty| {
+            let style = self.style(ty);
+            Style {
+                foreground: style.foreground.or(below.foreground),
+                background: style.background.or(below.background),
+            }
+        })
+    }
+
+    pub fn set_style(&mut self, ty: AnnotationType, style: Style) {
+        self.styles.insert(ty, style);
+    }
+}
+
+/// Parses the name used in the config file for an `AnnotationType`.
+pub fn parse_annotation_type(name: &str) -> Option<AnnotationType> {
+    let ty = match name {
+        "number" => AnnotationType::Number,
+        "keyword" => AnnotationType::Keyword,
+        "type" => AnnotationType::Type,
+        "match" => AnnotationType::Match,
+        "selected_match" => AnnotationType::SelectedMatch,
+        "char" => AnnotationType::Char,
+        "string" => AnnotationType::String,
+        "lifetime" => AnnotationType::Lifetime,
+        "comment" => AnnotationType::Comment,
+        "selection" => AnnotationType::Selection,
+        "word_occurrence" => AnnotationType::WordOccurrence,
+        "diff_added" => AnnotationType::DiffAdded,
+        "diff_modified" => AnnotationType::DiffModified,
+        "diff_deleted" => AnnotationType::DiffDeleted,
+        "fold" => AnnotationType::Fold,
+        "touched" => AnnotationType::Touched,
+        "cursor_column" => AnnotationType::CursorColumn,
+        "cursor_line" => AnnotationType::CursorLine,
+        "cursor" => AnnotationType::Cursor,
+        _ => return None,
+    };
+
+    Some(ty)
+}
+
+/// Parses the name of a terminal color or a `#rrggbb` hex color.
+pub fn parse_color(value: &str) -> Option<Color> {
+    let value = value.trim();
+    if let Some(hex) = value.strip_prefix('#') {
+        return parse_hex(hex);
+    }
+
+    let color = match value.to_ascii_lowercase().as_str() {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "dark_red" => Color::DarkRed,
+        "green" => Color::Green,
+        "dark_green" => Color::DarkGreen,
+        "yellow" => Color::Yellow,
+        "dark_yellow" => Color::DarkYellow,
+        "blue" => Color::Blue,
+        "dark_blue" => Color::DarkBlue,
+        "magenta" => Color::Magenta,
+        "dark_magenta" => Color::DarkMagenta,
+        "cyan" => Color::Cyan,
+        "dark_cyan" => Color::DarkCyan,
+        "white" => Color::White,
+        "grey" | "gray" => Color::Grey,
+        "dark_grey" | "dark_gray" => Color::DarkGrey,
+        "reset" => Color::Reset,
+        _ => return None,
+    };
+
+    Some(color)
+}
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+
+    let channel = |range| u8::from_str_radix(hex.get(range)?, 16).ok();
+    Some(Color::Rgb {
+        r: channel(0..2)?,
+        g: channel(2..4)?,
+        b: channel(4..6)?,
+    })
+}
+
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+/// Converts an RGB color to the nearest one of the 256-color palette,
+/// picking between the 6x6x6 color cube and the grayscale ramp.
+pub fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    let nearest_level = |value: u8| {
+        CUBE_LEVELS
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, level)| level.abs_diff(value))
+            .map_or(0, |(i, _)| i)
+    };
+
+    let (ri, gi, bi) = (nearest_level(r), nearest_level(g), nearest_level(b));
+    let cube = (CUBE_LEVELS[ri], CUBE_LEVELS[gi], CUBE_LEVELS[bi]);
+    let cube_index = ri
+        .saturating_mul(36)
+        .saturating_add(gi.saturating_mul(6))
+        .saturating_add(bi)
+        .saturating_add(16);
+
+    let (gray_index, gray) = (0..24u8)
+        .map(|i| (i, i.saturating_mul(10).saturating_add(8)))
+        .min_by_key(|(_, gray)| distance((r, g, b), (*gray, *gray, *gray)))
+        .unwrap_or((0, 8));
+
+    if distance((r, g, b), (gray, gray, gray)) < distance((r, g, b), cube) {
+        gray_index.saturating_add(232)
+    } else {
+        u8::try_from(cube_index).unwrap_or(u8::MAX)
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let square = |x: u8, y: u8| u32::from(x.abs_diff(y)).saturating_pow(2);
+    square(a.0, b.0)
+        .saturating_add(square(a.1, b.1))
+        .saturating_add(square(a.2, b.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex_colors_are_parsed_as_rgb() {
+        assert_eq!(
+            parse_color("#ff8800"),
+            Some(Color::Rgb {
+                r: 255,
+                g: 136,
+                b: 0
+            })
+        );
+        assert_eq!(
+            parse_color(" #282C34 "),
+            Some(Color::Rgb {
+                r: 40,
+                g: 44,
+                b: 52
+            })
+        );
+        assert_eq!(parse_color("#ff880"), None);
+        assert_eq!(parse_color("#ff88zz"), None);
+        assert_eq!(parse_color("#ff88\u{e9}"), None);
+    }
+
+    #[test]
+    fn styles_take_hex_colors_on_both_sides() {
+        let style = Style::parse("#ff8800 on dark_grey").unwrap();
+        assert_eq!(parse_color("#ff8800"), style.foreground);
+        assert_eq!(style.background, Some(Color::DarkGrey));
+        assert_eq!(Style::parse("on #000000").unwrap().foreground, None);
+    }
+
+    #[test]
+    fn rgb_colors_degrade_to_the_nearest_of_the_palette() {
+        assert_eq!(rgb_to_ansi256(255, 136, 0), 208);
+        assert_eq!(rgb_to_ansi256(0, 0, 0), 16);
+        assert_eq!(rgb_to_ansi256(255, 255, 255), 231);
+        assert_eq!(rgb_to_ansi256(128, 128, 128), 244);
+    }
+}
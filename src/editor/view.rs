@@ -1,31 +1,142 @@
 use super::{
-    editor_cmd::{Direction, EditorCommand},
+    editor_cmd::{Case, Direction, EditorCommand, SearchDirection, VisualKind},
     terminal::{Position, TerminalSize},
 };
 
 use crate::editor::{
-    Terminal, annotated_line::AnnotatedLine, document_status::DocumentStatus,
-    highlighter::Highlighter, line::Line, ui_component::UiComponent,
+    Terminal,
+    annotated_line::{AnnotatedLine, AnnotationType},
+    config::Config,
+    document_status::{DocumentStatus, ScrollPosition},
+    file_type::FileType,
+    highlighter::{self, HighlightCache, Highlighter},
+    line::{GraphemeIndex, Line, MatchOptions},
+    theme::Theme,
+    ui_component::UiComponent,
 };
 
-use std::cmp;
+use std::{cmp, fs, ops::Range, path::Path, str::FromStr};
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
+mod abbrev;
 mod buffer;
-use buffer::Buffer;
+mod diff;
+mod encoding;
+pub use buffer::Buffer;
+use buffer::BufferStats;
+use diff::{Diff, LineChange};
 mod file_info;
+mod fold;
+mod indent;
+mod large_file;
+mod loader;
+mod marks;
+mod modeline;
+mod selection;
+pub use encoding::Encoding;
+use fold::Folds;
+pub use loader::LoadStatus;
+use loader::Loader;
+use marks::Marks;
+use modeline::Modeline;
+use selection::Selection;
 
 const EDITOR_NAME: &str = env!("CARGO_PKG_NAME");
 const EDITOR_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// How many lines above the screen are scanned to find out
+/// if the first visible line is inside a block comment.
+/// Files bigger than this are loaded on a background thread.
+const BACKGROUND_LOAD_SIZE: u64 = 1024 * 1024;
+/// Columns on the left of the text showing the changed lines.
+const GUTTER_WIDTH: usize = 2;
+const TOUCHED_GUTTER_WIDTH: usize = 1;
+const TOUCHED_MARKER: &str = "▎";
+/// Smooth scrolling covers this fraction of the rows left at every frame.
+const SMOOTH_SCROLL_FRAMES: usize = 4;
+/// The brackets and quotes closed as they are typed, with `auto_close` on.
+const PAIRS: [(char, char); 5] = [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+
+/// The characters surrounding text for `ch`, a bracket stands
+/// for its pair and anything else surrounds with itself.
+fn surround_pair(ch: char) -> (char, char) {
+    PAIRS
+        .iter()
+        .chain(&[('<', '>')])
+        .find(|&&(open, close)| open == ch || close == ch)
+        .copied()
+        .unwrap_or((ch, ch))
+}
 
 /// Rapresents a valid grapheme on the terminal, it is
 /// different from position since in only point to a valid
 /// character and not to a specific cell in the terminal.
-#[derive(Clone, Copy, Default)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
 pub struct Location {
     pub grapheme_index: usize,
     pub line_index: usize,
 }
 
+/// Where the cursor lands after an editing command, every command
+/// declares it instead of relying on how the movements snap the cursor.
+#[derive(Clone, Copy)]
+enum CursorPlacement {
+    At(Location),
+    FirstNonBlank(usize),
+}
+
+/// The number of matches of the search term and which one
+/// the cursor is on, valid until the buffer reaches a new revision.
+#[derive(Clone, Copy)]
+struct MatchCount {
+    revision: usize,
+    ordinal: usize,
+    count: usize,
+}
+
+/// The line and the screen column of the cursor, when they are
+/// highlighted, printed below everything else on the rows.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+struct Crosshair {
+    line: Option<usize>,
+    column: Option<usize>,
+}
+
+/// What the gutter shows on the left of a row: how the line differs
+/// from the file, and whether it was edited since the file was saved.
+#[derive(Clone, Copy)]
+struct Gutter<'a> {
+    diff: Option<&'a Diff>,
+    touched: Option<&'a Buffer>,
+}
+
+/// The number of matches of a search term while it's typed,
+/// valid until the buffer reaches a new revision.
+struct MatchPreview {
+    term: String,
+    scoped: bool,
+    revision: usize,
+    count: usize,
+}
+
+/// Rapresents the text last deleted or yanked from a selection,
+/// whole lines are pasted below the cursor line.
+#[derive(Default)]
+struct Register {
+    text: String,
+    linewise: bool,
+}
+
+/// Rapresents an insertion on a block selection, what is typed on its
+/// first line is copied on the others when leaving insert mode. Lines
+/// not reaching the column are padded when appending, skipped otherwise.
+struct BlockInsert {
+    lines: Range<usize>,
+    column: GraphemeIndex,
+    pad: bool,
+}
+
 /// This struct rapresents what we are showing on the screen.
 /// The field `need_redraw` is needed for when something is changed
 /// on the screen and we need to refresh the screen, otherwise nothing
@@ -38,49 +149,305 @@ pub struct View {
     needs_redraw: bool,
     size: TerminalSize,
     text_location: Location,
+    /// The cursors added next to the main one, valid while
+    /// the buffer is at `cursors_revision`.
+    extra_cursors: Vec<Location>,
+    cursors_revision: usize,
     scroll_offset: Position,
+    /// The row `scroll_offset` moves to over the next
+    /// frames, when `smooth_scroll` is on.
+    scroll_target: Option<usize>,
     search_term: String,
+    /// The last term searched that wasn't empty, searched
+    /// again when the search is submitted with no text.
+    last_search_term: String,
+    last_search_direction: SearchDirection,
+    search_scope: Option<(Location, Location)>,
+    /// The matches aren't highlighted until the next search,
+    /// set by `:noh`.
+    matches_hidden: bool,
+    whole_word_search: bool,
+    highlighted_word: Option<String>,
+    crosshair: Crosshair,
+    match_count: Option<MatchCount>,
+    match_preview: Option<MatchPreview>,
+    config: Config,
+    /// The config as it was loaded, `config` gets the settings
+    /// of the file type and of the modeline on top of it.
+    base_config: Config,
+    highlight_cache: HighlightCache,
+    loader: Option<Loader>,
+    pending_goto: Option<(usize, Option<usize>)>,
+    selection: Option<Selection>,
+    register: Register,
+    block_insert: Option<BlockInsert>,
+    diff: Option<Diff>,
+    folds: Folds,
+    marks: Marks,
 }
 
 impl View {
+    /// Creates a view showing the text, which isn't bound to a file.
+    pub fn from_text(text: &str) -> Self {
+        let Ok(buffer) = Buffer::from_str(text);
+        let mut view = Self::default();
+        view.set_buffer(buffer);
+        view
+    }
+
+    pub fn buffer(&self) -> &Buffer {
+        &self.buffer
+    }
+
+    /// The text of each line of the buffer.
+    pub fn lines(&self) -> Vec<String> {
+        self.buffer.lines.iter().map(ToString::to_string).collect()
+    }
+
     /// Calculates the position of the cursor on the visible
     /// screen subtracting the offset from the position.
     /// (See struct Position definition)
     pub fn cursor_position(&self) -> Position {
-        self.text_location_to_position()
-            .subtract(&self.scroll_offset)
+        let Position { x, y } = self
+            .text_location_to_position()
+            .subtract(&self.scroll_offset);
+        Position {
+            x: x.saturating_add(self.gutter_width()),
+            y,
+        }
+    }
+
+    /// The gutter is shown only when the lines are compared with
+    /// a file, or when the config asks for the touched lines.
+    fn gutter_width(&self) -> usize {
+        let diff = if self.diff.is_some() { GUTTER_WIDTH } else { 0 };
+        let touched = if self.config.touched_gutter {
+            TOUCHED_GUTTER_WIDTH
+        } else {
+            0
+        };
+        diff.saturating_add(touched)
+    }
+
+    /// Columns left for the text once the gutter is drawn.
+    fn text_width(&self) -> usize {
+        self.size.width.saturating_sub(self.gutter_width())
+    }
+
+    /// Keeps the current lines to compare the buffer with, if they come
+    /// from a file and the config asks for the gutter.
+    fn reset_diff(&mut self) {
+        let compared = self.config.diff_gutter
+            && self.buffer.file_info.path.is_some()
+            && !self.buffer.is_read_only();
+        self.diff = compared.then(|| Diff::new(&self.buffer.lines, self.buffer.revision()));
+        self.set_needs_redraw(true);
+    }
+
+    /// Whether the gutter doesn't show the last changes yet.
+    pub fn has_stale_diff(&self) -> bool {
+        self.diff
+            .as_ref()
+            .is_some_and(|diff| diff.is_stale(self.buffer.revision()))
+    }
+
+    /// Compares the buffer with the file again, it is not done on every
+    /// change since it gets slow on long files.
+    pub fn refresh_diff(&mut self) {
+        if let Some(diff) = &mut self.diff {
+            diff.update(&self.buffer.lines, self.buffer.revision());
+            self.set_needs_redraw(true);
+        }
     }
 
     /// Loads the buffer with the content of the file we are
-    /// rendering.
+    /// rendering. Big files are loaded on the background,
+    /// `poll_loading` tells when they are ready.
     pub fn load(&mut self, path: &str) -> Result<(), std::io::Error> {
-        let buf = Buffer::load(path)?;
+        if fs::metadata(path)?.len() > BACKGROUND_LOAD_SIZE {
+            self.loader = Some(Loader::start(path));
+            return Ok(());
+        }
 
-        self.buffer = buf;
-        self.set_needs_redraw(true);
+        let buf = Buffer::load(path)?;
+        self.set_buffer(buf);
 
         Ok(())
     }
 
+    fn set_buffer(&mut self, buffer: Buffer) {
+        self.buffer = buffer;
+        self.text_location = Location::default();
+        self.scroll_offset = Position::default();
+        self.scroll_target = None;
+        self.match_count = None;
+        self.selection = None;
+        self.extra_cursors.clear();
+        self.folds = Folds::default();
+        self.marks = Marks::default();
+        self.apply_file_type_config();
+        if self.config.modelines {
+            self.apply_modeline();
+        }
+        self.reset_diff();
+    }
+
+    /// Applies the settings of a modeline on the first
+    /// or last line of the buffer, the first one wins.
+    fn apply_modeline(&mut self) {
+        let last = self.buffer.height().saturating_sub(1);
+        let Some(modeline) = [0, last]
+            .into_iter()
+            .filter_map(|line_index| self.buffer.line(line_index))
+            .find_map(|line| Modeline::parse(line.get_string()))
+        else {
+            return;
+        };
+
+        if let Some(file_type) = modeline.file_type {
+            self.buffer.file_info.set_file_type(file_type);
+            self.apply_file_type_config();
+        }
+        if let Some(tab_width) = modeline.tab_width {
+            self.config.tab_width = tab_width;
+        }
+        if let Some(expand_tab) = modeline.expand_tab {
+            self.config.expand_tab = expand_tab;
+        }
+    }
+
+    /// Takes the settings of the file type of the buffer,
+    /// or the global ones where the type doesn't set them.
+    fn apply_file_type_config(&mut self) {
+        let settings = self
+            .base_config
+            .file_types
+            .get(&self.buffer.file_info.file_type())
+            .copied()
+            .unwrap_or_default();
+        self.config.tab_width = settings.tab_width.unwrap_or(self.base_config.tab_width);
+        self.config.expand_tab = settings.expand_tab.unwrap_or(self.base_config.expand_tab);
+    }
+
+    pub fn is_loading(&self) -> bool {
+        self.loader.is_some()
+    }
+
+    /// Checks on the file loaded on the background, the
+    /// buffer is replaced as soon as it is ready.
+    pub fn poll_loading(&mut self) -> Option<LoadStatus> {
+        let (status, buffer) = self.loader.as_ref()?.poll();
+        if let Some(buffer) = buffer {
+            self.set_buffer(buffer);
+            if let Some((line_number, column)) = self.pending_goto.take() {
+                self.goto(line_number, column);
+            }
+        }
+
+        if matches!(status, Some(LoadStatus::Loaded | LoadStatus::Failed(_))) {
+            self.loader = None;
+        }
+
+        status
+    }
+
+    /// Stops loading the file, the current buffer is kept.
+    pub fn cancel_loading(&mut self) {
+        if let Some(loader) = self.loader.take() {
+            loader.cancel();
+        }
+    }
+
+    pub fn set_config(&mut self, config: Config) {
+        self.base_config = config.clone();
+        self.config = config;
+        self.apply_file_type_config();
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
     /// Handles the `EditorCommand` sent to view.
     pub fn handle_command(&mut self, cmd: EditorCommand) {
-        match cmd {
-            EditorCommand::Move(mov) => self.handle_movement(mov),
-            EditorCommand::Resize(_) => {}
+        let placement = match cmd {
+            EditorCommand::Move(mov) => {
+                self.handle_movement(mov);
+                return;
+            }
+            EditorCommand::BlockStart => {
+                let location = self
+                    .buffer
+                    .find_enclosing_open("{", "}", self.text_location);
+                self.jump_to(location);
+                return;
+            }
+            EditorCommand::BlockEnd => {
+                let location = self
+                    .buffer
+                    .find_enclosing_close("{", "}", self.text_location);
+                self.jump_to(location);
+                return;
+            }
+            EditorCommand::Resize(_) => None,
+            _ if self.buffer.is_read_only() => return,
+            EditorCommand::Indent => self.indent_line(),
+            EditorCommand::Dedent => self.dedent_line(),
+            EditorCommand::DuplicateLine => Some(self.duplicate_line()),
+            EditorCommand::MoveLineUp => self.move_line_up(),
+            EditorCommand::MoveLineDown => self.move_line_down(),
+            EditorCommand::TransposeChars => self.transpose_chars(),
+            EditorCommand::Increment(delta) => self.increment_number(delta),
+            EditorCommand::ToggleCase => self.toggle_case(),
+            EditorCommand::ChangeWordCase(case) => self.change_word_case(case),
+            EditorCommand::JoinLines => self.join_lines(true),
+            EditorCommand::JoinWithoutSpace => self.join_lines(false),
+            EditorCommand::DeleteLine => self.delete_line(),
+            EditorCommand::DeleteToEol | EditorCommand::ChangeToEol => self.delete_to_eol(),
+            EditorCommand::SubstituteChar => self.substitute_char(),
+            EditorCommand::SubstituteLine => self.substitute_line(),
             _ => unreachable!(),
-        }
+        };
+
+        self.place_cursor(placement);
     }
 
     fn current_line_len(&self) -> usize {
         self.buffer
-            .lines
-            .get(self.text_location.line_index)
+            .line(self.text_location.line_index)
             .map_or(0, Line::grapheme_count)
     }
 
+    pub fn is_read_only(&self) -> bool {
+        self.buffer.is_read_only()
+    }
+
+    /// With `auto_close` on, typing the closing character in front of
+    /// the same one steps over it.
     pub fn handle_insertion(&mut self, sy: char) {
+        if self.buffer.is_read_only() {
+            return;
+        }
+
+        if !sy.is_alphanumeric() && sy != '_' {
+            self.expand_abbreviation();
+        }
+
+        if self.config.auto_close {
+            if PAIRS.iter().any(|&(_, close)| close == sy) && self.char_at_cursor(0) == Some(sy) {
+                self.handle_movement(Direction::Right);
+                return;
+            }
+            if let Some(close) = self.closing_pair(sy) {
+                self.buffer.insert_char(close, self.text_location);
+            }
+        }
+
         let old_len = self.current_line_len();
         self.buffer.insert_char(sy, self.text_location);
+        if self.config.normalize_input {
+            self.buffer.normalize_line(self.text_location.line_index);
+        }
         let new_len = self.current_line_len();
 
         #[allow(clippy::arithmetic_side_effects)]
@@ -90,292 +457,2503 @@ impl View {
         }
     }
 
+    /// Inserts the character as it is, without closing pairs,
+    /// expanding abbreviations or normalizing the line.
+    pub fn insert_raw(&mut self, sy: char) {
+        if self.buffer.is_read_only() {
+            return;
+        }
+
+        let old_len = self.current_line_len();
+        self.buffer.insert_char(sy, self.text_location);
+        if self.current_line_len() > old_len {
+            self.handle_movement(Direction::Right);
+        }
+        self.set_needs_redraw(true);
+    }
+
+    /// Replaces the text typed since the last blank before the cursor,
+    /// or the part of it from its first word character, with the
+    /// text of its abbreviation. Returns `false` if there is none.
+    fn expand_abbreviation(&mut self) -> bool {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        let Some(line) = self.buffer.line(line_index) else {
+            return false;
+        };
+
+        let before = line.get_str(0..grapheme_index);
+        let typed = before.rsplit(char::is_whitespace).next().unwrap_or(before);
+        let word = typed.trim_start_matches(|ch: char| !ch.is_alphanumeric() && ch != '_');
+        let Some((abbreviation, text)) = [typed, word].into_iter().find_map(|abbreviation| {
+            self.config
+                .abbreviations
+                .get(abbreviation)
+                .map(|text| (abbreviation, abbrev::expand(text)))
+        }) else {
+            return false;
+        };
+
+        let start = grapheme_index.saturating_sub(abbreviation.graphemes(true).count());
+        self.buffer.delete_range(line_index, start..grapheme_index);
+        let end = self.buffer.insert_text(
+            Location {
+                grapheme_index: start,
+                line_index,
+            },
+            &text,
+        );
+        self.place_cursor(Some(CursorPlacement::At(end)));
+        self.set_needs_redraw(true);
+        true
+    }
+
+    /// Replaces the word before the cursor with its snippet, the lines
+    /// after the first get the indentation of the cursor line and the
+    /// cursor lands on `$0`, or after the body. Returns `false` if the
+    /// word isn't the trigger of a snippet.
+    pub fn expand_snippet(&mut self) -> bool {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        if self.buffer.is_read_only() {
+            return false;
+        }
+
+        let Some(line) = self.buffer.line(line_index) else {
+            return false;
+        };
+        let start = line.previous_word_start(grapheme_index);
+        let Some(body) = self
+            .config
+            .snippets
+            .get(line.get_str(start..grapheme_index))
+        else {
+            return false;
+        };
+
+        let indentation = line.get_str(0..line.first_non_blank());
+        let tab = if self.config.expand_tab {
+            " ".repeat(self.config.tab_width)
+        } else {
+            String::from("\t")
+        };
+        let mut text = body
+            .replace('\t', &tab)
+            .replace('\n', &format!("\n{indentation}"));
+
+        let mut end = None;
+        if let Some(marker) = text.find("$0") {
+            let before = &text[..marker];
+            let last_line = before.rsplit('\n').next().unwrap_or_default();
+            let graphemes = last_line.graphemes(true).count();
+            let lines = before.matches('\n').count();
+            end = Some(Location {
+                grapheme_index: if lines == 0 {
+                    start.saturating_add(graphemes)
+                } else {
+                    graphemes
+                },
+                line_index: line_index.saturating_add(lines),
+            });
+            text.replace_range(marker..marker.saturating_add(2), "");
+        }
+
+        self.buffer.delete_range(line_index, start..grapheme_index);
+        let body_end = self.buffer.insert_text(
+            Location {
+                grapheme_index: start,
+                line_index,
+            },
+            &text,
+        );
+        self.place_cursor(Some(CursorPlacement::At(end.unwrap_or(body_end))));
+        self.set_needs_redraw(true);
+        true
+    }
+
+    /// Inserts a pasted text at the cursor position, splitting
+    /// it on newlines.
+    pub fn handle_paste(&mut self, text: &str) {
+        let text: String = if self.config.normalize_input {
+            text.nfc().collect()
+        } else {
+            text.to_string()
+        };
+
+        let text = if self.config.paste_reindent && text.contains('\n') {
+            self.reindent_paste(&text)
+        } else {
+            text
+        };
+
+        if self.buffer.is_read_only() {
+            return;
+        }
+
+        // The text is inserted as it is, without closing the pairs
+        // or expanding the abbreviations like typing does.
+        let at = self.text_location;
+        let tail = self.current_line_len().saturating_sub(at.grapheme_index);
+        let mut end = self.buffer.insert_text(at, &text.replace('\r', ""));
+        if self.config.normalize_input {
+            for line_index in at.line_index..=end.line_index {
+                self.buffer.normalize_line(line_index);
+            }
+            end.grapheme_index = self
+                .buffer
+                .line(end.line_index)
+                .map_or(0, |line| line.grapheme_count().saturating_sub(tail));
+        }
+        self.place_cursor(Some(CursorPlacement::At(end)));
+        self.set_needs_redraw(true);
+    }
+
+    /// Converts the indentation of the pasted lines to the one of
+    /// the file and moves them to the indentation of the cursor line.
+    fn reindent_paste(&self, text: &str) -> String {
+        let base = self
+            .buffer
+            .line(self.text_location.line_index)
+            .map_or(0, |line| {
+                indent::width(line.get_string(), self.config.tab_width)
+            });
+
+        indent::reindent(text, base, self.config.tab_width, self.config.expand_tab)
+    }
+
+    /// With `expand_tab` on, in the indentation made of spaces it
+    /// deletes back to the previous tab stop, like a tab would be.
+    /// With `auto_close` on, an empty pair is deleted as a whole.
     pub fn handle_backspace(&mut self) {
+        if self.buffer.is_read_only() {
+            return;
+        }
+
+        if self.config.auto_close && self.is_in_empty_pair() {
+            self.handle_deletion();
+        }
+
+        let soft_tab = self.soft_tab_before_cursor();
+        if soft_tab > 1 {
+            let Location {
+                grapheme_index,
+                line_index,
+            } = self.text_location;
+            let start = grapheme_index.saturating_sub(soft_tab);
+            self.buffer.delete_range(line_index, start..grapheme_index);
+            self.place_cursor(Some(CursorPlacement::At(Location {
+                grapheme_index: start,
+                line_index,
+            })));
+            return;
+        }
+
         if self.text_location.line_index != 0 || self.text_location.grapheme_index != 0 {
             self.handle_movement(Direction::Left);
             self.handle_deletion();
         }
     }
 
+    /// The character `offset` graphemes after the cursor, if it is one.
+    fn char_at_cursor(&self, offset: isize) -> Option<char> {
+        let index = self
+            .text_location
+            .grapheme_index
+            .checked_add_signed(offset)?;
+        let mut chars = self
+            .buffer
+            .line(self.text_location.line_index)?
+            .grapheme_at(index)?
+            .chars();
+        chars.next().filter(|_| chars.next().is_none())
+    }
+
+    /// The character closing the one typed, if it opens a pair that is
+    /// worth closing where the cursor is. Quotes are left alone after
+    /// a word, where they are likely apostrophes or lifetimes, and in Rust
+    /// after `&`, `<` or another quote, where they start a lifetime.
+    fn closing_pair(&self, open: char) -> Option<char> {
+        let &(_, close) = PAIRS.iter().find(|&&(pair_open, _)| pair_open == open)?;
+
+        let next = self.char_at_cursor(0);
+        if next.is_some_and(|ch| !ch.is_whitespace() && !PAIRS.iter().any(|&(_, c)| c == ch)) {
+            return None;
+        }
+        if open == close && self.char_at_cursor(-1).is_some_and(char::is_alphanumeric) {
+            return None;
+        }
+
+        let file_type = self.buffer.file_info.file_type();
+        if open == '\''
+            && file_type == FileType::Rust
+            && self
+                .char_at_cursor(-1)
+                .is_some_and(|ch| matches!(ch, '&' | '<' | '\''))
+        {
+            return None;
+        }
+
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        let line = self.buffer.line(line_index)?;
+        let (resume, state) = self
+            .highlight_cache
+            .resume_point(file_type, line_index, |row| {
+                self.buffer.line(row).map(Line::revision)
+            });
+        let before = (resume..line_index).filter_map(|row| self.buffer.line(row));
+        let byte = line.byte_range(grapheme_index..grapheme_index).start;
+        let syntax = highlighter::syntax_at(file_type, state, before, line, byte);
+        if matches!(
+            syntax,
+            Some(AnnotationType::String | AnnotationType::Char | AnnotationType::Comment)
+        ) {
+            return None;
+        }
+
+        Some(close)
+    }
+
+    /// Whether the cursor is between the two characters of a pair.
+    fn is_in_empty_pair(&self) -> bool {
+        let (Some(before), Some(after)) = (self.char_at_cursor(-1), self.char_at_cursor(0)) else {
+            return false;
+        };
+        PAIRS.contains(&(before, after))
+    }
+
+    /// The spaces between the cursor and the previous tab stop, if only
+    /// spaces are before the cursor and `expand_tab` is on.
+    fn soft_tab_before_cursor(&self) -> usize {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        if !self.config.expand_tab || grapheme_index == 0 {
+            return 0;
+        }
+
+        let only_spaces = self.buffer.line(line_index).is_some_and(|line| {
+            line.get_string()
+                .chars()
+                .take(grapheme_index)
+                .all(|ch| ch == ' ')
+        });
+        if !only_spaces {
+            return 0;
+        }
+
+        grapheme_index
+            .saturating_sub(1)
+            .checked_rem(self.config.tab_width)
+            .map_or(1, |spaces| spaces.saturating_add(1))
+    }
+
+    /// Deletes the word before the cursor, at the start
+    /// of a line it joins it with the previous one.
+    pub fn delete_word_back(&mut self) {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+
+        if grapheme_index == 0 {
+            self.handle_backspace();
+            return;
+        }
+
+        if self.buffer.is_read_only() {
+            return;
+        }
+
+        let start = self
+            .buffer
+            .line(line_index)
+            .map_or(0, |line| line.previous_word_start(grapheme_index));
+        self.buffer.delete_range(line_index, start..grapheme_index);
+        self.place_cursor(Some(CursorPlacement::At(Location {
+            grapheme_index: start,
+            line_index,
+        })));
+    }
+
+    /// Deletes the text before the cursor up to the first non-blank
+    /// character, when the cursor is already there up to the start of the line.
+    pub fn delete_to_line_start(&mut self) {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+
+        if grapheme_index == 0 || self.buffer.is_read_only() {
+            return;
+        }
+
+        let first_non_blank = self
+            .buffer
+            .line(line_index)
+            .map_or(0, Line::first_non_blank);
+        let start = if grapheme_index > first_non_blank {
+            first_non_blank
+        } else {
+            0
+        };
+
+        self.buffer.delete_range(line_index, start..grapheme_index);
+        self.place_cursor(Some(CursorPlacement::At(Location {
+            grapheme_index: start,
+            line_index,
+        })));
+    }
+
     pub fn handle_deletion(&mut self) {
+        if self.buffer.is_read_only() {
+            return;
+        }
+
         self.buffer.delete(self.text_location);
         self.set_needs_redraw(true);
     }
 
     pub fn save_as(&mut self, file_name: &str) -> Result<(), std::io::Error> {
-        self.set_needs_redraw(true);
-        self.buffer.save_as(file_name)
+        self.buffer.save_as(file_name)?;
+        self.reset_diff();
+        Ok(())
+    }
+
+    /// Appends the lines to another file, the buffer stays bound
+    /// to its own. Returns the number of bytes written.
+    pub fn append_to(&self, file_name: &str) -> Result<usize, std::io::Error> {
+        self.buffer.append_to(file_name)
+    }
+
+    pub fn file_path(&self) -> Option<&Path> {
+        self.buffer.file_info.path.as_deref()
+    }
+
+    /// Reads the file again from disk discarding the changes,
+    /// the cursor stays where it was as far as the new content allows.
+    pub fn reload(&mut self) -> Result<(), std::io::Error> {
+        let Some(path) = self
+            .file_path()
+            .map(|path| path.to_string_lossy().to_string())
+        else {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "The buffer has no file name",
+            ));
+        };
+
+        let file_type = self.buffer.file_info.file_type();
+        let mut buffer = Buffer::load(&path)?;
+        if file_type != buffer.file_info.file_type() {
+            buffer.file_info.set_file_type(file_type);
+        }
+
+        // The folds, the marks and the diff are reset like when
+        // opening the file, only the cursor and the scroll are kept.
+        let (location, scroll_offset) = (self.text_location, self.scroll_offset);
+        self.set_buffer(buffer);
+        self.scroll_offset = scroll_offset;
+        self.place_cursor(Some(CursorPlacement::At(location)));
+        Ok(())
+    }
+
+    pub fn text(&self) -> String {
+        self.buffer.text()
+    }
+
+    /// Replaces the content of the buffer, the cursor stays
+    /// where it was as far as the new content allows.
+    pub fn replace_text(&mut self, text: &str) {
+        if self.buffer.is_read_only() {
+            return;
+        }
+
+        self.buffer.replace_text(text);
+        self.place_cursor(Some(CursorPlacement::At(self.text_location)));
     }
 
     pub fn save(&mut self) -> Result<(), std::io::Error> {
-        self.buffer.save()
+        self.buffer.save()?;
+        self.reset_diff();
+        Ok(())
+    }
+
+    pub fn save_forced(&mut self) -> Result<(), std::io::Error> {
+        self.buffer.save_forced()?;
+        self.reset_diff();
+        Ok(())
+    }
+
+    pub fn file_type(&self) -> FileType {
+        self.buffer.file_info.file_type()
+    }
+
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        self.buffer.set_encoding(encoding);
+    }
+
+    pub fn set_final_newline(&mut self, enabled: bool) {
+        self.buffer.set_final_newline(enabled);
+    }
+
+    /// Overrides the type detected from the file name, its settings
+    /// are taken and the highlighting changes on the next draw.
+    pub fn set_file_type(&mut self, file_type: FileType) {
+        self.buffer.file_info.set_file_type(file_type);
+        self.apply_file_type_config();
+        self.set_needs_redraw(true);
+    }
+
+    pub fn stats(&self) -> BufferStats {
+        self.buffer.stats()
     }
 
     pub fn is_file_modified(&self) -> bool {
         self.buffer.is_dirty()
     }
 
-    pub fn handle_enter(&mut self) {
-        self.buffer.insert_newline(self.text_location);
-        self.handle_movement(Direction::Down);
-        self.handle_movement(Direction::Home);
+    fn indent_line(&mut self) -> Option<CursorPlacement> {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        let inserted =
+            self.buffer
+                .indent_line(line_index, self.config.tab_width, self.config.expand_tab);
+
+        (inserted > 0).then_some(CursorPlacement::At(Location {
+            grapheme_index: grapheme_index.saturating_add(inserted),
+            line_index,
+        }))
+    }
+
+    fn dedent_line(&mut self) -> Option<CursorPlacement> {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        let removed = self.buffer.dedent_line(line_index, self.config.tab_width);
+
+        (removed > 0).then_some(CursorPlacement::At(Location {
+            grapheme_index: grapheme_index.saturating_sub(removed),
+            line_index,
+        }))
+    }
+
+    /// Starts selecting from the cursor, or changes the kind
+    /// of the selection keeping its anchor.
+    pub fn select(&mut self, kind: VisualKind) {
+        let anchor = self
+            .selection
+            .map_or(self.text_location, |selection| selection.anchor);
+        self.selection = Some(Selection { anchor, kind });
         self.set_needs_redraw(true);
     }
 
-    /// Handles the movement of view.
-    pub fn handle_movement(&mut self, mov: Direction) {
-        let height = self.size.height;
+    pub fn selection_kind(&self) -> Option<VisualKind> {
+        self.selection.map(|selection| selection.kind)
+    }
 
-        match mov {
-            Direction::Up => self.move_up_by(1),
-            Direction::Left => self.move_left(),
-            Direction::Right => self.move_right(),
-            Direction::Down => self.move_down_by(1),
-            Direction::End => self.move_end_of_line(),
-            Direction::Home => self.move_start_of_line(),
-            Direction::PageUp => self.move_up_by(height.saturating_sub(1)),
-            Direction::PageDown => self.move_down_by(height.saturating_sub(1)),
+    /// Remembers the selection as it is now, so that `reselect`
+    /// can bring it back after it ended.
+    pub fn remember_selection(&mut self) {
+        if let Some(selection) = self.selection {
+            self.marks.set_selection(selection, self.text_location);
         }
+    }
+
+    /// Selects again what was selected last, returns `false`
+    /// if nothing was selected yet.
+    pub fn reselect(&mut self) -> bool {
+        let Some((selection, cursor)) = self.marks.selection() else {
+            return false;
+        };
 
+        self.selection = Some(selection);
+        self.move_to(cursor);
         self.scroll_location();
+        self.set_needs_redraw(true);
+        true
     }
 
-    fn move_up_by(&mut self, count: usize) {
-        self.text_location.line_index = self.text_location.line_index.saturating_sub(count);
-        self.snap_to_grapheme();
+    /// Starts tracking the text typed from the cursor on.
+    pub fn mark_insert_start(&mut self) {
+        self.marks.start_insert(self.text_location);
+    }
+
+    /// Ends the insertion started by `mark_insert_start` at the cursor.
+    pub fn mark_insert_end(&mut self) {
+        self.marks.end_insert(self.text_location);
+    }
+
+    /// The cursors added next to the main one, none once the
+    /// buffer was edited without going through all of them.
+    fn extra_cursors(&self) -> &[Location] {
+        if self.cursors_revision == self.buffer.revision() {
+            &self.extra_cursors
+        } else {
+            &[]
+        }
+    }
+
+    pub fn has_extra_cursors(&self) -> bool {
+        !self.extra_cursors().is_empty()
+    }
+
+    pub fn clear_extra_cursors(&mut self) {
+        if !self.extra_cursors.is_empty() {
+            self.extra_cursors.clear();
+            self.set_needs_redraw(true);
+        }
+    }
+
+    /// Adds a cursor on the next occurrence of the word under the cursor,
+    /// at the same column inside it, and moves the main cursor there.
+    /// Returns `false` if there is no word or no other occurrence.
+    pub fn add_cursor_on_next_occurrence(&mut self) -> bool {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        let Some(line) = self.buffer.line(line_index) else {
+            return false;
+        };
+        let Some(range) = line.word_range_at(grapheme_index) else {
+            return false;
+        };
+        let word = line.get_str(range.clone()).to_string();
+        let offset = grapheme_index.saturating_sub(range.start);
+
+        let mut cursors = self.extra_cursors().to_vec();
+        cursors.push(self.text_location);
+        let options = MatchOptions {
+            ignore_case: false,
+            whole_word: true,
+        };
+        let word_start = Location {
+            grapheme_index: range.start,
+            line_index,
+        };
+        let mut from = Location {
+            grapheme_index: range.end,
+            line_index,
+        };
+        let cursor = loop {
+            let Some(found) = self.buffer.search_forward(&word, from, options) else {
+                return false;
+            };
+            if found == word_start {
+                return false;
+            }
+
+            let cursor = Location {
+                grapheme_index: found.grapheme_index.saturating_add(offset),
+                line_index: found.line_index,
+            };
+            if !cursors.contains(&cursor) {
+                break cursor;
+            }
+            from = Location {
+                grapheme_index: found.grapheme_index.saturating_add(range.len()),
+                line_index: found.line_index,
+            };
+        };
+
+        self.extra_cursors = cursors;
+        self.cursors_revision = self.buffer.revision();
+        self.place_cursor(Some(CursorPlacement::At(cursor)));
+        true
+    }
+
+    /// Runs the edit at every cursor starting from the last one in the
+    /// buffer, so that it doesn't move the cursors still to be edited.
+    /// The edit can't add or remove lines: the cursors already edited
+    /// on its line shift by the graphemes it added or removed.
+    fn edit_at_cursors(&mut self, edit: impl Fn(&mut Self)) {
+        let mut cursors: Vec<(Location, bool)> = self
+            .extra_cursors()
+            .iter()
+            .map(|&cursor| (cursor, false))
+            .collect();
+        cursors.push((self.text_location, true));
+        cursors.sort_by_key(|(cursor, _)| cmp::Reverse((cursor.line_index, cursor.grapheme_index)));
+
+        let line_len =
+            |view: &Self, index: usize| view.buffer.line(index).map_or(0, Line::grapheme_count);
+        let mut edited: Vec<(Location, bool)> = Vec::with_capacity(cursors.len());
+        for (cursor, is_main) in cursors {
+            let old_len = line_len(self, cursor.line_index);
+            self.text_location = cursor;
+            edit(self);
+            let new_len = line_len(self, cursor.line_index);
+
+            for (other, _) in &mut edited {
+                if other.line_index == cursor.line_index {
+                    other.grapheme_index = other
+                        .grapheme_index
+                        .saturating_add(new_len)
+                        .saturating_sub(old_len);
+                }
+            }
+            edited.push((self.text_location, is_main));
+        }
+
+        let main = edited
+            .iter()
+            .find(|(_, is_main)| *is_main)
+            .map_or(self.text_location, |(cursor, _)| *cursor);
+        self.extra_cursors.clear();
+        for (cursor, _) in edited {
+            if cursor != main && !self.extra_cursors.contains(&cursor) {
+                self.extra_cursors.push(cursor);
+            }
+        }
+        self.cursors_revision = self.buffer.revision();
+        self.place_cursor(Some(CursorPlacement::At(main)));
+    }
+
+    pub fn insert_at_cursors(&mut self, sy: char) {
+        self.edit_at_cursors(|view| view.handle_insertion(sy));
+    }
+
+    /// Deletes before every cursor, the ones at the start
+    /// of a line are left there.
+    pub fn backspace_at_cursors(&mut self) {
+        self.edit_at_cursors(|view| {
+            if view.text_location.grapheme_index > 0 {
+                view.handle_backspace();
+            }
+        });
+    }
+
+    /// Deletes under every cursor, the ones at the end
+    /// of a line are left there.
+    pub fn delete_at_cursors(&mut self) {
+        self.edit_at_cursors(|view| {
+            if view.text_location.grapheme_index < view.current_line_len() {
+                view.handle_deletion();
+            }
+        });
+    }
+
+    pub fn clear_selection(&mut self) {
+        if self.selection.take().is_some() {
+            self.set_needs_redraw(true);
+        }
+    }
+
+    /// Copies the selected text to the register and ends the selection.
+    pub fn yank_selection(&mut self) {
+        let Some(selection) = self.selection.take() else {
+            return;
+        };
+
+        let (start, end) = selection.span(self.text_location, &self.buffer);
+        let text = if selection.kind == VisualKind::Block {
+            let columns = selection.columns(self.text_location);
+            selection
+                .lines(self.text_location)
+                .filter_map(|row| self.buffer.line(row))
+                .map(|line| line.get_str(columns.clone()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        } else {
+            self.buffer.text_between(start, end)
+        };
+        self.register = Register {
+            text,
+            linewise: selection.kind == VisualKind::Line,
+        };
+
+        let placement = match selection.kind {
+            VisualKind::Char => CursorPlacement::At(start),
+            VisualKind::Block => CursorPlacement::At(Location {
+                grapheme_index: selection.columns(self.text_location).start,
+                line_index: start.line_index,
+            }),
+            VisualKind::Line => CursorPlacement::FirstNonBlank(start.line_index),
+        };
+        self.place_cursor(Some(placement));
+    }
+
+    /// Deletes the selected text keeping it in the register,
+    /// a line-wise selection removes the whole lines.
+    pub fn delete_selection(&mut self) {
+        if self.buffer.is_read_only() {
+            self.clear_selection();
+            return;
+        }
+
+        let Some(selection) = self.selection else {
+            return;
+        };
+
+        let (start, end) = selection.span(self.text_location, &self.buffer);
+        let lines = selection.lines(self.text_location);
+        let columns = selection.columns(self.text_location);
+        self.yank_selection();
+
+        let placement = match selection.kind {
+            VisualKind::Char => {
+                self.buffer.delete_between(start, end);
+                CursorPlacement::At(start)
+            }
+            VisualKind::Block => {
+                for row in lines.clone() {
+                    self.buffer.delete_range(row, columns.clone());
+                }
+                CursorPlacement::At(Location {
+                    grapheme_index: columns.start,
+                    line_index: lines.start,
+                })
+            }
+            VisualKind::Line => {
+                for _ in lines {
+                    self.buffer.delete_line(start.line_index);
+                }
+                CursorPlacement::FirstNonBlank(cmp::min(
+                    start.line_index,
+                    self.buffer.height().saturating_sub(1),
+                ))
+            }
+        };
+        self.place_cursor(Some(placement));
+    }
+
+    /// Indents or dedents all the selected lines and ends the selection.
+    pub fn shift_selection(&mut self, indent: bool) {
+        let Some(selection) = self.selection.take() else {
+            return;
+        };
+
+        if !self.buffer.is_read_only() {
+            let lines = selection.lines(self.text_location);
+            for line_index in lines.clone() {
+                if indent {
+                    self.buffer.indent_line(
+                        line_index,
+                        self.config.tab_width,
+                        self.config.expand_tab,
+                    );
+                } else {
+                    self.buffer.dedent_line(line_index, self.config.tab_width);
+                }
+            }
+            self.place_cursor(Some(CursorPlacement::FirstNonBlank(lines.start)));
+        }
+        self.set_needs_redraw(true);
+    }
+
+    /// Comments the selected lines out, or back in if all of
+    /// them are comments, with the line comment of the file type.
+    pub fn toggle_comment_selection(&mut self) -> bool {
+        let Some(selection) = self.selection.take() else {
+            return false;
+        };
+        self.set_needs_redraw(true);
+
+        let Some(prefix) = self.buffer.file_info.file_type().line_comment() else {
+            return false;
+        };
+
+        if !self.buffer.is_read_only() {
+            let lines = selection.lines(self.text_location);
+            self.buffer.toggle_comment(lines.clone(), prefix);
+            self.place_cursor(Some(CursorPlacement::FirstNonBlank(lines.start)));
+        }
+        true
+    }
+
+    /// Adds `step` times its ordinal to the first selected number of
+    /// every line, so the numbers become a sequence. Returns how many
+    /// numbers were changed.
+    pub fn increment_selection(&mut self, step: i64) -> usize {
+        let Some(selection) = self.selection.take() else {
+            return 0;
+        };
+        self.set_needs_redraw(true);
+        if self.buffer.is_read_only() {
+            return 0;
+        }
+
+        let span = selection.span(self.text_location, &self.buffer);
+        let mut ordinal: i64 = 0;
+        for line_index in selection.lines(self.text_location) {
+            let len = self.buffer.line(line_index).map_or(0, Line::grapheme_count);
+            let Some(range) = selection.row_range(self.text_location, span, line_index, len) else {
+                continue;
+            };
+
+            let delta = step.saturating_mul(ordinal.saturating_add(1));
+            let at = Location {
+                grapheme_index: range.start,
+                line_index,
+            };
+            if self.buffer.increment_number(at, delta).is_some() {
+                ordinal = ordinal.saturating_add(1);
+            }
+        }
+
+        self.place_cursor(Some(CursorPlacement::At(span.0)));
+        usize::try_from(ordinal).unwrap_or(0)
+    }
+
+    /// Surrounds the word under the cursor with the pair of `ch`,
+    /// returns `false` if there is no word there.
+    pub fn surround_word(&mut self, ch: char) -> bool {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        let Some(word) = self
+            .buffer
+            .line(line_index)
+            .and_then(|line| line.word_range_at(grapheme_index))
+        else {
+            return false;
+        };
+
+        if !self.buffer.is_read_only() {
+            let start = Location {
+                grapheme_index: word.start,
+                line_index,
+            };
+            let end = Location {
+                grapheme_index: word.end,
+                line_index,
+            };
+            self.surround(start, end, surround_pair(ch));
+            self.place_cursor(Some(CursorPlacement::At(start)));
+        }
+        true
+    }
+
+    /// Surrounds the selected text with the pair of `ch`, every row
+    /// of a block selection is surrounded on its own.
+    pub fn surround_selection(&mut self, ch: char) -> bool {
+        let Some(selection) = self.selection.take() else {
+            return false;
+        };
+        self.set_needs_redraw(true);
+        if self.buffer.is_read_only() {
+            return true;
+        }
+
+        let cursor = self.text_location;
+        let (start, end) = selection.span(cursor, &self.buffer);
+        let pair = surround_pair(ch);
+        if selection.kind == VisualKind::Block {
+            for row in selection.lines(cursor) {
+                let len = self.buffer.line(row).map_or(0, Line::grapheme_count);
+                if let Some(range) = selection.row_range(cursor, (start, end), row, len) {
+                    let at = |grapheme_index| Location {
+                        grapheme_index,
+                        line_index: row,
+                    };
+                    self.surround(at(range.start), at(range.end), pair);
+                }
+            }
+        } else {
+            // A selected line break is left outside of the pair.
+            let end = if end.grapheme_index == 0 && end.line_index > start.line_index {
+                let line_index = end.line_index.saturating_sub(1);
+                Location {
+                    grapheme_index: self.buffer.line(line_index).map_or(0, Line::grapheme_count),
+                    line_index,
+                }
+            } else {
+                end
+            };
+            self.surround(start, end, pair);
+        }
+
+        let start = if selection.kind == VisualKind::Block {
+            Location {
+                grapheme_index: selection.columns(cursor).start,
+                line_index: start.line_index,
+            }
+        } else {
+            start
+        };
+        self.place_cursor(Some(CursorPlacement::At(start)));
+        true
+    }
+
+    fn surround(&mut self, start: Location, end: Location, (open, close): (char, char)) {
+        self.buffer.insert_char(close, end);
+        self.buffer.insert_char(open, start);
+    }
+
+    /// Deletes the pair of `ch` around the cursor,
+    /// returns `false` if there is none.
+    pub fn delete_surround(&mut self, ch: char) -> bool {
+        let Some((start, end)) = self.find_surround(surround_pair(ch)) else {
+            return false;
+        };
+
+        if !self.buffer.is_read_only() {
+            self.buffer.delete(end);
+            self.buffer.delete(start);
+            self.place_cursor(Some(CursorPlacement::At(start)));
+        }
+        true
+    }
+
+    /// Replaces the pair of `old` around the cursor with the pair
+    /// of `new`, returns `false` if there is none.
+    pub fn change_surround(&mut self, old: char, new: char) -> bool {
+        let Some((start, end)) = self.find_surround(surround_pair(old)) else {
+            return false;
+        };
+
+        if !self.buffer.is_read_only() {
+            let (open, close) = surround_pair(new);
+            for (at, ch) in [(end, close), (start, open)] {
+                self.buffer.delete(at);
+                self.buffer.insert_char(ch, at);
+            }
+            self.place_cursor(Some(CursorPlacement::At(start)));
+        }
+        true
+    }
+
+    /// The locations of the opening and closing characters of the
+    /// innermost pair around the cursor, the cursor can be on either.
+    /// Quotes can't be nested so they are only looked for on the line.
+    fn find_surround(&self, (open, close): (char, char)) -> Option<(Location, Location)> {
+        let cursor = self.text_location;
+        if open == close {
+            return self.find_quotes(open);
+        }
+
+        let (open, close) = (open.to_string(), close.to_string());
+        let under = self
+            .buffer
+            .line(cursor.line_index)
+            .and_then(|line| line.grapheme_at(cursor.grapheme_index));
+        let start = if under == Some(open.as_str()) {
+            cursor
+        } else {
+            self.buffer.find_enclosing_open(&open, &close, cursor)?
+        };
+        let end = self.buffer.find_enclosing_close(&open, &close, start)?;
+        Some((start, end))
+    }
+
+    fn find_quotes(&self, quote: char) -> Option<(Location, Location)> {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        let line = self.buffer.line(line_index)?;
+        let quote = quote.to_string();
+        let quotes: Vec<GraphemeIndex> = (0..line.grapheme_count())
+            .filter(|&index| line.grapheme_at(index) == Some(quote.as_str()))
+            .collect();
+
+        // On a quote, the quotes before it tell whether it opens or closes.
+        let (start, end) = if let Some(position) = quotes.iter().position(|&q| q == grapheme_index)
+        {
+            if position.is_multiple_of(2) {
+                (grapheme_index, *quotes.get(position.saturating_add(1))?)
+            } else {
+                (quotes[position.saturating_sub(1)], grapheme_index)
+            }
+        } else {
+            (
+                *quotes.iter().rev().find(|&&q| q < grapheme_index)?,
+                *quotes.iter().find(|&&q| q > grapheme_index)?,
+            )
+        };
+
+        let at = |grapheme_index| Location {
+            grapheme_index,
+            line_index,
+        };
+        Some((at(start), at(end)))
+    }
+
+    /// Ends a block selection moving the cursor to its first line, at
+    /// the left column or after the right one when appending. Returns
+    /// `false` if there isn't a block selection to insert on.
+    pub fn start_block_insert(&mut self, append: bool) -> bool {
+        let Some(selection) = self.selection else {
+            return false;
+        };
+
+        if selection.kind != VisualKind::Block || self.buffer.is_read_only() {
+            return false;
+        }
+
+        self.selection = None;
+        let lines = selection.lines(self.text_location);
+        let columns = selection.columns(self.text_location);
+        let column = if append { columns.end } else { columns.start };
+
+        self.text_location = Location {
+            grapheme_index: column,
+            line_index: lines.start,
+        };
+        if append {
+            let len = self.current_line_len();
+            if len < column {
+                self.buffer.insert_text(
+                    Location {
+                        grapheme_index: len,
+                        line_index: lines.start,
+                    },
+                    &" ".repeat(column.saturating_sub(len)),
+                );
+            }
+        }
+
+        self.block_insert = Some(BlockInsert {
+            lines,
+            column,
+            pad: append,
+        });
+        self.place_cursor(Some(CursorPlacement::At(self.text_location)));
+        self.set_needs_redraw(true);
+        true
+    }
+
+    /// Copies the text typed on the first line of a block insertion
+    /// on the other lines, nothing is copied if the cursor left the line.
+    pub fn finish_block_insert(&mut self) {
+        let Some(block) = self.block_insert.take() else {
+            return;
+        };
+
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        if line_index != block.lines.start || grapheme_index <= block.column {
+            return;
+        }
+
+        let Some(text) = self
+            .buffer
+            .line(line_index)
+            .map(|line| line.get_str(block.column..grapheme_index).to_string())
+        else {
+            return;
+        };
+
+        for row in block.lines.start.saturating_add(1)..block.lines.end {
+            let Some(len) = self.buffer.line(row).map(Line::grapheme_count) else {
+                break;
+            };
+
+            if !block.pad && len <= block.column {
+                continue;
+            }
+
+            if len < block.column {
+                self.buffer.insert_text(
+                    Location {
+                        grapheme_index: len,
+                        line_index: row,
+                    },
+                    &" ".repeat(block.column.saturating_sub(len)),
+                );
+            }
+
+            self.buffer.insert_text(
+                Location {
+                    grapheme_index: block.column,
+                    line_index: row,
+                },
+                &text,
+            );
+        }
+
+        self.set_needs_redraw(true);
+    }
+
+    /// Pastes the register after the cursor, or below
+    /// the cursor line if it holds whole lines.
+    pub fn paste(&mut self) {
+        if self.buffer.is_read_only() || self.register.text.is_empty() {
+            return;
+        }
+
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+
+        let placement = if self.register.linewise {
+            let below = cmp::min(line_index.saturating_add(1), self.buffer.height());
+            self.buffer.insert_lines(below, &self.register.text);
+            CursorPlacement::FirstNonBlank(below)
+        } else {
+            let after = cmp::min(grapheme_index.saturating_add(1), self.current_line_len());
+            let mut end = self.buffer.insert_text(
+                Location {
+                    grapheme_index: after,
+                    line_index,
+                },
+                &self.register.text,
+            );
+            end.grapheme_index = end.grapheme_index.saturating_sub(1);
+            CursorPlacement::At(end)
+        };
+        self.place_cursor(Some(placement));
+    }
+
+    /// Duplicates the current line leaving the cursor on the
+    /// copy at the same column. On the line past the end of the
+    /// file the copy is the empty line appended in its place.
+    fn duplicate_line(&mut self) -> CursorPlacement {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        let copy = cmp::min(line_index.saturating_add(1), self.buffer.height());
+        self.buffer.duplicate_line(line_index);
+
+        CursorPlacement::At(Location {
+            grapheme_index,
+            line_index: copy,
+        })
+    }
+
+    /// Swaps the current line with the one above, the cursor
+    /// follows the moved line.
+    fn move_line_up(&mut self) -> Option<CursorPlacement> {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        let above = line_index.checked_sub(1)?;
+
+        self.buffer
+            .swap_lines(line_index, above)
+            .then_some(CursorPlacement::At(Location {
+                grapheme_index,
+                line_index: above,
+            }))
+    }
+
+    /// Swaps the current line with the one below, the cursor
+    /// follows the moved line.
+    fn move_line_down(&mut self) -> Option<CursorPlacement> {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        let below = line_index.saturating_add(1);
+
+        self.buffer
+            .swap_lines(line_index, below)
+            .then_some(CursorPlacement::At(Location {
+                grapheme_index,
+                line_index: below,
+            }))
+    }
+
+    /// Swaps the character under the cursor with the one before it
+    /// and advances the cursor. At the start of the line the first two
+    /// characters are swapped, at the end the last two.
+    fn transpose_chars(&mut self) -> Option<CursorPlacement> {
+        let len = self.current_line_len();
+        if len < 2 {
+            return None;
+        }
+
+        let line_index = self.text_location.line_index;
+        let grapheme_index = self
+            .text_location
+            .grapheme_index
+            .clamp(1, len.saturating_sub(1));
+        self.buffer.transpose(Location {
+            grapheme_index,
+            line_index,
+        });
+
+        Some(CursorPlacement::At(Location {
+            grapheme_index: grapheme_index.saturating_add(1),
+            line_index,
+        }))
+    }
+
+    /// Adds `delta` to the number under or after the cursor,
+    /// the cursor lands on its last character.
+    fn increment_number(&mut self, delta: i64) -> Option<CursorPlacement> {
+        let line_index = self.text_location.line_index;
+        let grapheme_index = self.buffer.increment_number(self.text_location, delta)?;
+
+        Some(CursorPlacement::At(Location {
+            grapheme_index,
+            line_index,
+        }))
+    }
+
+    /// Toggles the case of the character under the cursor and
+    /// moves to the next one, staying on the last one of the line.
+    fn toggle_case(&mut self) -> Option<CursorPlacement> {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+
+        let len = self.current_line_len();
+        if grapheme_index >= len {
+            return None;
+        }
+
+        self.buffer.change_case(
+            line_index,
+            grapheme_index..grapheme_index.saturating_add(1),
+            Case::Toggle,
+        );
+
+        Some(CursorPlacement::At(Location {
+            grapheme_index: cmp::min(grapheme_index.saturating_add(1), len.saturating_sub(1)),
+            line_index,
+        }))
+    }
+
+    /// Converts the case of the word under the cursor leaving
+    /// the cursor at its start.
+    fn change_word_case(&mut self, case: Case) -> Option<CursorPlacement> {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+
+        let range = self
+            .buffer
+            .line(line_index)
+            .and_then(|line| line.word_range_at(grapheme_index))?;
+
+        let start = range.start;
+        self.buffer.change_case(line_index, range, case);
+
+        Some(CursorPlacement::At(Location {
+            grapheme_index: start,
+            line_index,
+        }))
+    }
+
+    /// Joins the current line with the next one, the cursor
+    /// lands on the join point.
+    fn join_lines(&mut self, insert_space: bool) -> Option<CursorPlacement> {
+        let line_index = self.text_location.line_index;
+        let grapheme_index = self.buffer.join_lines(line_index, insert_space)?;
+
+        Some(CursorPlacement::At(Location {
+            grapheme_index,
+            line_index,
+        }))
+    }
+
+    /// Deletes the current line, the cursor lands on the first
+    /// non-blank character of the line taking its place.
+    fn delete_line(&mut self) -> Option<CursorPlacement> {
+        let line_index = self.text_location.line_index;
+        if !self.buffer.delete_line(line_index) {
+            return None;
+        }
+
+        let line_index = cmp::min(line_index, self.buffer.height().saturating_sub(1));
+        Some(CursorPlacement::FirstNonBlank(line_index))
+    }
+
+    /// Deletes from the cursor to the end of the line keeping the
+    /// text in the register, an empty line is left as it is.
+    fn delete_to_eol(&mut self) -> Option<CursorPlacement> {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        let line = self.buffer.line(line_index)?;
+        let len = line.grapheme_count();
+        if grapheme_index >= len {
+            return None;
+        }
+
+        self.register = Register {
+            text: line.get_str(grapheme_index..len).to_string(),
+            linewise: false,
+        };
+        self.buffer.delete_range(line_index, grapheme_index..len);
+        Some(CursorPlacement::At(self.text_location))
+    }
+
+    /// Deletes the character under the cursor keeping it in the register.
+    fn substitute_char(&mut self) -> Option<CursorPlacement> {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        let text = self.buffer.line(line_index)?.grapheme_at(grapheme_index)?;
+
+        self.register = Register {
+            text: text.to_string(),
+            linewise: false,
+        };
+        self.buffer
+            .delete_range(line_index, grapheme_index..grapheme_index.saturating_add(1));
+        Some(CursorPlacement::At(self.text_location))
+    }
+
+    /// Deletes the text of the line keeping its indentation,
+    /// the whole line goes in the register.
+    fn substitute_line(&mut self) -> Option<CursorPlacement> {
+        let line_index = self.text_location.line_index;
+        let text = self.buffer.line(line_index)?.get_string().to_string();
+
+        self.register = Register {
+            text,
+            linewise: true,
+        };
+        let grapheme_index = self.buffer.clear_line(line_index)?;
+        Some(CursorPlacement::At(Location {
+            grapheme_index,
+            line_index,
+        }))
+    }
+
+    /// Moves the cursor where the editing command declared, clamping
+    /// it inside the buffer, and refreshes the screen.
+    fn place_cursor(&mut self, placement: Option<CursorPlacement>) {
+        let Some(placement) = placement else {
+            return;
+        };
+
+        let location = match placement {
+            CursorPlacement::At(location) => location,
+            CursorPlacement::FirstNonBlank(line_index) => Location {
+                grapheme_index: self
+                    .buffer
+                    .line(line_index)
+                    .map_or(0, Line::first_non_blank),
+                line_index,
+            },
+        };
+
+        let line_index = cmp::min(location.line_index, self.buffer.height());
+        let line_len = self.buffer.line(line_index).map_or(0, Line::grapheme_count);
+        self.text_location = Location {
+            grapheme_index: cmp::min(location.grapheme_index, line_len),
+            line_index,
+        };
+
+        self.scroll_location();
+        self.set_needs_redraw(true);
+    }
+
+    /// Moves the cursor to the line and column, both counted from 1
+    /// and clamped to the buffer, or to the first non blank of the
+    /// line without a column. While the file is loading the jump
+    /// is done as soon as it is ready.
+    pub fn goto(&mut self, line_number: usize, column: Option<usize>) {
+        if self.is_loading() {
+            self.pending_goto = Some((line_number, column));
+            return;
+        }
+
+        let line_index = cmp::min(
+            line_number.saturating_sub(1),
+            self.buffer.height().saturating_sub(1),
+        );
+        self.text_location = Location {
+            grapheme_index: 0,
+            line_index,
+        };
+        self.center_screen();
+        self.text_location.grapheme_index = match column {
+            Some(column) => cmp::min(column.saturating_sub(1), self.current_line_len()),
+            None => self
+                .buffer
+                .line(line_index)
+                .map_or(0, Line::first_non_blank),
+        };
+        self.scroll_location();
+    }
+
+    /// Moves the cursor to the location, if any, centering
+    /// the screen on it.
+    fn jump_to(&mut self, location: Option<Location>) {
+        if let Some(location) = location {
+            self.text_location = location;
+            self.center_screen();
+        }
+    }
+
+    pub fn handle_enter(&mut self) {
+        if self.buffer.is_read_only() {
+            return;
+        }
+
+        self.buffer.insert_newline(self.text_location);
+        self.place_cursor(Some(CursorPlacement::At(Location {
+            grapheme_index: 0,
+            line_index: self.text_location.line_index.saturating_add(1),
+        })));
+    }
+
+    /// Handles the movement of view.
+    pub fn handle_movement(&mut self, mov: Direction) {
+        let height = self.size.height;
+
+        match mov {
+            Direction::Up => self.move_up_by(1),
+            Direction::Left => self.move_left(),
+            Direction::Right => self.move_right(),
+            Direction::Down => self.move_down_by(1),
+            Direction::End => self.move_end_of_line(),
+            Direction::Home => self.move_smart_start_of_line(),
+            Direction::LineStart => self.move_start_of_line(),
+            Direction::PageUp => self.move_up_by(height.saturating_sub(1)),
+            Direction::PageDown => self.move_down_by(height.saturating_sub(1)),
+            Direction::WordLeft => self.move_word_left(),
+            Direction::WordRight => self.move_word_right(),
+            Direction::InsertStart => {
+                if let Some((start, _)) = self.marks.insert() {
+                    self.move_to(start);
+                }
+            }
+            Direction::InsertEnd => {
+                if let Some((start, end)) = self.marks.insert() {
+                    self.move_to(Self::last_inserted(start, end));
+                }
+            }
+        }
+
+        if self.selection.is_some() {
+            self.set_needs_redraw(true);
+        }
+        self.scroll_location();
+    }
+
+    fn move_to(&mut self, location: Location) {
+        self.text_location = location;
+        self.snap_to_valid_line();
+        self.snap_to_grapheme();
+    }
+
+    /// The last grapheme typed in an insertion that ended at `end`,
+    /// or `start` if nothing was typed on its line.
+    fn last_inserted(start: Location, end: Location) -> Location {
+        if end.grapheme_index > 0
+            && (end.line_index != start.line_index || end.grapheme_index > start.grapheme_index)
+        {
+            Location {
+                grapheme_index: end.grapheme_index.saturating_sub(1),
+                line_index: end.line_index,
+            }
+        } else if end.line_index == start.line_index {
+            start
+        } else {
+            end
+        }
+    }
+
+    /// Moves by rows of the screen, so that a closed fold is skipped.
+    fn move_up_by(&mut self, count: usize) {
+        let row = self.folds.row_of(self.text_location.line_index);
+        self.text_location.line_index = self.folds.line_at(row.saturating_sub(count));
+        self.snap_to_grapheme();
+    }
+
+    fn move_down_by(&mut self, count: usize) {
+        let row = self.folds.row_of(self.text_location.line_index);
+        self.text_location.line_index = self.folds.line_at(row.saturating_add(count));
+        self.snap_to_grapheme();
+        self.snap_to_valid_line();
+    }
+
+    /// Enables moving to the right even when reached the end of the line
+    /// by moving down by 1.
+    fn move_right(&mut self) {
+        let line_num = self.buffer.height();
+        let line_width = self
+            .buffer
+            .line(self.text_location.line_index)
+            .map_or(0, Line::grapheme_count);
+
+        if self.text_location.grapheme_index < line_width {
+            self.text_location.grapheme_index = self.text_location.grapheme_index.saturating_add(1);
+        } else if self.text_location.line_index < line_num {
+            self.text_location.line_index = self.text_location.line_index.saturating_add(1);
+            self.move_start_of_line();
+        }
+    }
+
+    /// Enables moving to the left even when reached the start of the line
+    /// by moving up by 1.
+    fn move_left(&mut self) {
+        if self.text_location.grapheme_index > 0 {
+            self.text_location.grapheme_index = self.text_location.grapheme_index.saturating_sub(1);
+        } else if self.text_location.line_index > 0 {
+            self.text_location.line_index = self.text_location.line_index.saturating_sub(1);
+            self.move_end_of_line();
+        }
+    }
+
+    /// Moves to the start of the previous word, from the start
+    /// of a line to the end of the one above.
+    fn move_word_left(&mut self) {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+
+        if grapheme_index == 0 {
+            self.move_left();
+        } else {
+            self.text_location.grapheme_index = self
+                .buffer
+                .line(line_index)
+                .map_or(0, |line| line.previous_word_start(grapheme_index));
+        }
+    }
+
+    /// Moves to the start of the next word, from the end
+    /// of a line to the start of the one below.
+    fn move_word_right(&mut self) {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+
+        if grapheme_index >= self.current_line_len() {
+            self.move_right();
+        } else {
+            self.text_location.grapheme_index = self
+                .buffer
+                .line(line_index)
+                .map_or(0, |line| line.next_word_start(grapheme_index));
+        }
+    }
+
+    fn move_start_of_line(&mut self) {
+        self.text_location.grapheme_index = 0;
+    }
+
+    /// Moves to the first non-blank character of the line, or to
+    /// the start of the line if the cursor is already there, so that
+    /// pressing it repeatedly alternates between the two.
+    fn move_smart_start_of_line(&mut self) {
+        let first_non_blank = self
+            .buffer
+            .line(self.text_location.line_index)
+            .map_or(0, Line::first_non_blank);
+
+        self.text_location.grapheme_index = if self.text_location.grapheme_index == first_non_blank
+        {
+            0
+        } else {
+            first_non_blank
+        };
+    }
+
+    fn move_end_of_line(&mut self) {
+        self.text_location.grapheme_index = self
+            .buffer
+            .line(self.text_location.line_index)
+            .map_or(0, Line::grapheme_count);
+    }
+
+    /// Avoids the cursor going after the actual lenght of the line
+    /// counting the graphemes.
+    fn snap_to_grapheme(&mut self) {
+        self.text_location.grapheme_index =
+            self.buffer
+                .line(self.text_location.line_index)
+                .map_or(0, |line| {
+                    cmp::min(
+                        self.text_location.grapheme_index,
+                        line.grapheme_count().saturating_sub(1),
+                    )
+                });
+    }
+
+    /// Avoids the cursor going after the actual height of the
+    /// entire file.
+    fn snap_to_valid_line(&mut self) {
+        self.text_location.line_index =
+            cmp::min(self.text_location.line_index, self.buffer.height());
+    }
+
+    /// Enables scrolling by converting the Location
+    /// to the Position and moving towards it.
+    /// If the `scroll_offset` is changed we then need to
+    /// refresh the screen by setting `needs_redraw` to `true`.
+    fn scroll_location(&mut self) {
+        self.sync_folds();
+        let Position {
+            x: current_row,
+            y: current_line,
+        } = self.text_location_to_position();
+
+        self.scroll_orizontally(current_row);
+        self.scroll_vertically(current_line);
+    }
+
+    /// Sets the `scroll_offset` based on how much we are
+    /// far from the Position origin x coordinate.
+    fn scroll_orizontally(&mut self, to: usize) {
+        let width = self.text_width();
+
+        let offset_changed = if to < self.scroll_offset.x {
+            self.scroll_offset.x = to;
+            true
+        } else if to >= self.scroll_offset.x.saturating_add(width) {
+            self.scroll_offset.x = to.saturating_sub(width).saturating_add(1);
+            true
+        } else {
+            false
+        };
+
+        self.needs_redraw = self.needs_redraw || offset_changed;
+    }
+
+    /// Sets the `scroll_offset` based on how much we are
+    /// far from the Position origin y coordinate, keeping
+    /// `scroll_margin` rows around it unless the buffer ends.
+    fn scroll_vertically(&mut self, to: usize) {
+        let height = self.size.height;
+        #[allow(clippy::integer_division)]
+        let margin = cmp::min(self.config.scroll_margin, height.saturating_sub(1) / 2);
+        let current = self.scroll_target.unwrap_or(self.scroll_offset.y);
+
+        let target = if to < current.saturating_add(margin) {
+            to.saturating_sub(margin)
+        } else if to.saturating_add(margin) >= current.saturating_add(height) {
+            let last_row = self.folds.row_of(self.buffer.height().saturating_sub(1));
+            let bottom = cmp::max(
+                last_row.saturating_add(1).saturating_sub(height),
+                to.saturating_add(1).saturating_sub(height),
+            );
+            cmp::max(
+                cmp::min(
+                    to.saturating_add(margin)
+                        .saturating_sub(height)
+                        .saturating_add(1),
+                    bottom,
+                ),
+                current,
+            )
+        } else {
+            current
+        };
+
+        if target == current {
+            return;
+        }
+
+        if self.config.smooth_scroll && target.abs_diff(self.scroll_offset.y) > 1 {
+            self.scroll_target = Some(target);
+        } else {
+            self.scroll_offset.y = target;
+            self.scroll_target = None;
+            self.needs_redraw = true;
+        }
+    }
+
+    /// Whether `scroll_offset` is still moving towards its target.
+    pub fn is_scrolling(&self) -> bool {
+        self.scroll_target.is_some()
+    }
+
+    /// Moves `scroll_offset` a frame closer to its target, the
+    /// steps get smaller as it gets closer.
+    pub fn scroll_step(&mut self) {
+        let Some(target) = self.scroll_target else {
+            return;
+        };
+
+        let step = target
+            .abs_diff(self.scroll_offset.y)
+            .div_ceil(SMOOTH_SCROLL_FRAMES);
+        self.scroll_offset.y = if target > self.scroll_offset.y {
+            self.scroll_offset.y.saturating_add(step)
+        } else {
+            self.scroll_offset.y.saturating_sub(step)
+        };
+        if self.scroll_offset.y == target {
+            self.scroll_target = None;
+        }
+        self.set_needs_redraw(true);
+    }
+
+    /// Jumps to the end of the smooth scrolling at once.
+    pub fn finish_scrolling(&mut self) {
+        if let Some(target) = self.scroll_target.take() {
+            self.scroll_offset.y = target;
+            self.set_needs_redraw(true);
+        }
+    }
+
+    /// Renders a single line on a specific row, in debug if something
+    /// goes wrong we report it by panicking.
+    fn render_line(
+        terminal: &mut Terminal,
+        row_num: usize,
+        line: &str,
+    ) -> Result<(), std::io::Error> {
+        terminal.print_row(row_num, line)
+    }
+
+    fn render_annotated_line(
+        terminal: &mut Terminal,
+        row_num: usize,
+        line: &AnnotatedLine,
+        theme: &Theme,
+    ) -> Result<(), std::io::Error> {
+        terminal.print_annotated_row(row_num, line, theme)
+    }
+
+    /// Converts the current Location to the correspective Position
+    /// on the infinite grid, the lines hidden by folds take no rows.
+    fn text_location_to_position(&self) -> Position {
+        let line_index = self.text_location.line_index;
+        let x = self.buffer.line(line_index).map_or(0, |line| {
+            line.width_until(self.text_location.grapheme_index)
+        });
+        Position {
+            x,
+            y: self.folds.row_of(line_index),
+        }
+    }
+
+    /// Moves the folds and the marks along the lines inserted or deleted
+    /// by the last edit and opens the folds hiding the cursor.
+    fn sync_folds(&mut self) {
+        let line_index = self.text_location.line_index;
+        let edits = self.buffer.take_line_edits();
+        self.folds.sync(&edits, self.buffer.height());
+        self.marks.sync(&edits, self.buffer.height());
+        if self.folds.reveal(line_index) {
+            self.set_needs_redraw(true);
+        }
+    }
+
+    /// Folds the selected lines and ends the selection, returns
+    /// `false` if they are less than two.
+    pub fn fold_selection(&mut self) -> bool {
+        let Some(selection) = self.selection.take() else {
+            return false;
+        };
+        self.set_needs_redraw(true);
+        self.sync_folds();
+
+        let lines = selection.lines(self.text_location);
+        let start = lines.start;
+        if !self.folds.create(lines) {
+            return false;
+        }
+        self.place_cursor(Some(CursorPlacement::FirstNonBlank(start)));
+        true
+    }
+
+    /// Opens the closed fold under the cursor, or closes the innermost
+    /// one containing it moving the cursor on its first line. Returns
+    /// `false` if there isn't any.
+    pub fn toggle_fold(&mut self) -> bool {
+        self.sync_folds();
+        let Some(start) = self.folds.toggle(self.text_location.line_index) else {
+            return false;
+        };
+        self.text_location.line_index = start;
+        self.snap_to_grapheme();
+        self.scroll_location();
+        self.set_needs_redraw(true);
+        true
+    }
+
+    /// The lines from the first on the screen to the last one, the
+    /// lines hidden by folds between them included.
+    fn visible_lines(&self) -> Range<usize> {
+        let scroll_top = self.scroll_offset.y;
+        let end = cmp::min(
+            self.folds
+                .line_at(scroll_top.saturating_add(self.size.height)),
+            self.buffer.height(),
+        );
+        cmp::min(self.folds.line_at(scroll_top), end)..end
+    }
+
+    /// Highlights the rows on the screen, with the selection and the
+    /// cursors added next to the main one on top. The rows above the
+    /// screen and the ones hidden by a fold are only looked back at.
+    fn highlight_rows(
+        highlighter: &mut Highlighter,
+        buffer: &Buffer,
+        folds: &Folds,
+        rows: Range<usize>,
+        selection: Option<(Selection, (Location, Location))>,
+        cursor: Location,
+        extra_cursors: &[Location],
+    ) {
+        for row in rows {
+            if let Some(line) = buffer.line(row) {
+                if row < highlighter.first_row() || folds.line_at(folds.row_of(row)) != row {
+                    highlighter.look_back(row, line);
+                    continue;
+                }
+                highlighter.highlight(row, line);
+                if let Some(range) = selection.and_then(|(selection, span)| {
+                    selection.row_range(cursor, span, row, line.grapheme_count())
+                }) {
+                    highlighter.select(row, line.byte_range(range));
+                }
+                highlighter.mark_cursors(row, line, extra_cursors);
+            }
+        }
+    }
+
+    /// Adds what is drawn around the text of a row, the summary
+    /// of a closed fold, the gutter and the cursor crosshair.
+    fn decorate_row(
+        folds: &Folds,
+        gutter: Gutter,
+        annotated: &mut AnnotatedLine,
+        line: &Line,
+        line_index: usize,
+        room: usize,
+        crosshair: Crosshair,
+    ) {
+        let mut room = room;
+        if let Some(lines) = folds.closed_at(line_index) {
+            let summary = Self::append_fold_summary(annotated, line, lines.len(), room);
+            room = room.saturating_sub(summary);
+        }
+
+        if crosshair.line == Some(line_index) {
+            Self::mark_cursor_line(annotated, room);
+        }
+
+        if let Some(diff) = gutter.diff {
+            let change = diff.change(line_index);
+            annotated.prepend_str(
+                &format!("{} ", change.map_or(' ', LineChange::marker)),
+                change.map_or(AnnotationType::None, LineChange::annotation_type),
+            );
+        }
+        if let Some(buffer) = gutter.touched {
+            if buffer.is_touched(line_index) {
+                annotated.prepend_str(TOUCHED_MARKER, AnnotationType::Touched);
+            } else {
+                annotated.prepend_str(" ", AnnotationType::None);
+            }
+        }
+
+        if let Some(column) = crosshair.column {
+            Self::mark_cursor_column(annotated, column);
+        }
+    }
+
+    /// Appends blank cells marked with the annotation, to show
+    /// what goes on past the end of the text of the row.
+    fn pad_row(annotated: &mut AnnotatedLine, cells: usize, ty: AnnotationType) {
+        let end = annotated.get_line().len();
+        annotated.append_str(&" ".repeat(cells));
+        annotated.push_annotation(end..end.saturating_add(cells), ty);
+    }
+
+    /// Shows how many lines a closed fold hides after its first one, a
+    /// brace opening the fold is closed in the summary. Returns the
+    /// columns it takes.
+    fn append_fold_summary(
+        annotated: &mut AnnotatedLine,
+        line: &Line,
+        len: usize,
+        room: usize,
+    ) -> usize {
+        let closing = if line.get_string().trim_end().ends_with('{') {
+            "} "
+        } else {
+            ""
+        };
+        let summary: String = format!(" … {closing}{len} lines")
+            .chars()
+            .take(room)
+            .collect();
+
+        let end = annotated.get_line().len();
+        annotated.append_str(&summary);
+        annotated.push_annotation(end..end.saturating_add(summary.len()), AnnotationType::Fold);
+        summary.width()
+    }
+
+    /// The row of an empty file that isn't filled, along with its text:
+    /// the title, or the first line left blank without `show_welcome`.
+    fn empty_file_row(&self, pos_y: usize, width: usize) -> Option<(usize, String)> {
+        if !self.buffer.is_empty() {
+            return None;
+        }
+        if !self.config.show_welcome {
+            return Some((pos_y, String::new()));
+        }
+
+        #[allow(clippy::integer_division)]
+        let vertical_center: usize = self.size.height / 3;
+        Some((
+            vertical_center,
+            Self::build_title(width, &self.config.eof_fill),
+        ))
+    }
+
+    /// Draws a row after the end of the file with the `fill`
+    /// character, unless it's the row of the empty file.
+    fn render_past_end(
+        terminal: &mut Terminal,
+        row: usize,
+        empty_file_row: Option<&(usize, String)>,
+        fill: &str,
+    ) -> Result<(), std::io::Error> {
+        let text = match empty_file_row {
+            Some((empty_row, text)) if *empty_row == row => text.as_str(),
+            _ => fill,
+        };
+        Self::render_line(terminal, row, text)
+    }
+
+    /// Draws the title screen.
+    fn build_title(width: usize, fill: &str) -> String {
+        if width == 0 {
+            return String::new();
+        }
+
+        let msg = format!("{EDITOR_NAME}::{EDITOR_VERSION}");
+        let len = msg.len();
+        let room = width.saturating_sub(fill.width());
+
+        // If the title doesn't fit we simply hide the title screen
+        if room < len {
+            return fill.to_string();
+        }
+
+        format!("{fill}{msg:^room$}")
+    }
+
+    /// The word under the cursor, whose occurrences are
+    /// highlighted when the config asks for it.
+    fn word_under_cursor(&self) -> Option<&str> {
+        if !self.config.word_highlight {
+            return None;
+        }
+
+        self.word_at_cursor()
+    }
+
+    fn word_at_cursor(&self) -> Option<&str> {
+        let line = self.buffer.line(self.text_location.line_index)?;
+        let range = line.word_range_at(self.text_location.grapheme_index)?;
+        Some(line.get_str(range))
+    }
+
+    pub fn set_word_highlight(&mut self, enabled: bool) {
+        self.config.word_highlight = enabled;
+        self.set_needs_redraw(true);
+    }
+
+    /// The line and the screen column of the cursor that the config asks
+    /// to highlight, `focus_mode` highlights both.
+    fn crosshair(&self) -> Crosshair {
+        let focus = self.config.focus_mode;
+        Crosshair {
+            line: focus.then_some(self.text_location.line_index),
+            column: (self.config.cursor_column || focus).then(|| self.cursor_position().x),
+        }
+    }
+
+    pub fn set_cursor_column(&mut self, enabled: bool) {
+        self.config.cursor_column = enabled;
+        self.set_needs_redraw(true);
+    }
+
+    pub fn set_focus_mode(&mut self, enabled: bool) {
+        self.config.focus_mode = enabled;
+        self.set_needs_redraw(true);
+    }
+
+    /// Pads the row up to the edge of the screen and puts the cursor
+    /// line below it, so that the matches are printed over it.
+    fn mark_cursor_line(annotated: &mut AnnotatedLine, room: usize) {
+        annotated.append_str(&" ".repeat(room));
+        let end = annotated.get_line().len();
+        annotated.push_annotation_below(0..end, AnnotationType::CursorLine);
+    }
+
+    /// Puts the cursor column below the cell of the row on the screen
+    /// column, so that the matches and the selection are printed over
+    /// it. A shorter row is padded with spaces to reach the column.
+    fn mark_cursor_column(annotated: &mut AnnotatedLine, column: usize) {
+        let mut width = 0_usize;
+        let mut cell = None;
+        for (start, grapheme) in annotated.get_line().grapheme_indices(true) {
+            let next = width.saturating_add(grapheme.width());
+            if column < next {
+                cell = Some(start..start.saturating_add(grapheme.len()));
+                break;
+            }
+            width = next;
+        }
+
+        let cell = cell.unwrap_or_else(|| {
+            let end = annotated
+                .get_line()
+                .len()
+                .saturating_add(column.saturating_sub(width));
+            annotated.append_str(&" ".repeat(column.saturating_sub(width).saturating_add(1)));
+            end..end.saturating_add(1)
+        });
+        annotated.push_annotation_below(cell, AnnotationType::CursorColumn);
+    }
+
+    /// With smartcase the search ignores the case of letters
+    /// unless the term contains an uppercase one.
+    fn match_options(&self) -> MatchOptions {
+        MatchOptions {
+            ignore_case: self.ignores_case(&self.search_term),
+            whole_word: self.whole_word_search,
+        }
     }
 
-    fn move_down_by(&mut self, count: usize) {
-        self.text_location.line_index = self.text_location.line_index.saturating_add(count);
-        self.snap_to_grapheme();
-        self.snap_to_valid_line();
+    fn ignores_case(&self, term: &str) -> bool {
+        self.config.smartcase && !term.chars().any(char::is_uppercase)
     }
 
-    /// Enables moving to the right even when reached the end of the line
-    /// by moving down by 1.
-    fn move_right(&mut self) {
-        let line_num = self.buffer.lines.len();
-        let line_width = self
-            .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, Line::grapheme_count);
-
-        if self.text_location.grapheme_index < line_width {
-            self.text_location.grapheme_index = self.text_location.grapheme_index.saturating_add(1);
-        } else if self.text_location.line_index < line_num {
-            self.move_down_by(1);
-            self.move_start_of_line();
+    pub fn clear_search_term(&mut self) {
+        self.search_scope = None;
+        if !self.search_term.is_empty() {
+            self.search_term.clear();
+            self.needs_redraw = true;
         }
     }
 
-    /// Enables moving to the left even when reached the start of the line
-    /// by moving up by 1.
-    fn move_left(&mut self) {
-        if self.text_location.grapheme_index > 0 {
-            self.text_location.grapheme_index = self.text_location.grapheme_index.saturating_sub(1);
-        } else if self.text_location.line_index > 0 {
-            self.move_up_by(1);
-            self.move_end_of_line();
+    /// Ends the selection keeping the text it covered as the
+    /// only place searched until the scope is cleared.
+    pub fn scope_search_to_selection(&mut self) {
+        if let Some(selection) = self.selection.take() {
+            self.search_scope = Some(selection.span(self.text_location, &self.buffer));
+            self.match_preview = None;
+            self.set_needs_redraw(true);
         }
     }
 
-    fn move_start_of_line(&mut self) {
-        self.text_location.grapheme_index = 0;
+    pub fn clear_search_scope(&mut self) {
+        if self.search_scope.take().is_some() {
+            self.match_count = None;
+            self.set_needs_redraw(true);
+        }
     }
 
-    fn move_end_of_line(&mut self) {
-        self.text_location.grapheme_index = self
+    /// Replaces the matches of the text inside the search scope,
+    /// which is cleared. Returns how many were replaced.
+    pub fn replace_in_scope(&mut self, needle: &str, replacement: &str) -> usize {
+        let Some((start, end)) = self.search_scope.take() else {
+            return 0;
+        };
+
+        let options = MatchOptions {
+            ignore_case: self.ignores_case(needle),
+            whole_word: false,
+        };
+        let replaced = self
             .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, Line::grapheme_count);
+            .replace_between(needle, replacement, start, end, options);
+        self.place_cursor(Some(CursorPlacement::At(start)));
+        replaced
     }
 
-    /// Avoids the cursor going after the actual lenght of the line
-    /// counting the graphemes.
-    fn snap_to_grapheme(&mut self) {
-        self.text_location.grapheme_index = self
+    /// Aligns the lines of the search scope on the delimiter,
+    /// the scope is cleared. Returns how many lines were padded.
+    pub fn align_in_scope(&mut self, delimiter: &str) -> usize {
+        let Some((start, end)) = self.search_scope.take() else {
+            return 0;
+        };
+
+        let last_line = if end.grapheme_index == 0 && end.line_index > start.line_index {
+            end.line_index
+        } else {
+            end.line_index.saturating_add(1)
+        };
+        let padded = self
             .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, |line| {
-                cmp::min(
-                    self.text_location.grapheme_index,
-                    line.grapheme_count().saturating_sub(1),
-                )
-            });
+            .align_lines(start.line_index..last_line, delimiter);
+        self.place_cursor(Some(CursorPlacement::At(start)));
+        self.set_needs_redraw(true);
+        padded
     }
 
-    /// Avoids the cursor going after the actual height of the
-    /// entire file.
-    fn snap_to_valid_line(&mut self) {
-        self.text_location.line_index =
-            cmp::min(self.text_location.line_index, self.buffer.lines.len());
+    /// Stops highlighting the matches keeping the search
+    /// term, so that `n` and `N` still find them.
+    pub fn hide_matches(&mut self) {
+        self.matches_hidden = true;
+        self.set_needs_redraw(true);
     }
 
-    /// Enables scrolling by converting the Location
-    /// to the Position and moving towards it.
-    /// If the `scroll_offset` is changed we then need to
-    /// refresh the screen by setting `needs_redraw` to `true`.
-    fn scroll_location(&mut self) {
-        let Position {
-            x: current_row,
-            y: current_line,
-        } = self.text_location_to_position();
+    fn show_matches(&mut self) {
+        if self.matches_hidden {
+            self.matches_hidden = false;
+            self.set_needs_redraw(true);
+        }
+    }
 
-        self.scroll_orizontally(current_row);
-        self.scroll_vertically(current_line);
+    pub fn set_highlight_search(&mut self, enabled: bool) {
+        self.config.highlight_search = enabled;
+        self.set_needs_redraw(true);
     }
 
-    /// Sets the `scroll_offset` based on how much we are
-    /// far from the Position origin x coordinate.
-    fn scroll_orizontally(&mut self, to: usize) {
-        let width = self.size.width;
+    pub fn search_term(&self) -> &str {
+        &self.search_term
+    }
 
-        let offset_changed = if to < self.scroll_offset.x {
-            self.scroll_offset.x = to;
-            true
-        } else if to >= self.scroll_offset.x.saturating_add(width) {
-            self.scroll_offset.x = to.saturating_sub(width).saturating_add(1);
-            true
+    /// Sets the term to search, an empty one falls back
+    /// to the last term that was searched.
+    pub fn set_search_term(&mut self, term: String) {
+        if term.is_empty() {
+            self.search_term.clone_from(&self.last_search_term);
         } else {
-            false
+            self.last_search_term.clone_from(&term);
+            self.search_term = term;
+        }
+        self.whole_word_search = false;
+        self.match_count = None;
+    }
+
+    /// Searches the word under the cursor as a whole word, the
+    /// match under the cursor is skipped. Returns `false` if
+    /// the cursor isn't on a word.
+    pub fn search_word_under_cursor(&mut self, direction: SearchDirection) -> bool {
+        let Some(word) = self.word_at_cursor().map(ToOwned::to_owned) else {
+            return false;
         };
 
-        self.needs_redraw = self.needs_redraw || offset_changed;
+        let line_index = self.text_location.line_index;
+        if let Some(range) = self
+            .buffer
+            .line(line_index)
+            .and_then(|line| line.word_range_at(self.text_location.grapheme_index))
+        {
+            self.text_location.grapheme_index = range.start;
+        }
+
+        self.search_scope = None;
+        self.last_search_term.clone_from(&word);
+        self.search_term = word;
+        self.whole_word_search = true;
+        self.match_count = None;
+        self.last_search_direction = direction;
+        match direction {
+            SearchDirection::Forward => self.step_search_forward(),
+            SearchDirection::Backward => self.step_search_backward(),
+        };
+        self.set_needs_redraw(true);
+        true
     }
 
-    /// Sets the `scroll_offset` based on how much we are
-    /// far from the Position origin y coordinate.
-    fn scroll_vertically(&mut self, to: usize) {
-        let height = self.size.height;
+    /// Counts the matches of a term before it's searched, only inside
+    /// the search scope when `scoped`. The count is reused until the
+    /// term or the buffer change, large files aren't counted.
+    pub fn preview_match_count(&mut self, term: &str, scoped: bool) -> Option<usize> {
+        if term.is_empty() || self.buffer.is_read_only() {
+            return None;
+        }
 
-        let offset_changed = if to < self.scroll_offset.y {
-            self.scroll_offset.y = to;
-            true
-        } else if to >= self.scroll_offset.y.saturating_add(height) {
-            self.scroll_offset.y = to.saturating_sub(height).saturating_add(1);
-            true
-        } else {
-            false
+        let revision = self.buffer.revision();
+        if let Some(preview) = &self.match_preview
+            && preview.term == term
+            && preview.scoped == scoped
+            && preview.revision == revision
+        {
+            return Some(preview.count);
+        }
+
+        let options = MatchOptions {
+            ignore_case: self.ignores_case(term),
+            whole_word: false,
+        };
+        let count = match self.search_scope {
+            Some((start, end)) if scoped => {
+                self.buffer.matches_between(term, start, end, options).len()
+            }
+            _ => self.buffer.count_matches(term, options),
         };
 
-        self.needs_redraw = self.needs_redraw || offset_changed;
+        self.match_preview = Some(MatchPreview {
+            term: term.to_string(),
+            scoped,
+            revision,
+            count,
+        });
+        Some(count)
     }
 
-    /// Renders a single line on a specific row, in debug if something
-    /// goes wrong we report it by panicking.
-    fn render_line(row_num: usize, line: &str) -> Result<(), std::io::Error> {
-        Terminal::print_row(row_num, line)
+    /// Returns which match the cursor is on and how many matches
+    /// of the search term there are.
+    pub fn match_count(&self) -> Option<(usize, usize)> {
+        self.match_count
+            .map(|matches| (matches.ordinal, matches.count))
     }
 
-    fn render_annotated_line(row_num: usize, line: &AnnotatedLine) -> Result<(), std::io::Error> {
-        Terminal::print_annotated_row(row_num, line)
-    }
+    /// The matches of large files aren't counted,
+    /// it would mean reading the whole file.
+    fn recount_matches(&mut self) {
+        if let Some((start, end)) = self.search_scope {
+            let matches =
+                self.buffer
+                    .matches_between(&self.search_term, start, end, self.match_options());
+            let ordinal = matches
+                .iter()
+                .position(|location| {
+                    (location.line_index, location.grapheme_index)
+                        == (
+                            self.text_location.line_index,
+                            self.text_location.grapheme_index,
+                        )
+                })
+                .map_or(0, |index| index.saturating_add(1));
+            self.match_count = Some(MatchCount {
+                revision: self.buffer.revision(),
+                ordinal,
+                count: matches.len(),
+            });
+            return;
+        }
 
-    /// Converts the current Location to the correspective Position
-    /// on the infinite grid.
-    fn text_location_to_position(&self) -> Position {
-        let y = self.text_location.line_index;
-        let x = self.buffer.lines.get(y).map_or(0, |line| {
-            line.width_until(self.text_location.grapheme_index)
+        if self.buffer.is_read_only() {
+            self.match_count = None;
+            return;
+        }
+
+        let count = self
+            .buffer
+            .count_matches(&self.search_term, self.match_options());
+        let ordinal = if count == 0 {
+            0
+        } else {
+            self.buffer
+                .count_matches_before(&self.search_term, self.text_location, self.match_options())
+                .saturating_add(1)
+        };
+
+        self.match_count = Some(MatchCount {
+            revision: self.buffer.revision(),
+            ordinal,
+            count,
         });
-        Position { x, y }
     }
 
-    /// Draws the title screen.
-    fn build_title(width: usize) -> String {
-        if width == 0 {
-            return String::new();
+    /// Moves the cached ordinal to the next or previous match, the
+    /// matches are counted again if the buffer changed.
+    fn step_match_count(&mut self, forward: bool) {
+        let Some(matches) = self
+            .match_count
+            .as_mut()
+            .filter(|matches| matches.revision == self.buffer.revision())
+        else {
+            self.recount_matches();
+            return;
+        };
+
+        matches.ordinal = if forward {
+            if matches.ordinal >= matches.count {
+                1
+            } else {
+                matches.ordinal.saturating_add(1)
+            }
+        } else if matches.ordinal <= 1 {
+            matches.count
+        } else {
+            matches.ordinal.saturating_sub(1)
+        };
+    }
+
+    /// Jumps to the first match in the direction, which is
+    /// remembered for repeating the search. Returns `false`
+    /// if nothing matched.
+    pub fn search(&mut self, direction: SearchDirection) -> bool {
+        if self.search_term.is_empty() {
+            return false;
         }
 
-        let msg = format!("{EDITOR_NAME}::{EDITOR_VERSION}");
-        let len = msg.len();
-        let width_sub1 = width.saturating_sub(1);
+        self.show_matches();
+        self.last_search_direction = direction;
+        if self.search_scope.is_some() {
+            let found = self.step_search_in_scope(direction, true);
+            self.recount_matches();
+            return found;
+        }
 
-        // If the title doesn't fit we simply hide the title screen
-        if width_sub1 < len {
-            return String::from("~");
+        let location = match direction {
+            SearchDirection::Forward => self.buffer.search_forward(
+                &self.search_term,
+                self.text_location,
+                self.match_options(),
+            ),
+            SearchDirection::Backward => self.buffer.search_backwards(
+                &self.search_term,
+                self.text_location,
+                self.match_options(),
+            ),
+        };
+
+        if let Some(location) = location {
+            self.text_location = location;
+            self.scroll_vertically(self.text_location.line_index);
+            self.center_screen();
         }
 
-        format!("{:<}{:^width_sub1$}", "~", msg)
+        self.recount_matches();
+        location.is_some()
     }
 
-    pub fn clear_search_term(&mut self) {
-        if !self.search_term.is_empty() {
-            self.search_term.clear();
-            self.needs_redraw = true;
+    /// Repeats the last search in the same direction,
+    /// returns `false` if nothing matched.
+    pub fn search_next(&mut self) -> bool {
+        self.show_matches();
+        match self.last_search_direction {
+            SearchDirection::Forward => self.step_search_forward(),
+            SearchDirection::Backward => self.step_search_backward(),
         }
     }
 
-    pub fn set_search_term(&mut self, term: String) {
-        self.search_term = term;
+    /// Repeats the last search in the opposite direction,
+    /// returns `false` if nothing matched.
+    pub fn search_prev(&mut self) -> bool {
+        self.show_matches();
+        match self.last_search_direction {
+            SearchDirection::Forward => self.step_search_backward(),
+            SearchDirection::Backward => self.step_search_forward(),
+        }
     }
 
-    pub fn search(&mut self) {
-        if self.search_term.is_empty() {
-            return;
-        }
+    /// Moves to the next match inside the search scope in the direction,
+    /// wrapping around at its ends. The match under the cursor is
+    /// only accepted when starting a search.
+    fn step_search_in_scope(&mut self, direction: SearchDirection, include_cursor: bool) -> bool {
+        let Some((start, end)) = self.search_scope else {
+            return false;
+        };
 
-        if let Some(location) = self
-            .buffer
-            .search_forward(&self.search_term, self.text_location)
-        {
+        let cursor = (
+            self.text_location.line_index,
+            self.text_location.grapheme_index,
+        );
+        let key = |location: &Location| (location.line_index, location.grapheme_index);
+        let matches =
+            self.buffer
+                .matches_between(&self.search_term, start, end, self.match_options());
+        let found = match direction {
+            SearchDirection::Forward => matches
+                .iter()
+                .find(|location| {
+                    key(location) > cursor || (include_cursor && key(location) == cursor)
+                })
+                .or_else(|| matches.first()),
+            SearchDirection::Backward => matches
+                .iter()
+                .rev()
+                .find(|location| key(location) < cursor)
+                .or_else(|| matches.last()),
+        };
+
+        if let Some(&location) = found {
             self.text_location = location;
             self.scroll_vertically(self.text_location.line_index);
             self.center_screen();
         }
+        found.is_some()
     }
 
-    pub fn search_next(&mut self) {
+    fn step_search_forward(&mut self) -> bool {
         if self.search_term.is_empty() {
-            return;
+            return false;
+        }
+        if self.search_scope.is_some() {
+            let found = self.step_search_in_scope(SearchDirection::Forward, false);
+            self.recount_matches();
+            return found;
         }
         self.move_right();
 
-        if let Some(location) = self
-            .buffer
-            .search_forward(&self.search_term, self.text_location)
+        if let Some(location) =
+            self.buffer
+                .search_forward(&self.search_term, self.text_location, self.match_options())
         {
             self.text_location = location;
             self.scroll_vertically(self.text_location.line_index);
             self.center_screen();
+            self.step_match_count(true);
+            true
         } else {
             self.move_left();
+            false
         }
     }
 
-    pub fn search_prev(&mut self) {
+    fn step_search_backward(&mut self) -> bool {
         if self.search_term.is_empty() {
-            return;
+            return false;
+        }
+        if self.search_scope.is_some() {
+            let found = self.step_search_in_scope(SearchDirection::Backward, false);
+            self.recount_matches();
+            return found;
         }
         self.move_left();
 
-        if let Some(location) = self
-            .buffer
-            .search_backwards(&self.search_term, self.text_location)
-        {
+        if let Some(location) = self.buffer.search_backwards(
+            &self.search_term,
+            self.text_location,
+            self.match_options(),
+        ) {
             self.text_location = location;
             self.scroll_vertically(self.text_location.line_index);
             self.center_screen();
+            self.step_match_count(false);
+            true
         } else {
             self.move_right();
+            false
         }
     }
 
     pub fn get_status(&self) -> DocumentStatus {
         DocumentStatus {
-            file_type: self.buffer.file_info.file_type,
+            file_type: self.buffer.file_info.file_type(),
             file_name: self.buffer.file_info.to_string(),
             num_of_lines: self.buffer.height(),
             current_line: self.text_location.line_index,
+            scroll_position: ScrollPosition::new(
+                self.scroll_offset.y,
+                self.size.height,
+                self.folds.row_of(self.buffer.height()),
+            ),
             modified: self.buffer.is_dirty(),
+            read_only: self.buffer.is_read_only(),
+            encoding: self.buffer.encoding(),
+            final_newline: self.buffer.has_final_newline(),
         }
     }
 
+    /// The lines around the cursor are read first when the
+    /// cursor jumped far away in a large file.
     fn center_screen(&mut self) {
-        let TerminalSize { height, width } = self.size;
+        let height = self.size.height;
+        let width = self.text_width();
+        let line_index = self.text_location.line_index;
+        let _ = self
+            .buffer
+            .load_window(line_index.saturating_sub(height)..line_index.saturating_add(height));
+        self.sync_folds();
+
         let Position { x, y } = self.text_location_to_position();
 
         let vertical_mid = height.div_ceil(2);
@@ -383,6 +2961,7 @@ impl View {
 
         self.scroll_offset.y = y.saturating_sub(vertical_mid);
         self.scroll_offset.x = x.saturating_sub(horizontal_mid);
+        self.scroll_target = None;
 
         self.set_needs_redraw(true);
     }
@@ -393,8 +2972,12 @@ impl UiComponent for View {
         self.needs_redraw = val;
     }
 
+    /// Moving the cursor on another word changes the highlighted
+    /// occurrences, and on another column the cursor column.
     fn needs_redraw(&self) -> bool {
         self.needs_redraw
+            || self.highlighted_word.as_deref() != self.word_under_cursor()
+            || self.crosshair != self.crosshair()
     }
 
     fn set_size(&mut self, size: TerminalSize) {
@@ -405,40 +2988,602 @@ impl UiComponent for View {
     /// In renders the content of the file on the screen with the respective offset
     /// if it is present, otherwise is it gonna simply print
     /// the name of the editor and the version.
-    fn draw(&mut self, pos_y: usize) -> Result<(), std::io::Error> {
-        let query = (!self.search_term.is_empty()).then_some(self.search_term.as_str());
+    /// Only the visible lines are highlighted, the lines above them are looked
+    /// back at from the last row whose state is still known to resolve block
+    /// comments, so the cost doesn't grow with the file.
+    fn draw(&mut self, terminal: &mut Terminal, pos_y: usize) -> Result<(), std::io::Error> {
+        self.sync_folds();
+        // The matches are found again on every frame whatever the mode is,
+        // so they stay highlighted while typing and follow the edits.
+        let query =
+            (self.config.highlight_search && !self.matches_hidden && !self.search_term.is_empty())
+                .then_some(self.search_term.as_str());
         let selected_match = query.is_some().then_some(self.text_location);
-        let rows = self.buffer.lines.len();
-        let file_type = self.buffer.file_info.file_type;
-
-        let mut highlighter = Highlighter::new(rows, query, selected_match, file_type);
+        let options = self.match_options();
+        self.highlighted_word = self.word_under_cursor().map(ToOwned::to_owned);
+        self.crosshair = self.crosshair();
+        let file_type = self.buffer.file_info.file_type();
 
-        let TerminalSize { width, height } = self.size;
+        let height = self.size.height;
+        let width = self.text_width();
         let end_y = pos_y.saturating_add(height);
+        let empty_file_row = self.empty_file_row(pos_y, width);
+        let fill = self.config.eof_fill.as_str();
 
-        #[allow(clippy::integer_division)]
-        let vertical_center: usize = height / 3;
-
-        for (row, line) in self.buffer.lines.iter().enumerate() {
-            highlighter.highlight(row, line);
+        let scroll_top = self.scroll_offset.y;
+        let visible = self.visible_lines();
+
+        // A page is kept around the screen so that moving the cursor
+        // on a large file finds the lines it lands on.
+        self.buffer.load_window(
+            visible.start.saturating_sub(height)..visible.end.saturating_add(height),
+        )?;
+
+        let extra_cursors = self.extra_cursors().to_vec();
+        let mut highlighter = Highlighter::new(
+            visible.clone(),
+            query,
+            selected_match,
+            file_type,
+            &mut self.highlight_cache,
+        );
+        highlighter.set_match_options(options);
+        if let Some(word) = self.highlighted_word.as_deref() {
+            highlighter.highlight_word(word);
         }
+        if let Some(scope) = self.search_scope {
+            highlighter.limit_matches(scope);
+        }
+        let lookback_start = highlighter.resume(|row| self.buffer.line(row).map(Line::revision));
+
+        let cursor = self.text_location;
+        let selection = self
+            .selection
+            .map(|selection| (selection, selection.span(cursor, &self.buffer)));
+
+        Self::highlight_rows(
+            &mut highlighter,
+            &self.buffer,
+            &self.folds,
+            lookback_start..visible.end,
+            selection,
+            cursor,
+            &extra_cursors,
+        );
 
-        let scroll_top = self.scroll_offset.y;
         for current_row in pos_y..end_y {
-            let line_idx = current_row.saturating_sub(pos_y).saturating_add(scroll_top);
-            if let Some(line) = self.buffer.lines.get(line_idx) {
+            let line_idx = self
+                .folds
+                .line_at(current_row.saturating_sub(pos_y).saturating_add(scroll_top));
+            if let Some(line) = self.buffer.line(line_idx) {
                 let left = self.scroll_offset.x;
                 let right = self.scroll_offset.x.saturating_add(width);
 
                 let annotations = highlighter.get_annotations(line_idx);
-                Self::render_annotated_line(current_row, &line.get(left..right, annotations))?;
-            } else if current_row == vertical_center && self.buffer.is_empty() {
-                Self::render_line(current_row, &Self::build_title(width))?;
+                let mut annotated = line.get(left..right, annotations);
+                let len = line.grapheme_count();
+                let shown = cmp::min(line.width_until(len).saturating_sub(left), width);
+                if let Some((selection, span)) = selection {
+                    let padding = match (
+                        selection.kind,
+                        selection.row_range(cursor, span, line_idx, len),
+                    ) {
+                        (VisualKind::Line, Some(_)) => width.saturating_sub(shown),
+                        (VisualKind::Char, Some(range)) if range.end > len && shown < width => 1,
+                        _ => 0,
+                    };
+                    Self::pad_row(&mut annotated, padding, AnnotationType::Selection);
+                }
+                if annotated.get_line().width() < width
+                    && extra_cursors.contains(&Location {
+                        grapheme_index: len,
+                        line_index: line_idx,
+                    })
+                {
+                    Self::pad_row(&mut annotated, 1, AnnotationType::Cursor);
+                }
+
+                let room = width.saturating_sub(annotated.get_line().width());
+                Self::decorate_row(
+                    &self.folds,
+                    Gutter {
+                        diff: self.diff.as_ref(),
+                        touched: self.config.touched_gutter.then_some(&self.buffer),
+                    },
+                    &mut annotated,
+                    line,
+                    line_idx,
+                    room,
+                    self.crosshair,
+                );
+
+                Self::render_annotated_line(terminal, current_row, &annotated, &self.config.theme)?;
             } else {
-                Self::render_line(current_row, "~")?;
+                Self::render_past_end(terminal, current_row, empty_file_row.as_ref(), fill)?;
             }
         }
 
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(line_index: usize, grapheme_index: usize) -> Location {
+        Location {
+            grapheme_index,
+            line_index,
+        }
+    }
+
+    fn view_with(text: &str, config: &str) -> View {
+        let mut view = View::from_text(text);
+        view.set_config(Config::parse(config));
+        view
+    }
+
+    /// A file name in the temporary directory that no other test,
+    /// in this run or in one running next to it, writes to.
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let count = COUNTER.fetch_add(1, Ordering::Relaxed);
+        std::env::temp_dir().join(format!("beppe-{}-{count}-{name}", std::process::id()))
+    }
+
+    #[test]
+    fn placing_the_cursor_clamps_it_inside_the_buffer() {
+        let mut view = view_with("abc\nde", "");
+        view.place_cursor(Some(CursorPlacement::At(at(0, 99))));
+        assert_eq!(view.text_location, at(0, 3));
+        view.place_cursor(Some(CursorPlacement::At(at(9, 1))));
+        assert_eq!(view.text_location, at(2, 0));
+    }
+
+    #[test]
+    fn wide_graphemes_take_two_columns_and_tabs_one() {
+        let mut view = view_with("\u{6f22}\u{5b57}x\n\tb", "");
+        view.text_location = at(0, 2);
+        assert_eq!(view.text_location_to_position().x, 4);
+        view.text_location = at(0, 3);
+        assert_eq!(view.text_location_to_position().x, 5);
+        view.text_location = at(1, 1);
+        assert_eq!(view.text_location_to_position().x, 1);
+    }
+
+    #[test]
+    fn moving_across_a_shorter_line_snaps_to_its_last_grapheme() {
+        let mut view = view_with("abcdef\nab\nabcdef", "");
+        view.text_location = at(0, 5);
+        view.handle_command(EditorCommand::Move(Direction::Down));
+        assert_eq!(view.text_location, at(1, 1));
+        view.handle_command(EditorCommand::Move(Direction::Down));
+        assert_eq!(view.text_location, at(2, 1));
+
+        view.text_location = at(2, 5);
+        view.handle_command(EditorCommand::Move(Direction::Up));
+        assert_eq!(view.text_location, at(1, 1));
+        view.handle_command(EditorCommand::Move(Direction::Down));
+        view.handle_command(EditorCommand::Move(Direction::Down));
+        view.handle_command(EditorCommand::Move(Direction::Down));
+        assert_eq!(view.text_location, at(3, 0));
+    }
+
+    #[test]
+    fn joining_and_deleting_lines_place_the_cursor() {
+        let mut view = view_with("ab\n  cd\n  ef\ngh", "");
+        view.text_location = at(0, 1);
+        view.handle_command(EditorCommand::JoinLines);
+        assert_eq!(view.lines(), ["ab cd", "  ef", "gh"]);
+        assert_eq!(view.text_location, at(0, 2));
+
+        view.handle_command(EditorCommand::DeleteLine);
+        assert_eq!(view.lines(), ["  ef", "gh"]);
+        assert_eq!(view.text_location, at(0, 2));
+
+        view.text_location = at(1, 1);
+        view.handle_command(EditorCommand::DeleteLine);
+        assert_eq!(view.lines(), ["  ef"]);
+        assert_eq!(view.text_location, at(0, 2));
+    }
+
+    #[test]
+    fn goto_clamps_the_line_and_the_column() {
+        let mut view = view_with("one\n  two\nthree", "");
+        view.goto(2, Some(4));
+        assert_eq!(view.text_location, at(1, 3));
+        view.goto(2, None);
+        assert_eq!(view.text_location, at(1, 2));
+        view.goto(42, Some(10));
+        assert_eq!(view.text_location, at(2, 5));
+        view.goto(0, Some(0));
+        assert_eq!(view.text_location, at(0, 0));
+    }
+
+    #[test]
+    fn n_repeats_a_forward_search_forward_and_wraps() {
+        let mut view = view_with("foo bar\nbaz foo\nfoo", "");
+        view.set_search_term("foo".to_string());
+        assert!(view.search(SearchDirection::Forward));
+        assert_eq!(view.text_location, at(0, 0));
+        view.search_next();
+        assert_eq!(view.text_location, at(1, 4));
+        view.search_next();
+        assert_eq!(view.text_location, at(2, 0));
+        view.search_next();
+        assert_eq!(view.text_location, at(0, 0));
+        view.search_prev();
+        assert_eq!(view.text_location, at(2, 0));
+    }
+
+    #[test]
+    fn n_repeats_a_backward_search_backward_and_wraps() {
+        let mut view = view_with("foo bar\nbaz foo\nfoo", "");
+        view.text_location = at(1, 0);
+        view.set_search_term("foo".to_string());
+        assert!(view.search(SearchDirection::Backward));
+        assert_eq!(view.text_location, at(0, 0));
+        view.search_next();
+        assert_eq!(view.text_location, at(2, 0));
+        view.search_next();
+        assert_eq!(view.text_location, at(1, 4));
+        view.search_prev();
+        assert_eq!(view.text_location, at(2, 0));
+    }
+
+    #[test]
+    fn a_search_without_matches_leaves_the_cursor() {
+        let mut view = view_with("foo bar\nbaz foo", "");
+        view.text_location = at(1, 2);
+        view.set_search_term("qux".to_string());
+        assert!(!view.search(SearchDirection::Forward));
+        assert!(!view.search(SearchDirection::Backward));
+        assert!(!view.search_next());
+        assert_eq!(view.text_location, at(1, 2));
+    }
+
+    #[test]
+    fn smartcase_ignores_the_case_of_a_lowercase_term() {
+        let mut view = view_with("Foo foo FOO", "smartcase = true");
+        view.set_search_term("foo".to_string());
+        assert_eq!(view.buffer.count_matches("foo", view.match_options()), 3);
+        view.set_search_term("Foo".to_string());
+        assert_eq!(view.buffer.count_matches("Foo", view.match_options()), 1);
+        view.text_location = at(0, 1);
+        assert!(view.search(SearchDirection::Forward));
+        assert_eq!(view.text_location, at(0, 0));
+
+        view.set_config(Config::parse("smartcase = false"));
+        view.set_search_term("foo".to_string());
+        assert_eq!(view.buffer.count_matches("foo", view.match_options()), 1);
+    }
+
+    #[test]
+    fn home_alternates_between_the_first_non_blank_and_the_start() {
+        let mut view = view_with("    let a = 1;", "");
+        view.text_location = at(0, 9);
+        view.handle_command(EditorCommand::Move(Direction::Home));
+        assert_eq!(view.text_location, at(0, 4));
+        view.handle_command(EditorCommand::Move(Direction::Home));
+        assert_eq!(view.text_location, at(0, 0));
+        view.handle_command(EditorCommand::Move(Direction::Home));
+        assert_eq!(view.text_location, at(0, 4));
+
+        view.handle_command(EditorCommand::Move(Direction::LineStart));
+        assert_eq!(view.text_location, at(0, 0));
+        view.handle_command(EditorCommand::Move(Direction::LineStart));
+        assert_eq!(view.text_location, at(0, 0));
+    }
+
+    #[test]
+    fn moving_a_line_swaps_it_and_the_cursor_follows() {
+        let mut view = view_with("one\ntwo\nthree", "");
+        view.text_location = at(1, 2);
+        view.handle_command(EditorCommand::MoveLineUp);
+        assert_eq!(view.lines(), ["two", "one", "three"]);
+        assert_eq!(view.text_location, at(0, 2));
+        assert!(view.is_file_modified());
+
+        view.handle_command(EditorCommand::MoveLineDown);
+        view.handle_command(EditorCommand::MoveLineDown);
+        assert_eq!(view.lines(), ["one", "three", "two"]);
+        assert_eq!(view.text_location, at(2, 2));
+    }
+
+    #[test]
+    fn moving_a_line_past_the_ends_does_nothing() {
+        let mut view = view_with("one\ntwo", "");
+        view.handle_command(EditorCommand::MoveLineUp);
+        assert_eq!(view.text_location, at(0, 0));
+        view.text_location = at(1, 1);
+        view.handle_command(EditorCommand::MoveLineDown);
+        assert_eq!(view.lines(), ["one", "two"]);
+        assert_eq!(view.text_location, at(1, 1));
+        assert!(!view.is_file_modified());
+    }
+
+    #[test]
+    fn ctrl_u_deletes_to_the_first_non_blank_and_then_to_the_start() {
+        let mut view = view_with("    let a = 1;", "");
+        view.text_location = at(0, 10);
+        view.delete_to_line_start();
+        assert_eq!(view.lines(), ["    = 1;"]);
+        assert_eq!(view.text_location, at(0, 4));
+        view.delete_to_line_start();
+        assert_eq!(view.lines(), ["= 1;"]);
+        assert_eq!(view.text_location, at(0, 0));
+        view.delete_to_line_start();
+        assert_eq!(view.lines(), ["= 1;"]);
+    }
+
+    #[test]
+    fn a_modeline_sets_the_options_of_its_buffer() {
+        let text = "// beppe: tw=2 et ft=rust\nfn main() {}";
+        let mut view = View::default();
+        view.set_config(Config::parse("modelines = true\ntab_width = 8"));
+        view.set_buffer(Buffer::from_str(text).unwrap());
+        assert_eq!(view.config.tab_width, 2);
+        assert!(view.config.expand_tab);
+        assert_eq!(view.file_type(), FileType::Rust);
+
+        view.set_buffer(Buffer::from_str("fn main() {}").unwrap());
+        assert_eq!(view.config.tab_width, 8);
+        assert_eq!(view.file_type(), FileType::PlainText);
+
+        view.set_config(Config::parse("tab_width = 8"));
+        view.set_buffer(Buffer::from_str(text).unwrap());
+        assert_eq!(view.config.tab_width, 8);
+        assert_eq!(view.file_type(), FileType::PlainText);
+    }
+
+    #[test]
+    fn star_and_hash_jump_between_the_occurrences_of_the_word() {
+        let mut view = view_with("let foo = foo_bar;\nfoo(1);\nbar foo", "");
+        view.text_location = at(0, 5);
+        assert!(view.search_word_under_cursor(SearchDirection::Forward));
+        assert_eq!(view.text_location, at(1, 0));
+        view.search_next();
+        assert_eq!(view.text_location, at(2, 4));
+        view.search_next();
+        assert_eq!(view.text_location, at(0, 4));
+
+        assert!(view.search_word_under_cursor(SearchDirection::Backward));
+        assert_eq!(view.text_location, at(2, 4));
+        view.search_next();
+        assert_eq!(view.text_location, at(1, 0));
+
+        view.text_location = at(0, 8);
+        assert!(!view.search_word_under_cursor(SearchDirection::Forward));
+        assert_eq!(view.text_location, at(0, 8));
+    }
+
+    #[test]
+    fn backspace_in_the_indentation_deletes_to_the_previous_tab_stop() {
+        let mut view = view_with("          x", "expand_tab = true\ntab_width = 4");
+        view.text_location = at(0, 10);
+        view.handle_backspace();
+        assert_eq!(view.lines(), ["        x"]);
+        assert_eq!(view.text_location, at(0, 8));
+        view.handle_backspace();
+        assert_eq!(view.lines(), ["    x"]);
+        assert_eq!(view.text_location, at(0, 4));
+
+        view.text_location = at(0, 2);
+        view.handle_backspace();
+        assert_eq!(view.lines(), ["  x"]);
+        assert_eq!(view.text_location, at(0, 0));
+    }
+
+    #[test]
+    fn backspace_deletes_one_space_outside_the_indentation_or_without_soft_tabs() {
+        let mut view = view_with("a       b", "expand_tab = true\ntab_width = 4");
+        view.text_location = at(0, 8);
+        view.handle_backspace();
+        assert_eq!(view.lines(), ["a      b"]);
+
+        let mut view = view_with("        x", "expand_tab = false\ntab_width = 4");
+        view.text_location = at(0, 8);
+        view.handle_backspace();
+        assert_eq!(view.lines(), ["       x"]);
+    }
+
+    #[test]
+    fn an_empty_search_repeats_the_last_term() {
+        let mut view = view_with("bar foo\nfoo bar", "");
+        view.set_search_term("foo".to_string());
+        assert!(view.search(SearchDirection::Forward));
+        assert_eq!(view.text_location, at(0, 4));
+
+        view.clear_search_term();
+        view.text_location = at(0, 5);
+        view.set_search_term(String::new());
+        assert_eq!(view.search_term(), "foo");
+        assert!(view.search(SearchDirection::Forward));
+        assert_eq!(view.text_location, at(1, 0));
+    }
+
+    #[test]
+    fn each_file_type_takes_its_own_tab_settings() {
+        let config = "tab_width = 2\nft.rust.tab_width = 4\nft.rust.expand_tab = true";
+        let dir = std::env::temp_dir();
+        let rust = dir.join("beppe-file-type-test.rs");
+        let text = dir.join("beppe-file-type-test.txt");
+        fs::write(&rust, "fn main() {}\n").unwrap();
+        fs::write(&text, "main\n").unwrap();
+
+        let mut view = View::default();
+        view.set_config(Config::parse(config));
+        view.load(rust.to_str().unwrap()).unwrap();
+        let rust_settings = (view.config.tab_width, view.config.expand_tab);
+        view.load(text.to_str().unwrap()).unwrap();
+        let text_settings = (view.config.tab_width, view.config.expand_tab);
+        fs::remove_file(&rust).unwrap();
+        fs::remove_file(&text).unwrap();
+
+        assert_eq!(rust_settings, (4, true));
+        assert_eq!(text_settings, (2, false));
+        view.set_file_type(FileType::Rust);
+        assert_eq!((view.config.tab_width, view.config.expand_tab), (4, true));
+        view.set_file_type(FileType::Python);
+        assert_eq!((view.config.tab_width, view.config.expand_tab), (2, false));
+    }
+
+    #[test]
+    fn paste_does_not_close_pairs() {
+        let mut view = view_with("", "auto_close = true");
+        view.handle_paste("f(x)");
+        view.handle_paste(" g(");
+        assert_eq!(view.lines(), ["f(x) g("]);
+        assert_eq!(view.text_location, at(0, 7));
+    }
+
+    #[test]
+    fn paste_keeps_the_lines_and_the_text_after_the_cursor() {
+        let mut view = view_with("ab", "auto_close = true");
+        view.text_location = at(0, 1);
+        view.handle_paste("x\r\n  [y\n");
+        assert_eq!(view.lines(), ["ax", "  [y", "b"]);
+        assert_eq!(view.text_location, at(2, 0));
+    }
+
+    #[test]
+    fn paste_converts_tabs_to_the_indentation_of_the_file() {
+        let mut view = view_with(
+            "    a",
+            "expand_tab = true\ntab_width = 4\npaste_reindent = true",
+        );
+        view.text_location = at(0, 5);
+        view.handle_paste("\n\tb\n\t\tc");
+        assert_eq!(view.lines(), ["    a", "    b", "        c"]);
+        assert_eq!(view.text_location, at(2, 9));
+    }
+
+    #[test]
+    fn paste_keeps_the_tabs_without_reindenting() {
+        let mut view = view_with("    a", "expand_tab = true\ntab_width = 4");
+        view.text_location = at(0, 5);
+        view.handle_paste("\n\tb");
+        assert_eq!(view.lines(), ["    a", "\tb"]);
+    }
+
+    #[test]
+    fn paste_normalizes_the_lines_it_touches() {
+        let mut view = view_with("cafe", "normalize_input = true");
+        view.text_location = at(0, 4);
+        view.handle_paste("\u{301}!\ne\u{301}");
+        assert_eq!(view.lines(), ["caf\u{e9}!", "\u{e9}"]);
+        assert_eq!(view.text_location, at(1, 1));
+    }
+
+    #[test]
+    fn typing_expands_abbreviations() {
+        let mut view = view_with("", "abbrev.teh = the\nauto_close = true");
+        for ch in "(teh) teh.tehx ".chars() {
+            view.handle_insertion(ch);
+        }
+        assert_eq!(view.lines(), ["(the) the.tehx "]);
+        assert_eq!(view.text_location, at(0, 15));
+    }
+
+    #[test]
+    fn pairs_are_not_closed_in_a_comment_opened_far_above() {
+        let text = format!("/*\n{}*/", "a\n".repeat(300));
+        let mut view = view_with(&text, "auto_close = true");
+        view.set_file_type(FileType::Rust);
+        view.text_location = at(250, 1);
+        view.handle_insertion('(');
+        assert_eq!(view.buffer.line(250).unwrap().get_string(), "a(");
+    }
+
+    #[test]
+    fn a_lifetime_after_a_reference_or_in_generics_is_not_closed() {
+        let mut view = view_with("", "auto_close = true");
+        view.set_file_type(FileType::Rust);
+        for ch in "fn f<'a>(s: &'a str) {}".chars() {
+            view.handle_insertion(ch);
+        }
+        assert_eq!(view.lines(), ["fn f<'a>(s: &'a str) {}"]);
+
+        let mut view = view_with("", "auto_close = true");
+        for ch in "&'".chars() {
+            view.handle_insertion(ch);
+        }
+        assert_eq!(view.lines(), ["&''"]);
+    }
+
+    #[test]
+    fn pasting_above_a_fold_moves_it_down() {
+        let mut view = view_with("a\nb\nc\nd\ne\nf", "");
+        view.folds.create(2..4);
+        view.text_location = at(5, 0);
+        view.sync_folds();
+
+        view.text_location = at(0, 0);
+        view.handle_paste("x\ny\nz");
+        view.sync_folds();
+        assert_eq!(view.folds.closed_at(2), None);
+        assert_eq!(view.folds.closed_at(4), Some(4..6));
+        assert_eq!(view.buffer.line(4).unwrap().get_string(), "c");
+    }
+
+    #[test]
+    fn duplicating_a_line_moves_the_cursor_onto_the_copy() {
+        let mut view = view_with("ab\ncd", "");
+        view.text_location = at(0, 1);
+        let placement = view.duplicate_line();
+        view.place_cursor(Some(placement));
+        assert_eq!(view.lines(), ["ab", "ab", "cd"]);
+        assert_eq!(view.text_location, at(1, 1));
+
+        view.text_location = at(3, 0);
+        let placement = view.duplicate_line();
+        view.place_cursor(Some(placement));
+        assert_eq!(view.lines(), ["ab", "ab", "cd", ""]);
+        assert_eq!(view.text_location, at(3, 0));
+        assert_eq!(view.buffer.height(), 4);
+    }
+
+    #[test]
+    fn toggling_the_case_stays_on_the_last_grapheme() {
+        let mut view = view_with("aé", "");
+        view.text_location = at(0, 0);
+        let placement = view.toggle_case();
+        view.place_cursor(placement);
+        assert_eq!(view.text_location, at(0, 1));
+
+        let placement = view.toggle_case();
+        view.place_cursor(placement);
+        assert_eq!(view.lines(), ["AÉ"]);
+        assert_eq!(view.text_location, at(0, 1));
+    }
+
+    #[test]
+    fn paste_does_not_expand_abbreviations() {
+        let mut view = view_with("", "abbrev.teh = the");
+        view.handle_paste("teh teh.");
+        assert_eq!(view.lines(), ["teh teh."]);
+    }
+
+    #[test]
+    fn reload_discards_the_edits_and_the_state_of_the_old_content() {
+        let path = temp_path("reload-test.txt");
+        fs::write(&path, "one\ntwo\nthree\nfour\n").unwrap();
+        let mut view = View::default();
+        view.load(path.to_str().unwrap()).unwrap();
+
+        view.folds.create(1..3);
+        view.text_location = at(3, 4);
+        view.handle_insertion('!');
+        fs::write(&path, "one\ntwo\n").unwrap();
+        view.reload().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(view.lines(), ["one", "two"]);
+        assert!(!view.is_file_modified());
+        assert_eq!(view.folds.closed_at(1), None);
+        assert_eq!(view.text_location, at(2, 0));
+    }
+}
@@ -0,0 +1,40 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+/// The text typed in place of an abbreviation, where
+/// `$date` is today's date written as YYYY-MM-DD.
+pub fn expand(expansion: &str) -> String {
+    if expansion.contains("$date") {
+        expansion.replace("$date", &today())
+    } else {
+        expansion.to_string()
+    }
+}
+
+/// Today's date in UTC, converted from the days since the Unix epoch.
+#[allow(clippy::arithmetic_side_effects, clippy::integer_division)]
+fn today() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs() / SECONDS_PER_DAY);
+
+    // The years are counted from March so that the leap day
+    // is the last one, in eras of 400 years.
+    let shifted = days + 719_468;
+    let era = shifted / 146_097;
+    let day_of_era = shifted % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
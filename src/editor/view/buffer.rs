@@ -1,87 +1,781 @@
-use crate::editor::{line::Line, view::file_info::FileInfo};
+use crate::editor::{
+    editor_cmd::Case,
+    line::{self, GraphemeIndex, Line, MatchOptions},
+    view::{encoding::Encoding, file_info::FileInfo, large_file::LargeFile},
+};
 
 use super::Location;
 use std::{
-    fs::{self, File},
-    io::{Error, ErrorKind, Write},
+    cell::Cell,
+    cmp,
+    convert::Infallible,
+    fmt::{self, Display},
+    fs::{self, File, OpenOptions},
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{Error, ErrorKind, Read, Write},
+    mem,
+    ops::Range,
+    str::FromStr,
 };
 
+/// Files bigger than this are opened read-only, reading
+/// from the disk only the lines that are shown.
+const LARGE_FILE_SIZE: u64 = 16 * 1024 * 1024;
+
+/// The line terminator used when the file is written back,
+/// detected from the content of the file when loading it.
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    fn detect(content: &str) -> Self {
+        if content.contains("\r\n") {
+            Self::CrLf
+        } else {
+            Self::Lf
+        }
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+        }
+    }
+}
+
+/// Rapresents the content as it is on the disk, the lines keep their
+/// revision so that only the ones edited since are hashed again.
+#[derive(Default)]
+struct SavedContent {
+    lines: Vec<(u64, u64)>,
+    format: (LineEnding, Encoding, bool),
+}
+
+/// Statistics about the content of the buffer, `bytes` counts
+/// the line endings as well so it matches the size of the saved
+/// file, while `chars` only counts the characters in the lines.
+pub struct BufferStats {
+    pub lines: usize,
+    pub words: usize,
+    pub chars: usize,
+    pub bytes: usize,
+}
+
+/// Rapresents the lines an edit inserted or deleted: the `removed`
+/// lines from `start` on were replaced by `added` ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineEdit {
+    pub start: usize,
+    pub removed: usize,
+    pub added: usize,
+}
+
+impl LineEdit {
+    /// Where the line is after the edit, the removed lines
+    /// move to the added ones or to the line after them.
+    pub fn shift(&self, line: usize) -> usize {
+        let removed_end = self.start.saturating_add(self.removed);
+        if line < self.start {
+            line
+        } else if line >= removed_end {
+            line.saturating_sub(self.removed).saturating_add(self.added)
+        } else {
+            self.start
+                .saturating_add(cmp::min(line.saturating_sub(self.start), self.added))
+        }
+    }
+}
+
+/// For large files `lines` only holds the window of
+/// lines starting at `window_start` read from the disk.
 #[derive(Default)]
 pub struct Buffer {
     pub lines: Vec<Line>,
     pub file_info: FileInfo,
-    dirty: bool,
+    line_ending: LineEnding,
+    encoding: Encoding,
+    /// The last line isn't followed by a line ending,
+    /// kept as it was in the file when saving.
+    missing_final_newline: bool,
+    saved: SavedContent,
+    /// The last line revision when the file was loaded or saved,
+    /// the lines edited afterwards have a greater one.
+    saved_line_revision: u64,
+    /// The revision last compared with the content on
+    /// the disk, along with whether it was different.
+    dirty: Cell<Option<(usize, bool)>>,
+    revision: usize,
+    large_file: Option<LargeFile>,
+    window_start: usize,
+    /// The lines inserted or deleted since `take_line_edits`.
+    line_edits: Vec<LineEdit>,
 }
 
 impl Buffer {
     pub fn load(file_path: &str) -> Result<Self, std::io::Error> {
-        let lines: Vec<Line> = fs::read_to_string(file_path)?
-            .lines()
-            .map(Line::from)
-            .collect();
+        Self::load_with_progress(file_path, &mut |_| true)
+    }
 
-        Ok(Self {
+    /// Loads the file reporting the percentage done to `progress`,
+    /// the loading is interrupted as soon as it returns `false`.
+    pub fn load_with_progress(
+        file_path: &str,
+        progress: &mut dyn FnMut(usize) -> bool,
+    ) -> Result<Self, Error> {
+        let len = fs::metadata(file_path)?.len();
+        if len > LARGE_FILE_SIZE {
+            return Self::load_large(file_path, progress);
+        }
+
+        let mut file = File::open(file_path)?;
+        let mut bytes = Vec::with_capacity(usize::try_from(len).unwrap_or(0));
+        let mut chunk = vec![0; 64 * 1024];
+        loop {
+            let read = file.read(&mut chunk)?;
+            if read == 0 {
+                break;
+            }
+
+            bytes.extend_from_slice(&chunk[..read]);
+            let done = u64::try_from(bytes.len()).unwrap_or(u64::MAX);
+            if !progress(percentage(done, len).div_ceil(2)) {
+                return Err(interrupted());
+            }
+        }
+
+        let (content, encoding) = Encoding::decode(bytes);
+        let total = u64::try_from(content.len()).unwrap_or(u64::MAX);
+        let mut done: u64 = 0;
+        let mut lines = Vec::new();
+        for line in content.lines() {
+            lines.push(Line::from(line));
+            done = done.saturating_add(u64::try_from(line.len()).unwrap_or(u64::MAX));
+            if !progress(percentage(done, total).div_ceil(2).saturating_add(50)) {
+                return Err(interrupted());
+            }
+        }
+
+        let mut buffer = Self::from_lines(
             lines,
-            file_info: FileInfo::from(file_path),
-            dirty: false,
+            LineEnding::detect(&content),
+            FileInfo::from(file_path),
+        );
+        buffer.encoding = encoding;
+        buffer.missing_final_newline = misses_final_newline(&content);
+        buffer.mark_saved();
+        Ok(buffer)
+    }
+
+    fn from_lines(lines: Vec<Line>, line_ending: LineEnding, mut file_info: FileInfo) -> Self {
+        if let Some(first_line) = lines.first() {
+            file_info.detect_shebang(first_line.get_string());
+        }
+
+        let mut buffer = Self {
+            lines,
+            file_info,
+            line_ending,
+            ..Self::default()
+        };
+        buffer.mark_saved();
+        buffer
+    }
+
+    fn load_large(file_path: &str, progress: &mut dyn FnMut(usize) -> bool) -> Result<Self, Error> {
+        let large_file = LargeFile::open(file_path, progress)?;
+        let mut file_info = FileInfo::from(file_path);
+        if let Some(first_line) = large_file.read_lines(0..1)?.first() {
+            file_info.detect_shebang(first_line.get_string());
+        }
+
+        Ok(Self {
+            file_info,
+            large_file: Some(large_file),
+            ..Self::default()
         })
     }
 
+    /// Makes sure the lines in the range are in memory,
+    /// it does nothing for files that aren't large.
+    pub fn load_window(&mut self, range: Range<usize>) -> Result<(), Error> {
+        let Some(large_file) = &self.large_file else {
+            return Ok(());
+        };
+
+        let window_end = self.window_start.saturating_add(self.lines.len());
+        let range_end = cmp::min(range.end, large_file.height());
+        if range.start >= self.window_start && range_end <= window_end {
+            return Ok(());
+        }
+
+        self.lines = large_file.read_lines(range.clone())?;
+        self.window_start = range.start;
+        Ok(())
+    }
+
+    /// Returns the line at the index if it is in memory.
+    pub fn line(&self, index: usize) -> Option<&Line> {
+        index
+            .checked_sub(self.window_start)
+            .and_then(|index| self.lines.get(index))
+    }
+
+    /// Large files are opened read-only.
+    pub fn is_read_only(&self) -> bool {
+        self.large_file.is_some()
+    }
+
+    fn ensure_writable(&self) -> Result<(), Error> {
+        if self.is_read_only() {
+            return Err(Error::new(
+                ErrorKind::Unsupported,
+                "Large files are opened read-only",
+            ));
+        }
+
+        Ok(())
+    }
+
     pub fn save(&mut self) -> Result<(), Error> {
+        self.ensure_writable()?;
         if let Some(file_path) = &self.file_info.path {
-            let mut file = File::create(file_path)?;
+            fs::write(file_path, self.encoded()?)?;
 
-            for line in &self.lines {
-                writeln!(&mut file, "{line}")?;
-            }
-
-            self.dirty = false;
+            self.mark_saved();
             Ok(())
         } else {
             Err(Error::new(ErrorKind::NotFound, "File name wasn't provided"))
         }
     }
 
+    /// Saves the file even if it isn't writable by its owner. The content
+    /// is written to a copy next to it with the same permissions, which is
+    /// then renamed over the file, so that a failure leaves it as it was.
+    #[cfg(unix)]
+    pub fn save_forced(&mut self) -> Result<(), Error> {
+        use std::os::unix::fs::PermissionsExt;
+
+        self.ensure_writable()?;
+        let Some(file_path) = self.file_info.path.clone() else {
+            return Err(Error::new(ErrorKind::NotFound, "File name wasn't provided"));
+        };
+
+        let Ok(metadata) = fs::metadata(&file_path) else {
+            return self.save();
+        };
+
+        let permissions = metadata.permissions();
+        if permissions.mode() & 0o200 != 0 {
+            return self.save();
+        }
+
+        let contents = self.encoded()?;
+        let file_name = file_path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let copy = file_path.with_file_name(format!(".{file_name}.beppe-save"));
+
+        let written = fs::write(&copy, contents)
+            .and_then(|()| fs::set_permissions(&copy, permissions))
+            .and_then(|()| fs::rename(&copy, &file_path));
+        if written.is_err() {
+            let _ = fs::remove_file(&copy);
+        }
+        written?;
+
+        self.mark_saved();
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    pub fn save_forced(&mut self) -> Result<(), Error> {
+        self.save()
+    }
+
+    /// Writes the lines at the end of the file, which is created if it
+    /// doesn't exist. Returns the number of bytes written.
+    pub fn append_to(&self, file_path: &str) -> Result<usize, Error> {
+        self.ensure_writable()?;
+        let contents = self.encoded()?;
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(file_path)?
+            .write_all(&contents)?;
+
+        Ok(contents.len())
+    }
+
+    /// The lines as they are written to the file.
+    fn encoded(&self) -> Result<Vec<u8>, Error> {
+        self.encoding.encode(&self.to_string())
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// Changes how the file is written, which makes it modified.
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        if encoding != self.encoding {
+            self.encoding = encoding;
+            self.mark_dirty();
+        }
+    }
+
+    pub fn has_final_newline(&self) -> bool {
+        !self.missing_final_newline
+    }
+
+    /// Adds or removes the line ending after the last
+    /// line, which makes the file modified.
+    pub fn set_final_newline(&mut self, enabled: bool) {
+        if enabled == self.missing_final_newline {
+            self.missing_final_newline = !enabled;
+            self.mark_dirty();
+        }
+    }
+
+    /// The lines joined with `\n`, regardless of the line ending of the file.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for line in &self.lines {
+            text.push_str(line.get_string());
+            text.push('\n');
+        }
+        text
+    }
+
+    /// Replaces all the lines with the ones of the text.
+    pub fn replace_text(&mut self, text: &str) {
+        self.mark_dirty();
+        let removed = self.lines.len();
+        self.lines = text.lines().map(Line::from).collect();
+        self.record_lines(0, removed, self.lines.len());
+        self.missing_final_newline = misses_final_newline(text);
+    }
+
     pub fn save_as(&mut self, file_name: &str) -> Result<(), Error> {
+        let file_type = self.file_info.file_type();
         self.file_info = FileInfo::from(file_name);
+        if file_type != self.file_info.file_type() {
+            self.file_info.set_file_type(file_type);
+        }
         self.save()
     }
 
     pub fn insert_char(&mut self, character: char, at: Location) {
         // If I'm in a valid line i need to insert the character inside otherwise i push another
         // line to the document
-        self.dirty = true;
+        self.mark_dirty();
         if at.line_index == self.height() {
             self.lines.push(Line::from(&character.to_string()));
+            self.record_lines(at.line_index, 0, 1);
         } else {
             let line = self.lines.get_mut(at.line_index).unwrap();
             line.insert_char_at(at.grapheme_index, character);
         }
     }
 
+    pub fn normalize_line(&mut self, index: usize) {
+        if let Some(line) = self.lines.get_mut(index) {
+            line.normalize();
+        }
+    }
+
     pub fn delete(&mut self, at: Location) {
-        self.dirty = true;
+        self.mark_dirty();
         if let Some(line) = self.lines.get_mut(at.line_index) {
             if at.grapheme_index < line.grapheme_count() {
                 line.remove_at(at.grapheme_index);
             } else if at.line_index.saturating_add(1) < self.height() {
                 let next_line = self.lines.remove(at.line_index.saturating_add(1));
                 self.lines[at.line_index].append(&next_line);
+                self.record_lines(at.line_index.saturating_add(1), 1, 0);
+            }
+        }
+    }
+
+    pub fn delete_range(&mut self, line_index: usize, range: Range<GraphemeIndex>) {
+        if let Some(line) = self.lines.get_mut(line_index) {
+            line.remove_range(range);
+            self.mark_dirty();
+        }
+    }
+
+    /// Adds a level of indentation at the start of the line,
+    /// returns the number of graphemes inserted.
+    pub fn indent_line(&mut self, index: usize, tab_width: usize, expand_tab: bool) -> usize {
+        let Some(line) = self.lines.get_mut(index) else {
+            return 0;
+        };
+
+        let indent = if expand_tab {
+            " ".repeat(tab_width)
+        } else {
+            String::from("\t")
+        };
+        line.insert_str_at(0, &indent);
+        self.mark_dirty();
+
+        indent.len()
+    }
+
+    /// Removes a leading tab or up to `tab_width` leading spaces
+    /// from the line, returns the number of graphemes removed.
+    pub fn dedent_line(&mut self, index: usize, tab_width: usize) -> usize {
+        let Some(line) = self.lines.get_mut(index) else {
+            return 0;
+        };
+
+        let string = line.get_string();
+        let count = if string.starts_with('\t') {
+            1
+        } else {
+            string
+                .chars()
+                .take(tab_width)
+                .take_while(|&ch| ch == ' ')
+                .count()
+        };
+
+        if count > 0 {
+            line.remove_range(0..count);
+            self.mark_dirty();
+        }
+
+        count
+    }
+
+    /// Inserts a copy of the line below it, duplicating the
+    /// line past the end of the file appends an empty line.
+    pub fn duplicate_line(&mut self, index: usize) {
+        self.mark_dirty();
+        if let Some(line) = self.lines.get(index) {
+            let mut copy = line.clone();
+            copy.renew();
+            self.lines.insert(index.saturating_add(1), copy);
+            self.record_lines(index.saturating_add(1), 0, 1);
+        } else {
+            self.lines.push(Line::default());
+            self.record_lines(self.lines.len().saturating_sub(1), 0, 1);
+        }
+    }
+
+    pub fn change_case(&mut self, line_index: usize, range: Range<GraphemeIndex>, case: Case) {
+        if let Some(line) = self.lines.get_mut(line_index) {
+            line.change_case(range, case);
+            self.mark_dirty();
+        }
+    }
+
+    /// Adds `delta` to the number under or after the location, a `-` right
+    /// before a decimal number makes it negative. Returns the grapheme
+    /// index of the last character of the new number.
+    pub fn increment_number(&mut self, at: Location, delta: i64) -> Option<GraphemeIndex> {
+        let line = self.lines.get_mut(at.line_index)?;
+        let mut range = line.number_range_from(at.grapheme_index)?;
+        let literal: String = range.clone().filter_map(|i| line.grapheme_at(i)).collect();
+
+        let minus_index = range.start.checked_sub(1);
+        let negative = minus_index.and_then(|i| line.grapheme_at(i)) == Some("-");
+        let replacement = increment_literal(&literal, negative, delta)?;
+        if negative && let Some(minus_index) = minus_index {
+            range.start = minus_index;
+        }
+
+        line.replace_range(range.clone(), &replacement);
+        self.mark_dirty();
+
+        Some(
+            range
+                .start
+                .saturating_add(replacement.len())
+                .saturating_sub(1),
+        )
+    }
+
+    pub fn transpose(&mut self, at: Location) {
+        if let Some(line) = self.lines.get_mut(at.line_index) {
+            line.transpose_at(at.grapheme_index);
+            self.mark_dirty();
+        }
+    }
+
+    /// Joins the line with the next one separating them with a space,
+    /// the leading whitespace of the next line is removed. Without
+    /// `insert_space` the lines are joined as they are. Returns the
+    /// grapheme index of the join point.
+    pub fn join_lines(&mut self, index: usize, insert_space: bool) -> Option<GraphemeIndex> {
+        let next_index = index.saturating_add(1);
+        if next_index >= self.lines.len() {
+            return None;
+        }
+
+        let next = self.lines.remove(next_index);
+        self.record_lines(next_index, 1, 0);
+        let line = &mut self.lines[index];
+        let join_point = line.grapheme_count();
+        if !insert_space {
+            line.append(&next);
+            self.mark_dirty();
+            return Some(join_point);
+        }
+
+        let next = next.get_string().trim_start();
+
+        let needs_space = !next.is_empty()
+            && !line.get_string().is_empty()
+            && !line.get_string().ends_with(char::is_whitespace);
+        if needs_space {
+            line.append(&Line::from(" "));
+        }
+        line.append(&Line::from(next));
+        self.mark_dirty();
+
+        Some(join_point)
+    }
+
+    /// Removes the line, returns `false` if it doesn't exist.
+    pub fn delete_line(&mut self, index: usize) -> bool {
+        if index >= self.lines.len() {
+            return false;
+        }
+
+        self.lines.remove(index);
+        self.record_lines(index, 1, 0);
+        self.mark_dirty();
+        true
+    }
+
+    /// Deletes the text of the line after its indentation,
+    /// returns where the indentation ends.
+    pub fn clear_line(&mut self, index: usize) -> Option<GraphemeIndex> {
+        let line = self.lines.get_mut(index)?;
+        let indentation = line.first_non_blank();
+        line.remove_range(indentation..line.grapheme_count());
+        self.mark_dirty();
+        Some(indentation)
+    }
+
+    /// The text from `start` to `end` excluded, a location past
+    /// the end of a line includes its line break.
+    pub fn text_between(&self, start: Location, end: Location) -> String {
+        let mut text = String::new();
+        for line_index in start.line_index..=end.line_index {
+            let Some(line) = self.line(line_index) else {
+                break;
+            };
+
+            let from = if line_index == start.line_index {
+                start.grapheme_index
+            } else {
+                0
+            };
+
+            if line_index == end.line_index {
+                text.push_str(line.get_str(from..end.grapheme_index));
+            } else {
+                text.push_str(line.get_str(from..line.grapheme_count()));
+                text.push('\n');
             }
         }
+        text
+    }
+
+    /// Deletes the text from `start` to `end` excluded, the lines
+    /// in between are removed and the first and last are joined.
+    pub fn delete_between(&mut self, start: Location, end: Location) {
+        if start.line_index == end.line_index {
+            self.delete_range(start.line_index, start.grapheme_index..end.grapheme_index);
+            return;
+        }
+
+        if start.line_index >= self.lines.len() {
+            return;
+        }
+
+        let tail = self
+            .lines
+            .get_mut(end.line_index)
+            .map(|line| line.split_off(end.grapheme_index));
+        let last = cmp::min(end.line_index, self.lines.len().saturating_sub(1));
+
+        let line = &mut self.lines[start.line_index];
+        line.remove_range(start.grapheme_index..line.grapheme_count());
+        if let Some(tail) = &tail {
+            line.append(tail);
+        }
+
+        let removed = start.line_index.saturating_add(1)..last.saturating_add(1);
+        self.record_lines(removed.start, removed.len(), 0);
+        self.lines.drain(removed);
+        self.mark_dirty();
+    }
+
+    /// Inserts the text at the location, a line break in the
+    /// text splits the line. Returns where the text ends.
+    pub fn insert_text(&mut self, at: Location, text: &str) -> Location {
+        if at.line_index >= self.lines.len() {
+            self.lines.push(Line::default());
+            self.record_lines(self.lines.len().saturating_sub(1), 0, 1);
+        }
+
+        let line_index = cmp::min(at.line_index, self.lines.len().saturating_sub(1));
+        let tail = self.lines[line_index].split_off(at.grapheme_index);
+        let mut end = Location {
+            grapheme_index: cmp::min(at.grapheme_index, self.lines[line_index].grapheme_count()),
+            line_index,
+        };
+
+        for (i, part) in text.split('\n').enumerate() {
+            if i > 0 {
+                end.line_index = end.line_index.saturating_add(1);
+                end.grapheme_index = 0;
+                self.lines.insert(end.line_index, Line::default());
+            }
+
+            let line = &mut self.lines[end.line_index];
+            line.insert_str_at(end.grapheme_index, part);
+            end.grapheme_index = line.grapheme_count();
+        }
+
+        self.lines[end.line_index].append(&tail);
+        self.record_lines(
+            line_index.saturating_add(1),
+            0,
+            end.line_index.saturating_sub(line_index),
+        );
+        self.mark_dirty();
+        end
+    }
+
+    /// Inserts the lines of the text before the line at the index.
+    pub fn insert_lines(&mut self, index: usize, text: &str) {
+        let index = cmp::min(index, self.lines.len());
+        let lines: Vec<Line> = text.lines().map(Line::from).collect();
+        self.record_lines(index, 0, lines.len());
+        self.lines.splice(index..index, lines);
+        self.mark_dirty();
+    }
+
+    /// Pads the lines with spaces before the first occurrence of the
+    /// delimiter so that all of them start at the same column, the
+    /// lines without it are left as they are. Returns how many lines
+    /// got padded.
+    pub fn align_lines(&mut self, lines: Range<usize>, delimiter: &str) -> usize {
+        let end = cmp::min(lines.end, self.lines.len());
+        if self.is_read_only() || delimiter.is_empty() || lines.start >= end {
+            return 0;
+        }
+
+        let positions: Vec<(usize, GraphemeIndex, usize)> = (lines.start..end)
+            .filter_map(|index| {
+                let line = &self.lines[index];
+                let at = line.search_forward(delimiter, 0, MatchOptions::default())?;
+                Some((index, at, line.width_until(at)))
+            })
+            .collect();
+        let column = positions
+            .iter()
+            .map(|&(_, _, width)| width)
+            .max()
+            .unwrap_or(0);
+
+        let mut padded: usize = 0;
+        for (index, at, width) in positions {
+            if width < column {
+                let padding = " ".repeat(column.saturating_sub(width));
+                self.lines[index].insert_str_at(at, &padding);
+                padded = padded.saturating_add(1);
+            }
+        }
+
+        if padded > 0 {
+            self.mark_dirty();
+        }
+        padded
+    }
+
+    /// Comments the lines out with the prefix at their indentation, or
+    /// removes it if all the lines that aren't blank already start with it.
+    pub fn toggle_comment(&mut self, lines: Range<usize>, prefix: &str) {
+        let end = cmp::min(lines.end, self.lines.len());
+        if lines.start >= end {
+            return;
+        }
+
+        let is_blank = |line: &Line| line.get_string().trim().is_empty();
+        let commented = self.lines[lines.start..end]
+            .iter()
+            .filter(|line| !is_blank(line))
+            .all(|line| line.get_string().trim_start().starts_with(prefix));
+
+        let prefix_len = Line::from(prefix).grapheme_count();
+        for line in &mut self.lines[lines.start..end] {
+            if is_blank(line) {
+                continue;
+            }
+
+            let indent = line.first_non_blank();
+            if commented {
+                let mut comment_end = indent.saturating_add(prefix_len);
+                if line.grapheme_at(comment_end) == Some(" ") {
+                    comment_end = comment_end.saturating_add(1);
+                }
+                line.remove_range(indent..comment_end);
+            } else {
+                line.insert_str_at(indent, &format!("{prefix} "));
+            }
+        }
+
+        self.mark_dirty();
+    }
+
+    /// Swaps two lines, returns `false` if one of them is
+    /// out of bounds.
+    pub fn swap_lines(&mut self, a: usize, b: usize) -> bool {
+        if a.max(b) >= self.lines.len() {
+            return false;
+        }
+
+        if a != b {
+            self.lines.swap(a, b);
+            self.lines[a].renew();
+            self.lines[b].renew();
+            self.mark_dirty();
+        }
+        true
     }
 
     pub fn insert_newline(&mut self, at: Location) {
-        self.dirty = true;
+        self.mark_dirty();
         if let Some(line) = self.lines.get_mut(at.line_index) {
             let rem = line.split_off(at.grapheme_index);
             self.lines.insert(at.line_index.saturating_add(1), rem);
+            self.record_lines(at.line_index.saturating_add(1), 0, 1);
         } else {
             self.lines.push(Line::default());
+            self.record_lines(self.lines.len().saturating_sub(1), 0, 1);
         }
     }
 
-    pub fn search_forward(&self, needle: &str, start_location: Location) -> Option<Location> {
+    pub fn search_forward(
+        &self,
+        needle: &str,
+        start_location: Location,
+        options: MatchOptions,
+    ) -> Option<Location> {
+        if let Some(large_file) = &self.large_file {
+            return large_file.search_forward(needle, start_location, options);
+        }
+
         let mut is_first = true;
 
         for (i, line) in self
@@ -99,7 +793,7 @@ impl Buffer {
                 0
             };
 
-            if let Some(index) = line.search_forward(needle, start) {
+            if let Some(index) = line.search_forward(needle, start, options) {
                 return Some(Location {
                     grapheme_index: index,
                     line_index: i,
@@ -110,7 +804,16 @@ impl Buffer {
         None
     }
 
-    pub fn search_backwards(&self, needle: &str, start_location: Location) -> Option<Location> {
+    pub fn search_backwards(
+        &self,
+        needle: &str,
+        start_location: Location,
+        options: MatchOptions,
+    ) -> Option<Location> {
+        if let Some(large_file) = &self.large_file {
+            return large_file.search_backwards(needle, start_location, options);
+        }
+
         let mut is_first = true;
 
         for (i, line) in self
@@ -134,7 +837,7 @@ impl Buffer {
                 line.grapheme_count()
             };
 
-            if let Some(index) = line.search_backwards(needle, end) {
+            if let Some(index) = line.search_backwards(needle, end, options) {
                 return Some(Location {
                     grapheme_index: index,
                     line_index: i,
@@ -145,15 +848,567 @@ impl Buffer {
         None
     }
 
+    /// Looks backwards from the location for the `open` delimiter that is
+    /// not closed before it, skipping the nested pairs.
+    pub fn find_enclosing_open(&self, open: &str, close: &str, at: Location) -> Option<Location> {
+        if self.is_read_only() {
+            return None;
+        }
+
+        let mut depth: usize = 0;
+        for line_index in (0..=cmp::min(at.line_index, self.lines.len().saturating_sub(1))).rev() {
+            let line = &self.lines[line_index];
+            let end = if line_index == at.line_index {
+                cmp::min(at.grapheme_index, line.grapheme_count())
+            } else {
+                line.grapheme_count()
+            };
+
+            for grapheme_index in (0..end).rev() {
+                match line.grapheme_at(grapheme_index) {
+                    Some(g) if g == close => depth = depth.saturating_add(1),
+                    Some(g) if g == open && depth == 0 => {
+                        return Some(Location {
+                            grapheme_index,
+                            line_index,
+                        });
+                    }
+                    Some(g) if g == open => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Looks forward from the location for the `close` delimiter that
+    /// was not opened after it, skipping the nested pairs.
+    pub fn find_enclosing_close(&self, open: &str, close: &str, at: Location) -> Option<Location> {
+        if self.is_read_only() {
+            return None;
+        }
+
+        let mut depth: usize = 0;
+        for (line_index, line) in self.lines.iter().enumerate().skip(at.line_index) {
+            let start = if line_index == at.line_index {
+                at.grapheme_index.saturating_add(1)
+            } else {
+                0
+            };
+
+            for grapheme_index in start..line.grapheme_count() {
+                match line.grapheme_at(grapheme_index) {
+                    Some(g) if g == open => depth = depth.saturating_add(1),
+                    Some(g) if g == close && depth == 0 => {
+                        return Some(Location {
+                            grapheme_index,
+                            line_index,
+                        });
+                    }
+                    Some(g) if g == close => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+            }
+        }
+
+        None
+    }
+
+    pub fn stats(&self) -> BufferStats {
+        if let Some(large_file) = &self.large_file {
+            return large_file.stats();
+        }
+
+        let line_ending_len = self.line_ending.as_str().len();
+        let mut stats = BufferStats {
+            lines: self.lines.len(),
+            words: 0,
+            chars: 0,
+            bytes: 0,
+        };
+
+        for line in &self.lines {
+            let string = line.get_string();
+            stats.words = stats
+                .words
+                .saturating_add(string.split_whitespace().count());
+            stats.chars = stats.chars.saturating_add(string.chars().count());
+            stats.bytes = stats
+                .bytes
+                .saturating_add(self.encoding.encoded_len(string))
+                .saturating_add(line_ending_len);
+        }
+        if self.missing_final_newline {
+            stats.bytes = stats.bytes.saturating_sub(line_ending_len);
+        }
+
+        stats
+    }
+
+    pub fn count_matches(&self, needle: &str, options: MatchOptions) -> usize {
+        self.lines
+            .iter()
+            .map(|line| {
+                line.find_all(needle, 0..line.get_string().len(), options)
+                    .len()
+            })
+            .sum()
+    }
+
+    /// The matches of the needle lying entirely between `start`
+    /// and `end` excluded, in the order they appear.
+    pub fn matches_between(
+        &self,
+        needle: &str,
+        start: Location,
+        end: Location,
+        options: MatchOptions,
+    ) -> Vec<Location> {
+        let mut matches = Vec::new();
+        for line_index in start.line_index..=end.line_index {
+            let Some(line) = self.line(line_index) else {
+                break;
+            };
+
+            let from = if line_index == start.line_index {
+                start.grapheme_index
+            } else {
+                0
+            };
+            let to = if line_index == end.line_index {
+                end.grapheme_index
+            } else {
+                line.grapheme_count()
+            };
+
+            matches.extend(
+                line.find_all(needle, line.byte_range(from..to), options)
+                    .into_iter()
+                    .map(|(_, grapheme_index)| Location {
+                        grapheme_index,
+                        line_index,
+                    }),
+            );
+        }
+        matches
+    }
+
+    /// Replaces the matches of the needle between `start` and `end`
+    /// excluded, returns how many were replaced.
+    pub fn replace_between(
+        &mut self,
+        needle: &str,
+        replacement: &str,
+        start: Location,
+        end: Location,
+        options: MatchOptions,
+    ) -> usize {
+        if self.is_read_only() || needle.is_empty() {
+            return 0;
+        }
+
+        let matches = self.matches_between(needle, start, end, options);
+        let needle_len = Line::from(needle).grapheme_count();
+        for location in matches.iter().rev() {
+            if let Some(line) = self.lines.get_mut(location.line_index) {
+                let range =
+                    location.grapheme_index..location.grapheme_index.saturating_add(needle_len);
+                line.replace_range(range, replacement);
+            }
+        }
+
+        if !matches.is_empty() {
+            self.mark_dirty();
+        }
+        matches.len()
+    }
+
+    /// Counts the matches that start before the supplied location.
+    pub fn count_matches_before(&self, needle: &str, at: Location, options: MatchOptions) -> usize {
+        let before: usize = self
+            .lines
+            .iter()
+            .take(at.line_index)
+            .map(|line| {
+                line.find_all(needle, 0..line.get_string().len(), options)
+                    .len()
+            })
+            .sum();
+
+        let on_line = self.lines.get(at.line_index).map_or(0, |line| {
+            line.find_all(needle, 0..line.get_string().len(), options)
+                .iter()
+                .filter(|(_, grapheme_index)| *grapheme_index < at.grapheme_index)
+                .count()
+        });
+
+        before.saturating_add(on_line)
+    }
+
+    /// Marks the buffer as modified, every change also increases
+    /// the revision so cached results about the content can be
+    /// invalidated.
+    fn mark_dirty(&mut self) {
+        self.revision = self.revision.wrapping_add(1);
+    }
+
+    /// Remembers the content as the one on the disk.
+    fn mark_saved(&mut self) {
+        self.saved = SavedContent {
+            lines: self
+                .lines
+                .iter()
+                .map(|line| (line.revision(), line_hash(line)))
+                .collect(),
+            format: self.format(),
+        };
+        self.saved_line_revision = line::last_revision();
+        self.dirty.set(Some((self.revision, false)));
+    }
+
+    /// What changes the saved bytes besides the text of the lines.
+    fn format(&self) -> (LineEnding, Encoding, bool) {
+        (self.line_ending, self.encoding, self.missing_final_newline)
+    }
+
+    fn record_lines(&mut self, start: usize, removed: usize, added: usize) {
+        if removed != added {
+            self.line_edits.push(LineEdit {
+                start,
+                removed,
+                added,
+            });
+        }
+    }
+
+    /// The lines inserted or deleted since the last call, in the order
+    /// they were edited, so that what points at the lines can follow them.
+    pub fn take_line_edits(&mut self) -> Vec<LineEdit> {
+        mem::take(&mut self.line_edits)
+    }
+
+    pub fn revision(&self) -> usize {
+        self.revision
+    }
+
+    /// Whether the line was edited since the file was loaded or saved,
+    /// the lines of a large file are read again and are never touched.
+    pub fn is_touched(&self, index: usize) -> bool {
+        self.large_file.is_none()
+            && self
+                .line(index)
+                .is_some_and(|line| line.revision() > self.saved_line_revision)
+    }
+
     pub fn height(&self) -> usize {
-        self.lines.len()
+        self.large_file
+            .as_ref()
+            .map_or(self.lines.len(), LargeFile::height)
     }
 
     pub fn is_empty(&self) -> bool {
-        self.lines.is_empty()
+        self.height() == 0
     }
 
+    /// Whether the content differs from the one on the disk, so
+    /// that an edit undone by hand leaves the buffer clean. Only
+    /// the lines with a new revision are hashed, and the result
+    /// is kept until the next change.
     pub fn is_dirty(&self) -> bool {
-        self.dirty
+        if self.large_file.is_some() {
+            return false;
+        }
+        if let Some((revision, dirty)) = self.dirty.get()
+            && revision == self.revision
+        {
+            return dirty;
+        }
+
+        let dirty = self.format() != self.saved.format
+            || self.lines.len() != self.saved.lines.len()
+            || self
+                .lines
+                .iter()
+                .zip(&self.saved.lines)
+                .any(|(line, &(revision, hash))| {
+                    line.revision() != revision && line_hash(line) != hash
+                });
+        self.dirty.set(Some((self.revision, dirty)));
+        dirty
+    }
+}
+
+/// Builds a buffer that isn't bound to a file, the line
+/// ending is detected like when loading a file.
+impl FromStr for Buffer {
+    type Err = Infallible;
+
+    fn from_str(content: &str) -> Result<Self, Self::Err> {
+        let lines = content.lines().map(Line::from).collect();
+        let mut buffer = Self::from_lines(lines, LineEnding::detect(content), FileInfo::default());
+        buffer.missing_final_newline = misses_final_newline(content);
+        buffer.mark_saved();
+        Ok(buffer)
+    }
+}
+
+/// Prints the lines as they are saved, each one followed by the
+/// line ending of the file unless the last one has none.
+impl Display for Buffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let line_ending = self.line_ending.as_str();
+        for (index, line) in self.lines.iter().enumerate() {
+            write!(f, "{}", line.get_string())?;
+            if !self.missing_final_newline || index.saturating_add(1) < self.lines.len() {
+                write!(f, "{line_ending}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn line_hash(line: &Line) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    line.get_string().hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Whether the text has a last line that isn't followed by a line ending.
+fn misses_final_newline(content: &str) -> bool {
+    !content.is_empty() && !content.ends_with('\n')
+}
+
+/// How much of `total` is `done`, from 0 to 100.
+pub fn percentage(done: u64, total: u64) -> usize {
+    let percent = done.saturating_mul(100).checked_div(total).unwrap_or(100);
+    usize::try_from(cmp::min(percent, 100)).unwrap_or(100)
+}
+
+pub fn interrupted() -> Error {
+    Error::new(ErrorKind::Interrupted, "Loading was interrupted")
+}
+
+/// Adds `delta` to a number literal keeping its radix prefix, the width
+/// of its leading zeros and the case of its hex digits. Decimal numbers
+/// get their sign back, floats and numbers with `_` are not supported.
+fn increment_literal(literal: &str, negative: bool, delta: i64) -> Option<String> {
+    let radix = match literal.get(..2).map(str::to_ascii_lowercase).as_deref() {
+        Some("0x") => Some(16),
+        Some("0b") => Some(2),
+        Some("0o") => Some(8),
+        _ => None,
+    };
+
+    if let Some(radix) = radix {
+        let (prefix, digits) = literal.split_at(2);
+        let value = u64::from_str_radix(digits, radix)
+            .ok()?
+            .wrapping_add_signed(delta);
+        let width = digits.len();
+        let formatted = match radix {
+            16 if digits.chars().any(|ch| ch.is_ascii_uppercase()) => {
+                format!("{value:0width$X}")
+            }
+            16 => format!("{value:0width$x}"),
+            2 => format!("{value:0width$b}"),
+            _ => format!("{value:0width$o}"),
+        };
+        return Some(format!("{prefix}{formatted}"));
+    }
+
+    let magnitude: i64 = literal.parse().ok()?;
+    let value = if negative {
+        magnitude.checked_neg()?
+    } else {
+        magnitude
+    }
+    .checked_add(delta)?;
+
+    let width = if literal.len() > 1 && literal.starts_with('0') {
+        literal.len()
+    } else {
+        0
+    };
+    let sign = if value < 0 { "-" } else { "" };
+    Some(format!("{sign}{:0width$}", value.unsigned_abs()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::file_type::FileType;
+
+    fn at(line_index: usize, grapheme_index: usize) -> Location {
+        Location {
+            grapheme_index,
+            line_index,
+        }
+    }
+
+    fn buffer(text: &str) -> Buffer {
+        let Ok(buffer) = Buffer::from_str(text);
+        buffer
+    }
+
+    #[test]
+    fn the_text_is_printed_back_as_it_was_read() {
+        for text in [
+            "",
+            "\n",
+            "a\nb\n",
+            "a\r\nb\r\n",
+            "a\nb",
+            "a\r\n\r\nb",
+            "\u{e9}\t\n\n",
+        ] {
+            assert_eq!(buffer(text).to_string(), text);
+        }
+    }
+
+    #[test]
+    fn edits_are_printed_with_the_line_ending_of_the_text() {
+        let mut buffer = buffer("ab\r\ncd");
+        buffer.insert_newline(at(0, 1));
+        buffer.insert_char('x', at(2, 2));
+        assert_eq!(buffer.to_string(), "a\r\nb\r\ncdx");
+    }
+
+    #[test]
+    fn lines_are_swapped_only_inside_the_buffer() {
+        let mut buffer = buffer("a\nb\nc");
+        assert!(buffer.swap_lines(0, 2));
+        assert_eq!(buffer.to_string(), "c\nb\na");
+        assert!(buffer.swap_lines(1, 1));
+        assert!(!buffer.swap_lines(2, 3));
+        assert_eq!(buffer.to_string(), "c\nb\na");
+    }
+
+    #[test]
+    fn a_script_is_typed_by_its_shebang_unless_its_extension_tells() {
+        let dir = std::env::temp_dir();
+        let script = dir.join("beppe-shebang-test");
+        let rust = dir.join("beppe-shebang-test.rs");
+        fs::write(&script, "#!/bin/sh\necho hi\n").unwrap();
+        fs::write(&rust, "#!/bin/sh\n").unwrap();
+        let script_type = Buffer::load(script.to_str().unwrap())
+            .unwrap()
+            .file_info
+            .file_type();
+        let rust_type = Buffer::load(rust.to_str().unwrap())
+            .unwrap()
+            .file_info
+            .file_type();
+        fs::remove_file(&script).unwrap();
+        fs::remove_file(&rust).unwrap();
+
+        assert_eq!(script_type, FileType::Shell);
+        assert_eq!(rust_type, FileType::Rust);
+        assert_eq!(
+            buffer("#!/usr/bin/env python\n").file_info.file_type(),
+            FileType::Python
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_failed_forced_save_leaves_the_file_as_it_was() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir();
+        let path = dir.join(format!("beppe-forced-save-test-{}.txt", std::process::id()));
+        let copy = dir.join(format!(
+            ".beppe-forced-save-test-{}.txt.beppe-save",
+            std::process::id()
+        ));
+        fs::write(&path, "one\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o464)).unwrap();
+        let mut buffer = Buffer::load(path.to_str().unwrap()).unwrap();
+        buffer.insert_char('!', at(0, 3));
+
+        fs::create_dir(&copy).unwrap();
+        let failed = buffer.save_forced();
+        fs::remove_dir(&copy).unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+
+        let saved = buffer.save_forced();
+        let saved_contents = fs::read_to_string(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        fs::remove_file(&path).unwrap();
+
+        assert!(failed.is_err());
+        assert_eq!(contents, "one\n");
+        assert!(saved.is_ok());
+        assert_eq!(saved_contents, "one!\n");
+        assert_eq!(mode & 0o777, 0o464);
+    }
+
+    #[test]
+    fn joining_adds_a_space_only_without_g() {
+        let mut joined = buffer("let s = \"ab\n    cd\";\nx");
+        let mut raw = buffer("let s = \"ab\n    cd\";\nx");
+        assert_eq!(joined.join_lines(0, true), Some(11));
+        assert_eq!(raw.join_lines(0, false), Some(11));
+        assert_eq!(joined.to_string(), "let s = \"ab cd\";\nx");
+        assert_eq!(raw.to_string(), "let s = \"ab    cd\";\nx");
+        assert_eq!(joined.join_lines(1, true), None);
+    }
+
+    #[test]
+    fn joining_adds_no_space_next_to_blanks() {
+        let mut buffer = buffer("a \n  b\n\nc");
+        assert_eq!(buffer.join_lines(0, true), Some(2));
+        assert_eq!(buffer.join_lines(0, true), Some(3));
+        assert_eq!(buffer.to_string(), "a b\nc");
+    }
+
+    #[test]
+    fn an_edit_undone_by_hand_leaves_the_buffer_clean() {
+        let mut buffer = buffer("ab\ncd\n");
+        assert!(!buffer.is_dirty());
+
+        buffer.insert_char('x', at(1, 1));
+        assert!(buffer.is_dirty());
+        buffer.delete(at(1, 1));
+        assert!(!buffer.is_dirty());
+
+        buffer.insert_newline(at(0, 1));
+        assert!(buffer.is_dirty());
+        buffer.delete(at(0, 1));
+        assert!(!buffer.is_dirty());
+    }
+
+    #[test]
+    fn the_line_ending_and_the_final_newline_make_the_buffer_dirty() {
+        let mut buffer = buffer("ab\n");
+        buffer.set_final_newline(false);
+        assert!(buffer.is_dirty());
+        buffer.set_final_newline(true);
+        assert!(!buffer.is_dirty());
+    }
+
+    #[test]
+    fn swapping_lines_back_leaves_the_buffer_clean() {
+        let mut buffer = buffer("ab\ncd\n");
+        buffer.swap_lines(0, 1);
+        assert!(buffer.is_dirty());
+        buffer.swap_lines(0, 1);
+        assert!(!buffer.is_dirty());
+    }
+
+    #[test]
+    fn edited_lines_are_touched_until_the_save() {
+        let mut buffer = buffer("a\nb\nc\n");
+        buffer.insert_newline(at(0, 1));
+        buffer.duplicate_line(2);
+        buffer.swap_lines(2, 3);
+        buffer.swap_lines(4, 2);
+        let touched: Vec<bool> = (0..buffer.height())
+            .map(|index| buffer.is_touched(index))
+            .collect();
+        assert_eq!(touched, [false, true, true, true, true]);
+
+        buffer.mark_saved();
+        assert!((0..buffer.height()).all(|index| !buffer.is_touched(index)));
     }
 }
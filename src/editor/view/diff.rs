@@ -0,0 +1,163 @@
+use std::{cmp, iter};
+
+use crate::editor::{annotated_line::AnnotationType, line::Line};
+
+/// Changed lines bigger than this many pairs are all marked
+/// as modified instead of being compared with each other.
+const MAX_COMPARED_PAIRS: usize = 1_000_000;
+
+/// Rapresents how a line differs from the file on the disk.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LineChange {
+    Added,
+    Modified,
+    /// Lines of the file were removed right above this one,
+    /// or below it if it is the last one.
+    Deleted,
+}
+
+impl LineChange {
+    pub const fn marker(self) -> char {
+        match self {
+            Self::Added => '+',
+            Self::Modified => '~',
+            Self::Deleted => '-',
+        }
+    }
+
+    pub const fn annotation_type(self) -> AnnotationType {
+        match self {
+            Self::Added => AnnotationType::DiffAdded,
+            Self::Modified => AnnotationType::DiffModified,
+            Self::Deleted => AnnotationType::DiffDeleted,
+        }
+    }
+}
+
+/// Compares the lines of the buffer with the ones the file had when
+/// it was loaded or saved, the comparison is only done on request.
+pub struct Diff {
+    original: Vec<String>,
+    changes: Vec<Option<LineChange>>,
+    revision: usize,
+}
+
+impl Diff {
+    pub fn new(lines: &[Line], revision: usize) -> Self {
+        Self {
+            original: lines.iter().map(ToString::to_string).collect(),
+            changes: vec![None; lines.len()],
+            revision,
+        }
+    }
+
+    /// Whether the buffer changed since the last comparison.
+    pub fn is_stale(&self, revision: usize) -> bool {
+        self.revision != revision
+    }
+
+    pub fn update(&mut self, lines: &[Line], revision: usize) {
+        self.changes = compare(&self.original, lines);
+        self.revision = revision;
+    }
+
+    pub fn change(&self, line_index: usize) -> Option<LineChange> {
+        self.changes.get(line_index).copied().flatten()
+    }
+}
+
+/// Marks the changed lines, the lines shared at the start and at the end
+/// are skipped before comparing the others by their longest common subsequence.
+fn compare(original: &[String], current: &[Line]) -> Vec<Option<LineChange>> {
+    let mut changes = vec![None; current.len()];
+
+    let prefix = original
+        .iter()
+        .zip(current)
+        .take_while(|(old, new)| old.as_str() == new.get_string())
+        .count();
+    let suffix = original[prefix..]
+        .iter()
+        .rev()
+        .zip(current[prefix..].iter().rev())
+        .take_while(|(old, new)| old.as_str() == new.get_string())
+        .count();
+
+    let old = &original[prefix..original.len().saturating_sub(suffix)];
+    let new = &current[prefix..current.len().saturating_sub(suffix)];
+    let matches = if old.len().saturating_mul(new.len()) > MAX_COMPARED_PAIRS {
+        Vec::new()
+    } else {
+        common_lines(old, new)
+    };
+
+    let (mut old_index, mut new_index) = (0, 0);
+    for (old_match, new_match) in matches
+        .into_iter()
+        .chain(iter::once((old.len(), new.len())))
+    {
+        let removed = old_match.saturating_sub(old_index);
+        let added = new_match.saturating_sub(new_index);
+
+        for offset in 0..added {
+            let change = if offset < removed {
+                LineChange::Modified
+            } else {
+                LineChange::Added
+            };
+            changes[prefix.saturating_add(new_index).saturating_add(offset)] = Some(change);
+        }
+
+        if removed > added {
+            let below = prefix.saturating_add(new_match);
+            let at = if below < changes.len() {
+                below
+            } else {
+                below.saturating_sub(1)
+            };
+            if let Some(change @ None) = changes.get_mut(at) {
+                *change = Some(LineChange::Deleted);
+            }
+        }
+
+        old_index = old_match.saturating_add(1);
+        new_index = new_match.saturating_add(1);
+    }
+
+    changes
+}
+
+/// The pairs of indices of the lines kept from `old` in `new`.
+fn common_lines(old: &[String], new: &[Line]) -> Vec<(usize, usize)> {
+    let width = new.len().saturating_add(1);
+    let at = |i: usize, j: usize| i.saturating_mul(width).saturating_add(j);
+    let mut lengths = vec![0_usize; old.len().saturating_add(1).saturating_mul(width)];
+
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[at(i, j)] = if old[i] == new[j].get_string() {
+                lengths[at(i.saturating_add(1), j.saturating_add(1))].saturating_add(1)
+            } else {
+                cmp::max(
+                    lengths[at(i.saturating_add(1), j)],
+                    lengths[at(i, j.saturating_add(1))],
+                )
+            };
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j].get_string() {
+            pairs.push((i, j));
+            i = i.saturating_add(1);
+            j = j.saturating_add(1);
+        } else if lengths[at(i.saturating_add(1), j)] >= lengths[at(i, j.saturating_add(1))] {
+            i = i.saturating_add(1);
+        } else {
+            j = j.saturating_add(1);
+        }
+    }
+    pairs
+}
@@ -0,0 +1,84 @@
+use std::{
+    fmt::{self, Display},
+    io::{Error, ErrorKind},
+};
+
+/// Rapresents how the text of a file is stored on the disk.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Hash)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// ISO-8859-1, every byte is the code point of its character.
+    Latin1,
+}
+
+impl Encoding {
+    /// Parses the names accepted by `:set enc=`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "utf8" | "utf-8" => Some(Self::Utf8),
+            "latin1" | "latin-1" | "iso-8859-1" => Some(Self::Latin1),
+            _ => None,
+        }
+    }
+
+    /// Decodes the content of a file, which is read
+    /// as Latin-1 when it isn't valid UTF-8.
+    pub fn decode(bytes: Vec<u8>) -> (String, Self) {
+        match String::from_utf8(bytes) {
+            Ok(text) => (text, Self::Utf8),
+            Err(err) => (
+                err.into_bytes().into_iter().map(char::from).collect(),
+                Self::Latin1,
+            ),
+        }
+    }
+
+    /// Encodes the text, the characters that can't be represented
+    /// make it fail telling where the first one is.
+    pub fn encode(self, text: &str) -> Result<Vec<u8>, Error> {
+        match self {
+            Self::Utf8 => Ok(text.as_bytes().to_vec()),
+            Self::Latin1 => {
+                let unmappable = |ch: &char| u8::try_from(*ch).is_err();
+                let count = text.chars().filter(unmappable).count();
+                if let Some((line, ch)) = text
+                    .lines()
+                    .enumerate()
+                    .find_map(|(index, line)| line.chars().find(unmappable).map(|ch| (index, ch)))
+                {
+                    return Err(Error::new(
+                        ErrorKind::InvalidData,
+                        format!(
+                            "{count} characters can't be saved as {self}, the first is '{ch}' on line {}",
+                            line.saturating_add(1)
+                        ),
+                    ));
+                }
+
+                Ok(text
+                    .chars()
+                    .filter_map(|ch| u8::try_from(ch).ok())
+                    .collect())
+            }
+        }
+    }
+
+    /// The bytes the text takes once encoded.
+    pub fn encoded_len(self, text: &str) -> usize {
+        match self {
+            Self::Utf8 => text.len(),
+            Self::Latin1 => text.chars().count(),
+        }
+    }
+}
+
+impl Display for Encoding {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Utf8 => "UTF-8",
+            Self::Latin1 => "Latin-1",
+        };
+        write!(formatter, "{name}")
+    }
+}
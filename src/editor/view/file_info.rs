@@ -7,7 +7,8 @@ use crate::editor::file_type::FileType;
 
 #[derive(Default, Debug, Clone)]
 pub struct FileInfo {
-    pub file_type: FileType,
+    detected_type: FileType,
+    type_override: Option<FileType>,
     pub path: Option<PathBuf>,
 }
 
@@ -23,10 +24,31 @@ impl FileInfo {
         }
 
         Self {
-            file_type,
+            detected_type: file_type,
+            type_override: None,
             path: Some(path),
         }
     }
+
+    /// Detects the type from the `#!` line of a script,
+    /// only if the path didn't already tell it.
+    pub fn detect_shebang(&mut self, first_line: &str) {
+        if self.detected_type == FileType::PlainText
+            && let Some(file_type) = FileType::from_shebang(first_line)
+        {
+            self.detected_type = file_type;
+        }
+    }
+
+    /// The type set with `:set ft=` if any,
+    /// otherwise the one detected from the path.
+    pub fn file_type(&self) -> FileType {
+        self.type_override.unwrap_or(self.detected_type)
+    }
+
+    pub fn set_file_type(&mut self, file_type: FileType) {
+        self.type_override = Some(file_type);
+    }
 }
 
 impl Display for FileInfo {
@@ -0,0 +1,136 @@
+use std::{cmp, ops::Range};
+
+use crate::editor::view::buffer::LineEdit;
+
+/// Rapresents lines that can be hidden behind their first one,
+/// which is left on the screen to summarize them.
+#[derive(Clone)]
+struct Fold {
+    lines: Range<usize>,
+    closed: bool,
+}
+
+/// Keeps the folds sorted by their first line, a fold can be nested
+/// inside another one. The folds follow the lines the buffer reports
+/// as inserted or deleted.
+#[derive(Default)]
+pub struct Folds {
+    regions: Vec<Fold>,
+}
+
+impl Folds {
+    /// Adds a closed fold over the lines, a fold needs at least two of them.
+    pub fn create(&mut self, lines: Range<usize>) -> bool {
+        if lines.len() < 2 {
+            return false;
+        }
+
+        self.regions.retain(|fold| fold.lines != lines);
+        self.regions.push(Fold {
+            lines,
+            closed: true,
+        });
+        self.regions
+            .sort_by_key(|fold| (fold.lines.start, cmp::Reverse(fold.lines.end)));
+        true
+    }
+
+    /// Opens the closed fold shown on the line, or closes the innermost
+    /// fold containing it. Returns the first line of the fold, if any.
+    pub fn toggle(&mut self, line: usize) -> Option<usize> {
+        if self.closed_at(line).is_some() {
+            self.set_closed(|fold| fold.closed && fold.lines.start == line, false);
+            return Some(line);
+        }
+
+        let innermost = self
+            .regions
+            .iter()
+            .filter(|fold| fold.lines.contains(&line))
+            .min_by_key(|fold| fold.lines.len())
+            .map(|fold| fold.lines.clone())?;
+        self.set_closed(|fold| fold.lines == innermost, true);
+        Some(innermost.start)
+    }
+
+    /// Opens the folds hiding the line, returns `true` if there were any.
+    pub fn reveal(&mut self, line: usize) -> bool {
+        self.set_closed(
+            |fold| fold.closed && fold.lines.start < line && line < fold.lines.end,
+            false,
+        )
+    }
+
+    fn set_closed(&mut self, mut pred: impl FnMut(&Fold) -> bool, closed: bool) -> bool {
+        let mut found = false;
+        for fold in self.regions.iter_mut().filter(|fold| pred(fold)) {
+            fold.closed = closed;
+            found = true;
+        }
+        found
+    }
+
+    /// Moves the folds along the lines inserted or deleted by the edits,
+    /// the folds left with less than two lines are dropped.
+    pub fn sync(&mut self, edits: &[LineEdit], height: usize) {
+        if edits.is_empty() || self.regions.is_empty() {
+            return;
+        }
+
+        for fold in &mut self.regions {
+            for edit in edits {
+                fold.lines = edit.shift(fold.lines.start)..edit.shift(fold.lines.end);
+            }
+        }
+        self.regions
+            .retain(|fold| fold.lines.len() >= 2 && fold.lines.end <= height);
+    }
+
+    /// The outermost closed folds, in order and not overlapping.
+    fn closed(&self) -> impl Iterator<Item = &Range<usize>> {
+        let mut end = 0;
+        self.regions.iter().filter_map(move |fold| {
+            if !fold.closed || fold.lines.start < end {
+                return None;
+            }
+            end = fold.lines.end;
+            Some(&fold.lines)
+        })
+    }
+
+    /// The lines of the closed fold starting on the line, if any.
+    pub fn closed_at(&self, line: usize) -> Option<Range<usize>> {
+        self.closed()
+            .take_while(|lines| lines.start <= line)
+            .find(|lines| lines.start == line)
+            .cloned()
+    }
+
+    /// The row of the screen, counted from the top of the buffer,
+    /// where the line is shown, a hidden line is on its fold's row.
+    pub fn row_of(&self, line: usize) -> usize {
+        let mut hidden = 0_usize;
+        for lines in self.closed() {
+            if lines.start >= line {
+                break;
+            }
+            if line < lines.end {
+                return lines.start.saturating_sub(hidden);
+            }
+            hidden = hidden.saturating_add(lines.len().saturating_sub(1));
+        }
+        line.saturating_sub(hidden)
+    }
+
+    /// The line shown on the row, the opposite of `row_of`.
+    pub fn line_at(&self, row: usize) -> usize {
+        let mut line = row;
+        for lines in self.closed() {
+            if lines.start >= line {
+                break;
+            }
+            line = line.saturating_add(lines.len().saturating_sub(1));
+        }
+        line
+    }
+}
@@ -0,0 +1,66 @@
+/// Columns taken by the spaces and tabs the text starts with,
+/// a tab reaches the next multiple of `tab_width`.
+pub fn width(text: &str, tab_width: usize) -> usize {
+    let mut width: usize = 0;
+    for ch in text.chars() {
+        match ch {
+            ' ' => width = width.saturating_add(1),
+            '\t' => {
+                let to_stop = width
+                    .checked_rem(tab_width)
+                    .map_or(1, |column| tab_width.saturating_sub(column));
+                width = width.saturating_add(to_stop);
+            }
+            _ => break,
+        }
+    }
+    width
+}
+
+/// The indentation reaching the column, made of spaces with
+/// `expand_tab` on or of tabs followed by the spaces left.
+pub fn to_string(width: usize, tab_width: usize, expand_tab: bool) -> String {
+    if expand_tab || tab_width == 0 {
+        return " ".repeat(width);
+    }
+
+    let tabs = width.checked_div(tab_width).unwrap_or(0);
+    let spaces = width.checked_rem(tab_width).unwrap_or(0);
+    format!("{}{}", "\t".repeat(tabs), " ".repeat(spaces))
+}
+
+/// Reindents the lines of a pasted block keeping their indentation
+/// relative to each other: the least indented line lands at `base`.
+/// The first line is pasted after the cursor so only its relative
+/// indentation is kept, blank lines are left empty.
+pub fn reindent(text: &str, base: usize, tab_width: usize, expand_tab: bool) -> String {
+    let least = text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| width(line, tab_width))
+        .min()
+        .unwrap_or(0);
+
+    let mut reindented = String::with_capacity(text.len());
+    for (index, line) in text.split('\n').enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if index > 0 {
+            reindented.push('\n');
+        }
+
+        let content = line.trim_start_matches([' ', '\t']);
+        if content.is_empty() {
+            continue;
+        }
+
+        let relative = width(line, tab_width).saturating_sub(least);
+        let column = if index == 0 {
+            relative
+        } else {
+            base.saturating_add(relative)
+        };
+        reindented.push_str(&to_string(column, tab_width, expand_tab));
+        reindented.push_str(content);
+    }
+    reindented
+}
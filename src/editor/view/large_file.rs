@@ -0,0 +1,212 @@
+use std::{
+    cmp,
+    fs::File,
+    io::{BufRead, BufReader, Error, ErrorKind, Read, Seek, SeekFrom},
+    ops::Range,
+};
+
+use crate::editor::{
+    line::{Line, MatchOptions},
+    view::{
+        Location,
+        buffer::{BufferStats, interrupted, percentage},
+    },
+};
+
+/// How many lines are read at once when going through the whole file.
+const CHUNK_LINES: usize = 4096;
+
+/// Rapresents a file too big to be kept in memory, only the offsets
+/// where its lines start are kept and the lines are read from the
+/// file when they need to be shown.
+pub struct LargeFile {
+    file: File,
+    offsets: Vec<u64>,
+    len: u64,
+}
+
+impl LargeFile {
+    /// Opens the file and scans it once to find where every line starts.
+    pub fn open(file_path: &str, progress: &mut dyn FnMut(usize) -> bool) -> Result<Self, Error> {
+        let file = File::open(file_path)?;
+        let len = file.metadata()?.len();
+
+        let mut offsets = Vec::new();
+        if len > 0 {
+            offsets.push(0);
+        }
+
+        let mut reader = BufReader::with_capacity(64 * 1024, &file);
+        let mut position: u64 = 0;
+        loop {
+            let chunk = reader.fill_buf()?;
+            if chunk.is_empty() {
+                break;
+            }
+
+            for (i, byte) in chunk.iter().enumerate() {
+                if *byte == b'\n' {
+                    let next = position
+                        .saturating_add(u64::try_from(i).unwrap_or(u64::MAX))
+                        .saturating_add(1);
+                    if next < len {
+                        offsets.push(next);
+                    }
+                }
+            }
+
+            let read = chunk.len();
+            position = position.saturating_add(u64::try_from(read).unwrap_or(u64::MAX));
+            reader.consume(read);
+            if !progress(percentage(position, len)) {
+                return Err(interrupted());
+            }
+        }
+
+        Ok(Self { file, offsets, len })
+    }
+
+    pub fn height(&self) -> usize {
+        self.offsets.len()
+    }
+
+    /// Reads the lines in the range, invalid UTF-8 is replaced.
+    pub fn read_lines(&self, range: Range<usize>) -> Result<Vec<Line>, Error> {
+        Ok(self
+            .read_strings(range)?
+            .iter()
+            .map(|string| Line::from(string))
+            .collect())
+    }
+
+    fn read_strings(&self, range: Range<usize>) -> Result<Vec<String>, Error> {
+        let Some(&start) = self.offsets.get(range.start) else {
+            return Ok(Vec::new());
+        };
+        let end = self.offsets.get(range.end).copied().unwrap_or(self.len);
+        let size = usize::try_from(end.saturating_sub(start))
+            .map_err(|_| Error::new(ErrorKind::OutOfMemory, "Lines are too long to be read"))?;
+
+        let mut file = &self.file;
+        file.seek(SeekFrom::Start(start))?;
+        let mut bytes = vec![0; size];
+        file.read_exact(&mut bytes)?;
+
+        Ok(String::from_utf8_lossy(&bytes)
+            .lines()
+            .map(String::from)
+            .collect())
+    }
+
+    /// Iterates over the lines in the range reading them in
+    /// chunks, the chunks that can't be read are skipped.
+    fn lines(&self, range: Range<usize>) -> impl Iterator<Item = (usize, String)> + '_ {
+        range.clone().step_by(CHUNK_LINES).flat_map(move |start| {
+            let end = cmp::min(start.saturating_add(CHUNK_LINES), range.end);
+            self.read_strings(start..end)
+                .unwrap_or_default()
+                .into_iter()
+                .enumerate()
+                .map(move |(i, line)| (start.saturating_add(i), line))
+        })
+    }
+
+    /// Like `lines` but starting from the end of the range.
+    fn lines_rev(&self, range: Range<usize>) -> impl Iterator<Item = (usize, String)> + '_ {
+        range
+            .clone()
+            .rev()
+            .step_by(CHUNK_LINES)
+            .flat_map(move |last| {
+                let end = last.saturating_add(1);
+                let start = cmp::max(end.saturating_sub(CHUNK_LINES), range.start);
+                self.read_strings(start..end)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .enumerate()
+                    .rev()
+                    .map(move |(i, line)| (start.saturating_add(i), line))
+            })
+    }
+
+    /// Reads the file from the location on, wrapping around
+    /// at the end, until a line contains the needle.
+    pub fn search_forward(
+        &self,
+        needle: &str,
+        from: Location,
+        options: MatchOptions,
+    ) -> Option<Location> {
+        let height = self.height();
+        let wrapped = cmp::min(from.line_index.saturating_add(1), height);
+        self.lines(from.line_index..height)
+            .chain(self.lines(0..wrapped))
+            .enumerate()
+            .filter(|(_, (_, string))| Self::contains(string, needle, options))
+            .find_map(|(n, (line_index, string))| {
+                let start = if n == 0 { from.grapheme_index } else { 0 };
+                Line::from(&string)
+                    .search_forward(needle, start, options)
+                    .map(|grapheme_index| Location {
+                        grapheme_index,
+                        line_index,
+                    })
+            })
+    }
+
+    /// Reads the file backwards from the location, wrapping
+    /// around at the start, until a line contains the needle.
+    pub fn search_backwards(
+        &self,
+        needle: &str,
+        from: Location,
+        options: MatchOptions,
+    ) -> Option<Location> {
+        let height = self.height();
+        let first = cmp::min(from.line_index.saturating_add(1), height);
+        self.lines_rev(0..first)
+            .chain(self.lines_rev(from.line_index..height))
+            .enumerate()
+            .filter(|(_, (_, string))| Self::contains(string, needle, options))
+            .find_map(|(n, (line_index, string))| {
+                let line = Line::from(&string);
+                let end = if n == 0 {
+                    from.grapheme_index
+                } else {
+                    line.grapheme_count()
+                };
+                line.search_backwards(needle, end, options)
+                    .map(|grapheme_index| Location {
+                        grapheme_index,
+                        line_index,
+                    })
+            })
+    }
+
+    /// Cheap check made before splitting a line into graphemes.
+    fn contains(string: &str, needle: &str, options: MatchOptions) -> bool {
+        if options.ignore_case {
+            string.to_lowercase().contains(&needle.to_lowercase())
+        } else {
+            string.contains(needle)
+        }
+    }
+
+    pub fn stats(&self) -> BufferStats {
+        let mut stats = BufferStats {
+            lines: self.height(),
+            words: 0,
+            chars: 0,
+            bytes: usize::try_from(self.len).unwrap_or(usize::MAX),
+        };
+
+        for (_, string) in self.lines(0..self.height()) {
+            stats.words = stats
+                .words
+                .saturating_add(string.split_whitespace().count());
+            stats.chars = stats.chars.saturating_add(string.chars().count());
+        }
+
+        stats
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    io::Error,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+    },
+    thread,
+};
+
+use crate::editor::view::buffer::Buffer;
+
+enum LoadEvent {
+    Progress(usize),
+    Loaded(Result<Buffer, Error>),
+}
+
+/// Rapresents how a load on the background went since it was last polled.
+pub enum LoadStatus {
+    Progress(usize),
+    Loaded,
+    Failed(String),
+}
+
+/// Loads a file into a `Buffer` on a background thread,
+/// so that the editor keeps responding while it reads it.
+pub struct Loader {
+    path: String,
+    receiver: Receiver<LoadEvent>,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Loader {
+    pub fn start(path: &str) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let thread_path = path.to_string();
+        let thread_cancelled = Arc::clone(&cancelled);
+        thread::spawn(move || {
+            let mut last = None;
+            let result = Buffer::load_with_progress(&thread_path, &mut |percent| {
+                if last != Some(percent) {
+                    last = Some(percent);
+                    let _ = sender.send(LoadEvent::Progress(percent));
+                }
+                !thread_cancelled.load(Ordering::Relaxed)
+            });
+            let _ = sender.send(LoadEvent::Loaded(result));
+        });
+
+        Self {
+            path: path.to_string(),
+            receiver,
+            cancelled,
+        }
+    }
+
+    /// Collects what the thread sent, the loaded buffer
+    /// is returned together with the status.
+    pub fn poll(&self) -> (Option<LoadStatus>, Option<Buffer>) {
+        let mut status = None;
+        loop {
+            match self.receiver.try_recv() {
+                Ok(LoadEvent::Progress(percent)) => status = Some(LoadStatus::Progress(percent)),
+                Ok(LoadEvent::Loaded(Ok(buffer))) => {
+                    return (Some(LoadStatus::Loaded), Some(buffer));
+                }
+                Ok(LoadEvent::Loaded(Err(_))) | Err(TryRecvError::Disconnected) => {
+                    return (Some(LoadStatus::Failed(self.path.clone())), None);
+                }
+                Err(TryRecvError::Empty) => return (status, None),
+            }
+        }
+    }
+
+    /// Asks the thread to stop, it gives up at the next progress report.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
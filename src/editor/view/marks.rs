@@ -0,0 +1,72 @@
+use std::cmp;
+
+use crate::editor::view::{Location, buffer::LineEdit, selection::Selection};
+
+/// Keeps the places the view goes back to: the text typed during the
+/// last insertion and the last selection. Like the folds they are moved
+/// along the lines the buffer reports as inserted or deleted.
+#[derive(Default)]
+pub struct Marks {
+    insert_start: Option<Location>,
+    insert: Option<(Location, Location)>,
+    selection: Option<(Selection, Location)>,
+}
+
+impl Marks {
+    pub fn start_insert(&mut self, at: Location) {
+        self.insert_start = Some(at);
+    }
+
+    /// Remembers the text typed since `start_insert`, from its start
+    /// to the location the insertion ended at excluded.
+    pub fn end_insert(&mut self, at: Location) {
+        if let Some(start) = self.insert_start.take() {
+            let key = |location: Location| (location.line_index, location.grapheme_index);
+            self.insert = Some(if key(start) <= key(at) {
+                (start, at)
+            } else {
+                (at, start)
+            });
+        }
+    }
+
+    pub fn insert(&self) -> Option<(Location, Location)> {
+        self.insert
+    }
+
+    pub fn set_selection(&mut self, selection: Selection, cursor: Location) {
+        self.selection = Some((selection, cursor));
+    }
+
+    pub fn selection(&self) -> Option<(Selection, Location)> {
+        self.selection
+    }
+
+    /// Moves the marks along the lines inserted or deleted by the edits.
+    pub fn sync(&mut self, edits: &[LineEdit], height: usize) {
+        if edits.is_empty() {
+            return;
+        }
+
+        let shift = |location: &mut Location| {
+            let line = edits
+                .iter()
+                .fold(location.line_index, |line, edit| edit.shift(line));
+            location.line_index = cmp::min(line, height.saturating_sub(1));
+        };
+
+        let insert = self.insert.as_mut().map(|(start, end)| [start, end]);
+        let selection = self
+            .selection
+            .as_mut()
+            .map(|(selection, cursor)| [&mut selection.anchor, cursor]);
+        for location in insert
+            .into_iter()
+            .chain(selection)
+            .flatten()
+            .chain(self.insert_start.as_mut())
+        {
+            shift(location);
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use crate::editor::{config::Config, file_type::FileType};
+
+const MARKER: &str = "beppe:";
+
+/// Rapresents the settings written in a comment on the first or
+/// last line of a file, like `// beppe: tw=4 et ft=rust`.
+#[derive(Default)]
+pub struct Modeline {
+    pub tab_width: Option<usize>,
+    pub expand_tab: Option<bool>,
+    pub file_type: Option<FileType>,
+}
+
+impl Modeline {
+    /// Reads the settings following the `beppe:` marker, unknown
+    /// keys and invalid values are ignored.
+    pub fn parse(line: &str) -> Option<Self> {
+        let (_, settings) = line.split_once(MARKER)?;
+        let mut modeline = Self::default();
+
+        for setting in settings.split_whitespace() {
+            match setting.split_once('=') {
+                Some(("tw" | "tab_width", value)) => {
+                    modeline.tab_width = Config::parse_tab_width(value);
+                }
+                Some(("ft" | "filetype", name)) => modeline.file_type = FileType::from_name(name),
+                None if setting == "et" || setting == "expand_tab" => {
+                    modeline.expand_tab = Some(true);
+                }
+                None if setting == "noet" || setting == "noexpand_tab" => {
+                    modeline.expand_tab = Some(false);
+                }
+                _ => {}
+            }
+        }
+
+        Some(modeline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_settings_after_the_marker_are_read() {
+        let modeline = Modeline::parse("// beppe: tw=4 et ft=rust").unwrap();
+        assert_eq!(modeline.tab_width, Some(4));
+        assert_eq!(modeline.expand_tab, Some(true));
+        assert_eq!(modeline.file_type, Some(FileType::Rust));
+
+        let modeline = Modeline::parse("# beppe: noexpand_tab tab_width=8").unwrap();
+        assert_eq!(modeline.tab_width, Some(8));
+        assert_eq!(modeline.expand_tab, Some(false));
+        assert_eq!(modeline.file_type, None);
+    }
+
+    #[test]
+    fn unknown_keys_and_invalid_values_are_ignored() {
+        let modeline = Modeline::parse("beppe: tw=0 ft=cobol wrap x=1 et").unwrap();
+        assert_eq!(modeline.tab_width, None);
+        assert_eq!(modeline.file_type, None);
+        assert_eq!(modeline.expand_tab, Some(true));
+        assert!(Modeline::parse("// vim: ts=4").is_none());
+    }
+
+    #[test]
+    fn a_wide_tab_width_is_clamped() {
+        let modeline = Modeline::parse("// beppe: tw=4294967295").unwrap();
+        assert_eq!(modeline.tab_width, Some(32));
+    }
+}
@@ -0,0 +1,111 @@
+use std::{cmp, ops::Range};
+
+use crate::editor::{
+    editor_cmd::VisualKind,
+    line::{GraphemeIndex, Line},
+    view::{Location, buffer::Buffer},
+};
+
+/// Rapresents the text selected in visual mode, from the
+/// anchor where it started to the cursor.
+#[derive(Clone, Copy)]
+pub struct Selection {
+    pub anchor: Location,
+    pub kind: VisualKind,
+}
+
+impl Selection {
+    /// The anchor and the cursor in the order they appear in the buffer.
+    fn bounds(self, cursor: Location) -> (Location, Location) {
+        let key = |location: Location| (location.line_index, location.grapheme_index);
+        if key(self.anchor) <= key(cursor) {
+            (self.anchor, cursor)
+        } else {
+            (cursor, self.anchor)
+        }
+    }
+
+    /// The text covered by the selection, from the start included to the
+    /// end excluded. The grapheme under the cursor is part of it, and so
+    /// is the line break when the selection reaches past a line.
+    pub fn span(self, cursor: Location, buffer: &Buffer) -> (Location, Location) {
+        let (start, end) = self.bounds(cursor);
+        let next_line = Location {
+            grapheme_index: 0,
+            line_index: end.line_index.saturating_add(1),
+        };
+
+        match self.kind {
+            VisualKind::Line => (
+                Location {
+                    grapheme_index: 0,
+                    line_index: start.line_index,
+                },
+                next_line,
+            ),
+            VisualKind::Char | VisualKind::Block => {
+                let len = buffer.line(end.line_index).map_or(0, Line::grapheme_count);
+                if end.grapheme_index < len {
+                    (
+                        start,
+                        Location {
+                            grapheme_index: end.grapheme_index.saturating_add(1),
+                            line_index: end.line_index,
+                        },
+                    )
+                } else {
+                    (start, next_line)
+                }
+            }
+        }
+    }
+
+    /// The lines the selection is on.
+    pub fn lines(self, cursor: Location) -> Range<usize> {
+        let (start, end) = self.bounds(cursor);
+        start.line_index..end.line_index.saturating_add(1)
+    }
+
+    /// The columns of a block selection, from the leftmost of the
+    /// anchor and the cursor to the rightmost included.
+    pub fn columns(self, cursor: Location) -> Range<GraphemeIndex> {
+        let left = cmp::min(self.anchor.grapheme_index, cursor.grapheme_index);
+        let right = cmp::max(self.anchor.grapheme_index, cursor.grapheme_index);
+        left..right.saturating_add(1)
+    }
+
+    /// The graphemes of a row that are selected given the `span` of the
+    /// selection, the range ends past the length of the line when its
+    /// line break is selected as well.
+    pub fn row_range(
+        self,
+        cursor: Location,
+        (start, end): (Location, Location),
+        row: usize,
+        len: GraphemeIndex,
+    ) -> Option<Range<GraphemeIndex>> {
+        if self.kind == VisualKind::Block {
+            let columns = self.columns(cursor);
+            let end = cmp::min(columns.end, len);
+            return (self.lines(cursor).contains(&row) && columns.start < end)
+                .then_some(columns.start..end);
+        }
+
+        if row < start.line_index || row > end.line_index {
+            return None;
+        }
+
+        let from = if row == start.line_index {
+            start.grapheme_index
+        } else {
+            0
+        };
+        let to = if row == end.line_index {
+            end.grapheme_index
+        } else {
+            len.saturating_add(1)
+        };
+
+        (from < to).then_some(from..to)
+    }
+}
@@ -0,0 +1,35 @@
+#![warn(
+    clippy::all,
+    clippy::pedantic,
+    clippy::print_stdout,
+    clippy::arithmetic_side_effects,
+    clippy::as_conversions,
+    clippy::integer_division
+)]
+// The types are mainly written for the binary, the lints meant
+// for the documentation of libraries would flag most of them.
+#![allow(
+    clippy::must_use_candidate,
+    clippy::return_self_not_must_use,
+    clippy::missing_errors_doc,
+    clippy::missing_panics_doc
+)]
+
+//! Beppe is a terminal text editor, the types it is built on can
+//! also be used to embed it in other applications.
+//!
+//! The public types are:
+//! - [`Editor`]: the whole editor, driven by the terminal events.
+//! - [`View`]: the text area of the editor, showing a [`Buffer`].
+//! - [`Buffer`]: the lines of a file and the edits made on them.
+//! - [`Line`]: a line of text split into graphemes.
+//!
+//! [`Editor::feed_event`] takes the crossterm [`Event`]s that are
+//! otherwise read from the terminal, which is re-exported here.
+//!
+//! Everything else is internal to the editor.
+
+mod editor;
+
+pub use crossterm::event::Event;
+pub use editor::{Buffer, Editor, Line, View};
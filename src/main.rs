@@ -7,8 +7,7 @@
     clippy::integer_division
 )]
 
-mod editor;
-use editor::Editor;
+use beppe::Editor;
 
 fn main() {
     let mut beppe = Editor::new().unwrap();